@@ -0,0 +1,433 @@
+use crate::backup::write_atomic;
+use crate::challenges::Criterion;
+use crate::events::*;
+use crate::models::*;
+use crate::store::*;
+use crate::webhooks::{self, Webhook};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+
+pub const ALERTS: &str = "alerts.toml";
+pub const DEFAULT_TEXT: &str = "{rating} {species} on {reserve}, score {score}";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum AlertAction {
+    Notify { text: String },
+    Sound,
+    Webhook { name: String },
+    Highlight,
+}
+
+// A rule is a challenge criterion for a single kill and what to do when a
+// stored trophy meets it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlertRule {
+    pub name: String,
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub criterion: Criterion,
+    #[serde(default)]
+    pub actions: Vec<AlertAction>,
+}
+impl Default for AlertRule {
+    fn default() -> Self {
+        AlertRule {
+            name: "".to_string(),
+            enabled: true,
+            criterion: Criterion { mods: Boolean::True, ..Criterion::default() },
+            actions: vec![AlertAction::Notify { text: DEFAULT_TEXT.to_string() }],
+        }
+    }
+}
+impl AlertRule {
+    pub fn matches(&self, trophy: &Trophy) -> bool {
+        self.enabled && self.criterion.for_trophy(trophy)
+    }
+
+    pub fn highlights(&self, trophy: &Trophy) -> bool {
+        self.actions.contains(&AlertAction::Highlight) && self.matches(trophy)
+    }
+
+    pub fn message(&self, trophy: &Trophy) -> String {
+        let text = self.actions.iter().find_map(|a| match a {
+            AlertAction::Notify { text } if !text.trim().is_empty() => Some(text.as_str()),
+            _ => None,
+        });
+        expand(text.unwrap_or(DEFAULT_TEXT), trophy)
+    }
+
+    pub fn problem(&self, rules: &[AlertRule]) -> Option<String> {
+        if self.name.trim().is_empty() {
+            return Some("a rule needs a name".to_string());
+        }
+        if self.actions.is_empty() {
+            return Some("a rule needs at least one action".to_string());
+        }
+        if rules.iter().any(|r| r.name == self.name) {
+            return Some(format!("a rule named {} already exists", self.name));
+        }
+        None
+    }
+
+    pub fn describe(&self) -> String {
+        let c = &self.criterion;
+        let mut parts = Vec::new();
+        if !matches!(c.rating, Rating::All | Rating::Unknown) {
+            parts.push(c.rating.to_string());
+        }
+        if !matches!(c.gender, Gender::All | Gender::Unknown) {
+            parts.push(c.gender.to_string());
+        }
+        parts.push(if c.species.is_real() { c.species.to_string() } else { "any species".to_string() });
+        if !matches!(c.reserve, Reserve::All | Reserve::Unknown) {
+            parts.push(format!("on {}", c.reserve));
+        }
+        if !c.furs.is_empty() {
+            parts.push(format!("with {} fur", c.furs.join(", ")));
+        }
+        if c.score_comparison.is_set(c.score, c.score_upper) {
+            parts.push(format!("score {}", c.score_comparison.describe(c.score, c.score_upper)));
+        }
+        if c.weight_comparison.is_set(c.weight, c.weight_upper) {
+            parts.push(format!("weight {}", c.weight_comparison.describe(c.weight, c.weight_upper)));
+        }
+        parts.join(" ")
+    }
+}
+impl fmt::Display for AlertAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AlertAction::Notify { .. } => write!(f, "Notify"),
+            AlertAction::Sound => write!(f, "Sound"),
+            AlertAction::Webhook { name } => write!(f, "Webhook {}", name),
+            AlertAction::Highlight => write!(f, "Highlight"),
+        }
+    }
+}
+
+// The action checkboxes of the rule editor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActionChoices {
+    pub notify: bool,
+    pub text: String,
+    pub sound: bool,
+    pub webhook: Option<String>,
+    pub highlight: bool,
+}
+impl ActionChoices {
+    pub fn from_actions(actions: &[AlertAction]) -> Self {
+        let mut choices = ActionChoices { notify: false, text: DEFAULT_TEXT.to_string(), sound: false, webhook: None, highlight: false };
+        for action in actions {
+            match action {
+                AlertAction::Notify { text } => {
+                    choices.notify = true;
+                    choices.text = text.clone();
+                }
+                AlertAction::Sound => choices.sound = true,
+                AlertAction::Webhook { name } => choices.webhook = Some(name.clone()),
+                AlertAction::Highlight => choices.highlight = true,
+            }
+        }
+        choices
+    }
+
+    pub fn actions(&self) -> Vec<AlertAction> {
+        let mut actions = Vec::new();
+        if self.notify {
+            actions.push(AlertAction::Notify { text: self.text.clone() });
+        }
+        if self.sound {
+            actions.push(AlertAction::Sound);
+        }
+        if let Some(name) = &self.webhook {
+            actions.push(AlertAction::Webhook { name: name.clone() });
+        }
+        if self.highlight {
+            actions.push(AlertAction::Highlight);
+        }
+        actions
+    }
+}
+
+fn enabled() -> bool {
+    true
+}
+
+pub fn expand(text: &str, trophy: &Trophy) -> String {
+    text.replace("{species}", &trophy.species.to_string())
+        .replace("{reserve}", &trophy.reserve.to_string())
+        .replace("{rating}", &trophy.rating.to_string())
+        .replace("{gender}", &trophy.gender.to_string())
+        .replace("{fur}", &trophy.fur)
+        .replace("{score}", &format!("{:.2}", trophy.score))
+        .replace("{weight}", &format!("{:.1}", trophy.weight))
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct AlertFile {
+    #[serde(default)]
+    rule: Vec<AlertRule>,
+}
+
+pub fn alerts_path(data_dir: &Path) -> PathBuf {
+    data_dir.join(ALERTS)
+}
+
+pub fn load_rules(path: &Path) -> DataResult<Vec<AlertRule>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let invalid = |e: toml::de::Error| DataError::Invalid(format!("{}: {}", path.display(), e));
+    let mut table: toml::Table = toml::from_str(&fs::read_to_string(path)?).map_err(invalid)?;
+    if let Some(toml::Value::Array(rules)) = table.get_mut("rule") {
+        rules.iter_mut().filter_map(|r| r.as_table_mut()).for_each(move_legacy_fur);
+    }
+    let file: AlertFile = table.try_into().map_err(invalid)?;
+    Ok(file.rule)
+}
+
+// Rules saved before alerts used the criterion's furs kept a single fur on
+// the rule itself.
+fn move_legacy_fur(rule: &mut toml::Table) {
+    let Some(toml::Value::String(fur)) = rule.remove("fur") else {
+        return;
+    };
+    if fur.trim().is_empty() {
+        return;
+    }
+    let criterion = rule.entry("criterion").or_insert_with(|| toml::Value::Table(toml::Table::new()));
+    if let Some(criterion) = criterion.as_table_mut() {
+        let furs = criterion.entry("furs").or_insert_with(|| toml::Value::Array(Vec::new()));
+        if let Some(furs) = furs.as_array_mut().filter(|f| f.is_empty()) {
+            furs.push(toml::Value::String(fur.trim().to_string()));
+        }
+    }
+}
+
+// Rules shared between the window that edits them and the thread that acts
+// on them.
+#[derive(Clone)]
+pub struct Alerts {
+    path: PathBuf,
+    rules: Arc<Mutex<Vec<AlertRule>>>,
+}
+impl Alerts {
+    pub fn open(path: &Path) -> DataResult<Alerts> {
+        Ok(Alerts { path: path.to_path_buf(), rules: Arc::new(Mutex::new(load_rules(path)?)) })
+    }
+
+    pub fn empty(path: &Path) -> Alerts {
+        Alerts { path: path.to_path_buf(), rules: Arc::new(Mutex::new(Vec::new())) }
+    }
+
+    pub fn rules(&self) -> Vec<AlertRule> {
+        self.rules.lock().unwrap().clone()
+    }
+
+    pub fn save(&self, rules: Vec<AlertRule>) -> DataResult<()> {
+        let contents = toml::to_string_pretty(&AlertFile { rule: rules.clone() })
+            .map_err(|e| DataError::Invalid(e.to_string()))?;
+        write_atomic(&self.path, contents.as_bytes())?;
+        *self.rules.lock().unwrap() = rules;
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+fn play_sound() {
+    use windows::Win32::System::Diagnostics::Debug::MessageBeep;
+    use windows::Win32::UI::WindowsAndMessaging::MB_ICONASTERISK;
+    unsafe {
+        let _ = MessageBeep(MB_ICONASTERISK);
+    }
+}
+
+#[cfg(not(windows))]
+fn play_sound() {}
+
+#[cfg(windows)]
+fn show_toast(title: &str, message: &str) -> Result<(), String> {
+    notify_rust::Notification::new()
+        .appname("Trophy Lodge")
+        .summary(title)
+        .body(message)
+        .show()
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(not(windows))]
+fn show_toast(_title: &str, _message: &str) -> Result<(), String> {
+    Err("system notifications are only shown on Windows".to_string())
+}
+
+// Runs the actions of every rule a stored trophy meets. Notifications are
+// shown as a system toast from their own thread, and go back on the bus for
+// the window to show instead when that fails. Highlights are drawn by the
+// trophy table.
+pub fn spawn_alerts(alerts: Alerts, webhooks: Vec<Webhook>, events: &EventBus) {
+    let rx = events.subscribe();
+    let events = events.clone();
+    thread::spawn(move || {
+        let agent = ureq::AgentBuilder::new().timeout(Duration::from_secs(10)).build();
+        for event in rx {
+            let Event::TrophyStored { trophy } = event else {
+                continue;
+            };
+            for rule in alerts.rules().iter().filter(|r| r.matches(&trophy)) {
+                let alert = Event::Alert { rule: rule.name.clone(), message: rule.message(&trophy), trophy: trophy.clone() };
+                for action in &rule.actions {
+                    match action {
+                        AlertAction::Notify { .. } => {
+                            let (title, message, alert, events) = (rule.name.clone(), rule.message(&trophy), alert.clone(), events.clone());
+                            thread::spawn(move || {
+                                if show_toast(&title, &message).is_err() {
+                                    events.publish(alert);
+                                }
+                            });
+                        }
+                        AlertAction::Sound => play_sound(),
+                        AlertAction::Webhook { name } => {
+                            let sent = match webhooks.iter().find(|w| w.name == *name) {
+                                Some(webhook) => webhooks::send(&agent, webhook, &alert),
+                                None => Err("it is not in webhooks.toml".to_string()),
+                            };
+                            if let Err(e) = sent {
+                                events.publish(Event::status(format!("Webhook {} for rule {} failed: {}", name, rule.name, e)));
+                            }
+                        }
+                        AlertAction::Highlight => {}
+                    }
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::trophy;
+    use crate::webhooks::PayloadFormat;
+
+    fn diamond_rule() -> AlertRule {
+        let mut rule = AlertRule {
+            name: "Diamonds".to_string(),
+            ..AlertRule::default()
+        };
+        rule.criterion.rating = Rating::Diamond;
+        rule
+    }
+
+    #[test]
+    fn test_matches() {
+        let mut t = trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 250.0);
+        let mut rule = diamond_rule();
+        assert!(!rule.matches(&t));
+        t.rating = Rating::Diamond;
+        assert!(rule.matches(&t));
+        t.mods = Boolean::True;
+        assert!(rule.matches(&t));
+
+        rule.criterion.furs = vec!["albino".to_string()];
+        assert!(!rule.matches(&t));
+        t.fur = "Albino".to_string();
+        assert!(rule.matches(&t));
+
+        rule.criterion.species = Species::All;
+        rule.criterion.reserve = Reserve::LaytonLakeDistrict;
+        assert!(!rule.matches(&t));
+        rule.criterion.reserve = Reserve::All;
+        assert!(rule.matches(&t));
+
+        assert!(!rule.highlights(&t));
+        rule.actions.push(AlertAction::Highlight);
+        assert!(rule.highlights(&t));
+        rule.enabled = false;
+        assert!(!rule.matches(&t));
+    }
+
+    #[test]
+    fn test_message() {
+        let t = trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 250.0);
+        let mut rule = diamond_rule();
+        assert_eq!("Gold Red Deer on Hirschfelden Hunting Reserve, score 250.00", rule.message(&t));
+        rule.actions = vec![AlertAction::Sound, AlertAction::Notify { text: "{fur} {species}!".to_string() }];
+        assert_eq!("Piebald Red Deer!", rule.message(&t));
+    }
+
+    #[test]
+    fn test_describe() {
+        let mut rule = diamond_rule();
+        rule.criterion.furs = vec!["Albino".to_string()];
+        rule.criterion.score = 200.0;
+        assert_eq!("Diamond any species with Albino fur score >= 200", rule.describe());
+        rule.actions = vec![AlertAction::Sound, AlertAction::Webhook { name: "Discord".to_string() }, AlertAction::Highlight];
+        let choices = ActionChoices::from_actions(&rule.actions);
+        assert_eq!(Some("Discord".to_string()), choices.webhook);
+        assert_eq!(rule.actions, choices.actions());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = alerts_path(dir.path());
+        let alerts = Alerts::open(&path).unwrap();
+        assert!(alerts.rules().is_empty());
+        let mut rule = diamond_rule();
+        rule.actions = vec![AlertAction::Sound, AlertAction::Webhook { name: "Discord".to_string() }, AlertAction::Highlight];
+        assert_eq!(None, rule.problem(&alerts.rules()));
+        alerts.save(vec![rule.clone()]).unwrap();
+        assert_eq!(Some("a rule named Diamonds already exists".to_string()), rule.problem(&alerts.rules()));
+        assert_eq!(vec![rule], Alerts::open(&path).unwrap().rules());
+    }
+
+    #[test]
+    fn test_loads_legacy_fur() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = alerts_path(dir.path());
+        fs::write(&path, "[[rule]]\nname = \"Albinos\"\nfur = \" Albino \"\n\n[[rule]]\nname = \"Any\"\nfur = \"\"\n").unwrap();
+        let rules = load_rules(&path).unwrap();
+        assert_eq!(vec!["Albino".to_string()], rules[0].criterion.furs);
+        assert!(rules[1].criterion.furs.is_empty());
+
+        Alerts::empty(&path).save(rules.clone()).unwrap();
+        assert!(!fs::read_to_string(&path).unwrap().contains("\nfur = "));
+        assert_eq!(rules, load_rules(&path).unwrap());
+    }
+
+    #[test]
+    fn test_runs_actions() {
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", server.server_addr().to_ip().unwrap());
+        let webhook = Webhook { name: "Discord".to_string(), url, format: PayloadFormat::Discord, ratings: Vec::new(), furs: Vec::new(), challenge_completed: false };
+        let dir = tempfile::tempdir().unwrap();
+        let alerts = Alerts::empty(&alerts_path(dir.path()));
+        let mut rule = diamond_rule();
+        rule.actions.push(AlertAction::Webhook { name: "Discord".to_string() });
+        alerts.save(vec![rule]).unwrap();
+
+        let events = EventBus::new();
+        let rx = events.subscribe();
+        spawn_alerts(alerts, vec![webhook], &events);
+        let mut t = trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 250.0);
+        t.rating = Rating::Diamond;
+        events.publish(Event::TrophyStored { trophy: t.clone() });
+
+        let mut request = server.recv_timeout(Duration::from_secs(10)).unwrap().unwrap();
+        let mut body = String::new();
+        request.as_reader().read_to_string(&mut body).unwrap();
+        request.respond(tiny_http::Response::empty(204)).unwrap();
+        assert_eq!(r#"{"content":"Diamond Red Deer on Hirschfelden Hunting Reserve, score 250.00"}"#, body);
+
+        assert!(matches!(rx.recv().unwrap(), Event::TrophyStored { .. }));
+        let message = "Diamond Red Deer on Hirschfelden Hunting Reserve, score 250.00".to_string();
+        assert_eq!(Event::Alert { rule: "Diamonds".to_string(), message, trophy: t }, rx.recv_timeout(Duration::from_secs(10)).unwrap());
+    }
+}
@@ -0,0 +1,161 @@
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+const BACKUPS: usize = 3;
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+pub fn backup_path(path: &Path, n: usize) -> PathBuf {
+    with_suffix(path, &format!(".bak{}", n))
+}
+
+pub fn version_backup_path(path: &Path, version: u32) -> PathBuf {
+    with_suffix(path, &format!(".v{}", version))
+}
+
+pub fn backups(path: &Path) -> Vec<PathBuf> {
+    (1..=BACKUPS).map(|n| backup_path(path, n)).filter(|p| p.exists()).collect()
+}
+
+// Shifts .bak1 -> .bak2 and so on, dropping the oldest, then copies the
+// current file into .bak1.
+pub fn rotate_backups(path: &Path) -> io::Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    for n in (1..BACKUPS).rev() {
+        let from = backup_path(path, n);
+        if from.exists() {
+            fs::rename(&from, backup_path(path, n + 1))?;
+        }
+    }
+    fs::copy(path, backup_path(path, 1))?;
+    Ok(())
+}
+
+// Rotates only when the file differs from .bak1, so opening the app without
+// changing anything does not push older backups out.
+pub fn rotate_if_changed(path: &Path) -> io::Result<bool> {
+    if !path.exists() {
+        return Ok(false);
+    }
+    let newest = backup_path(path, 1);
+    if newest.exists() && fs::read(path)? == fs::read(&newest)? {
+        return Ok(false);
+    }
+    rotate_backups(path)?;
+    Ok(true)
+}
+
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let tmp = with_suffix(path, ".tmp");
+    let mut file = File::create(&tmp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    drop(file);
+    rotate_backups(path)?;
+    fs::rename(&tmp, path)
+}
+
+pub fn remove_with_backups(path: &Path) -> io::Result<()> {
+    for file in std::iter::once(path.to_path_buf()).chain(backups(path)) {
+        if file.exists() {
+            fs::remove_file(file)?;
+        }
+    }
+    Ok(())
+}
+
+// Restores the newest valid backup when the file is missing or fails the
+// check. Returns the backup that was used.
+pub fn recover(path: &Path, valid: impl Fn(&Path) -> bool) -> io::Result<Option<PathBuf>> {
+    let tmp = with_suffix(path, ".tmp");
+    if tmp.exists() {
+        fs::remove_file(tmp)?;
+    }
+    if path.exists() && valid(path) {
+        return Ok(None);
+    }
+    for backup in backups(path) {
+        if valid(&backup) {
+            if path.exists() {
+                fs::rename(path, with_suffix(path, ".corrupt"))?;
+            }
+            fs::copy(&backup, path)?;
+            return Ok(Some(backup));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn valid(path: &Path) -> bool {
+        fs::read_to_string(path).is_ok_and(|s| s.starts_with("ok"))
+    }
+
+    #[test]
+    fn test_write_keeps_backups() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("grinds.csv");
+        for i in 1..=5 {
+            write_atomic(&path, format!("ok {}", i).as_bytes()).unwrap();
+        }
+        assert_eq!("ok 5", fs::read_to_string(&path).unwrap());
+        assert_eq!("ok 4", fs::read_to_string(backup_path(&path, 1)).unwrap());
+        assert_eq!("ok 2", fs::read_to_string(backup_path(&path, 3)).unwrap());
+        assert!(!backup_path(&path, 4).exists());
+        assert!(!with_suffix(&path, ".tmp").exists());
+
+        remove_with_backups(&path).unwrap();
+        assert!(!path.exists());
+        assert!(backups(&path).is_empty());
+    }
+
+    #[test]
+    fn test_rotate_if_changed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lodge.db");
+        assert!(!rotate_if_changed(&path).unwrap());
+
+        fs::write(&path, b"ok 1").unwrap();
+        assert!(rotate_if_changed(&path).unwrap());
+        assert!(!rotate_if_changed(&path).unwrap());
+        assert!(!backup_path(&path, 2).exists());
+
+        fs::write(&path, b"ok 2").unwrap();
+        assert!(rotate_if_changed(&path).unwrap());
+        assert_eq!("ok 2", fs::read_to_string(backup_path(&path, 1)).unwrap());
+        assert_eq!("ok 1", fs::read_to_string(backup_path(&path, 2)).unwrap());
+    }
+
+    #[test]
+    fn test_recover() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trophies.csv");
+        assert_eq!(None, recover(&path, valid).unwrap());
+
+        write_atomic(&path, b"ok 1").unwrap();
+        write_atomic(&path, b"ok 2").unwrap();
+        assert_eq!(None, recover(&path, valid).unwrap());
+
+        fs::write(&path, b"broken").unwrap();
+        fs::write(with_suffix(&path, ".tmp"), b"half written").unwrap();
+        assert_eq!(Some(backup_path(&path, 1)), recover(&path, valid).unwrap());
+        assert_eq!("ok 1", fs::read_to_string(&path).unwrap());
+        assert_eq!("broken", fs::read_to_string(with_suffix(&path, ".corrupt")).unwrap());
+        assert!(!with_suffix(&path, ".tmp").exists());
+
+        fs::remove_file(&path).unwrap();
+        assert_eq!(Some(backup_path(&path, 1)), recover(&path, valid).unwrap());
+        assert!(path.exists());
+    }
+}
@@ -0,0 +1,192 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::models::*;
+use crate::challenges::*;
+
+const PRESETS: &[(&str, &str)] = &[
+    ("any_five_diamonds.toml", include_str!("../static/challenges/any_five_diamonds.toml")),
+    ("every_rating.toml", include_str!("../static/challenges/every_rating.toml")),
+    ("great_one.toml", include_str!("../static/challenges/great_one.toml")),
+    ("hirschfelden_tour.toml", include_str!("../static/challenges/hirschfelden_tour.toml")),
+    ("red_deer_gold_rush.toml", include_str!("../static/challenges/red_deer_gold_rush.toml")),
+    ("ten_species.toml", include_str!("../static/challenges/ten_species.toml")),
+    ("whitetail_everywhere.toml", include_str!("../static/challenges/whitetail_everywhere.toml")),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    BuiltIn,
+    File(PathBuf),
+}
+impl Source {
+    pub fn label(&self) -> String {
+        match self {
+            Source::BuiltIn => "Built in".to_string(),
+            Source::File(path) => path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CatalogEntry {
+    pub definition: ChallengeDefinition,
+    pub source: Source,
+}
+
+#[derive(Debug, Default)]
+pub struct Catalog {
+    pub entries: Vec<CatalogEntry>,
+    pub problems: Vec<String>,
+}
+
+pub fn catalog_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join("catalog")
+}
+
+fn parse(name: &str, contents: &str, json: bool) -> Result<ChallengeDefinition, String> {
+    let definition: ChallengeDefinition = if json {
+        serde_json::from_str(contents).map_err(|e| e.to_string())?
+    } else {
+        toml::from_str(contents).map_err(|e| e.to_string())?
+    };
+    match definition.problem() {
+        Some(problem) => Err(problem),
+        None => Ok(definition),
+    }
+    .map_err(|e| format!("{}: {}", name, e))
+}
+
+// The bundled presets followed by every .toml and .json file in the catalog
+// folder. Files that cannot be used are listed as problems.
+pub fn load_catalog(dir: &Path) -> Catalog {
+    let mut catalog = Catalog::default();
+    for (name, contents) in PRESETS {
+        match parse(name, contents, false) {
+            Ok(definition) => catalog.entries.push(CatalogEntry { definition, source: Source::BuiltIn }),
+            Err(e) => catalog.problems.push(e),
+        }
+    }
+    let Ok(entries) = fs::read_dir(dir) else {
+        return catalog;
+    };
+    let mut files: Vec<_> = entries.flatten().map(|e| e.path()).collect();
+    files.sort();
+    for file in files {
+        let json = match file.extension().and_then(|x| x.to_str()) {
+            Some("json") => true,
+            Some("toml") => false,
+            _ => continue,
+        };
+        let name = file.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let definition = fs::read_to_string(&file)
+            .map_err(|e| format!("{}: {}", name, e))
+            .and_then(|s| parse(&name, &s, json));
+        match definition {
+            Ok(definition) => catalog.entries.push(CatalogEntry { definition, source: Source::File(file) }),
+            Err(e) => catalog.problems.push(e),
+        }
+    }
+    catalog
+}
+
+pub struct CatalogFilter {
+    pub species: Species,
+    pub reserve: Reserve,
+    pub rating: Rating,
+    pub text: String,
+}
+impl Default for CatalogFilter {
+    fn default() -> Self {
+        CatalogFilter {
+            species: Species::All,
+            reserve: Reserve::All,
+            rating: Rating::All,
+            text: "".to_string(),
+        }
+    }
+}
+
+fn lives_on(species: Species, reserve: Reserve) -> bool {
+    reserve_species().get(&reserve).is_some_and(|s| s.contains(&species))
+}
+
+// A criterion counts when a kill of the searched species, on the searched
+// reserve or with the searched rating could complete it.
+fn criterion_matches(c: &Criterion, filter: &CatalogFilter) -> bool {
+    let any_reserve = matches!(c.reserve, Reserve::All | Reserve::Unknown);
+    let species = !filter.species.is_real()
+        || c.species == filter.species
+        || (!c.species.is_real() && (any_reserve || lives_on(filter.species, c.reserve)));
+    let reserve = matches!(filter.reserve, Reserve::All | Reserve::Unknown)
+        || c.reserve == filter.reserve
+        || (any_reserve && (!c.species.is_real() || lives_on(c.species, filter.reserve)));
+    let rating = matches!(filter.rating, Rating::All | Rating::Unknown) || c.rating == filter.rating;
+    species && reserve && rating
+}
+
+pub fn search<'a>(catalog: &'a Catalog, filter: &CatalogFilter) -> Vec<&'a CatalogEntry> {
+    let text = filter.text.trim().to_lowercase();
+    catalog
+        .entries
+        .iter()
+        .filter(|e| {
+            text.is_empty()
+                || e.definition.name.to_lowercase().contains(&text)
+                || e.definition.description.to_lowercase().contains(&text)
+        })
+        .filter(|e| e.definition.criteria.iter().any(|c| criterion_matches(c, filter)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(entries: Vec<&CatalogEntry>) -> Vec<String> {
+        entries.iter().map(|e| e.definition.name.clone()).collect()
+    }
+
+    #[test]
+    fn test_presets_are_valid() {
+        let dir = tempfile::tempdir().unwrap();
+        let catalog = load_catalog(dir.path());
+        assert_eq!(Vec::<String>::new(), catalog.problems);
+        assert_eq!(PRESETS.len(), catalog.entries.len());
+        assert!(catalog.entries.iter().all(|e| e.source == Source::BuiltIn));
+    }
+
+    #[test]
+    fn test_user_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("bears.json"), r#"{"name": "Bears", "criteria": [{"species": "BlackBear", "kills": 2}]}"#).unwrap();
+        fs::write(dir.path().join("broken.toml"), "name = \"Broken\"\ncriteria = []\n").unwrap();
+        fs::write(dir.path().join("notes.txt"), "not a challenge").unwrap();
+        let catalog = load_catalog(dir.path());
+        let bears = catalog.entries.last().unwrap();
+        assert_eq!("Bears", bears.definition.name);
+        assert_eq!("bears.json", bears.source.label());
+        assert_eq!(vec!["broken.toml: a challenge needs at least one criterion".to_string()], catalog.problems);
+    }
+
+    #[test]
+    fn test_search() {
+        let dir = tempfile::tempdir().unwrap();
+        let catalog = load_catalog(dir.path());
+        let mut filter = CatalogFilter::default();
+        assert_eq!(PRESETS.len(), search(&catalog, &filter).len());
+
+        filter.species = Species::RedDeer;
+        filter.reserve = Reserve::HirschfeldenHuntingReserve;
+        assert_eq!(vec!["Any Five Diamonds", "Every Rating", "Great One", "Hirschfelden Tour", "Red Deer Gold Rush", "Ten Species"], names(search(&catalog, &filter)));
+
+        filter.species = Species::WhitetailDeer;
+        filter.reserve = Reserve::LaytonLakeDistrict;
+        assert_eq!(vec!["Any Five Diamonds", "Every Rating", "Great One", "Ten Species", "Whitetail Everywhere"], names(search(&catalog, &filter)));
+
+        filter = CatalogFilter::default();
+        filter.rating = Rating::Diamond;
+        assert_eq!(vec!["Any Five Diamonds", "Red Deer Gold Rush"], names(search(&catalog, &filter)));
+        filter.text = "rush".to_string();
+        assert_eq!(vec!["Red Deer Gold Rush"], names(search(&catalog, &filter)));
+    }
+}
@@ -1,17 +1,431 @@
 use crate::models::*;
 use strum::IntoEnumIterator;
 use convert_case::{Case, Casing};
+use chrono::{DateTime, Days, Local, NaiveDate, NaiveTime};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+pub const DEFINITION_FORMAT: u32 = 1;
+
+fn definition_format() -> u32 {
+    DEFINITION_FORMAT
+}
+
+fn is_zero(days: &u32) -> bool {
+    *days == 0
+}
+
+// A shareable challenge. Criteria keep All and Unknown as written, they are
+// only expanded into per species rows when the challenge is started.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChallengeDefinition {
+    #[serde(default = "definition_format")]
+    pub format: u32,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    // Days a run has to complete the challenge, 0 for no time limit.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub days: u32,
+    pub criteria: Vec<Criterion>,
+}
+
+// When a new run of a challenge ends.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Deadline {
+    None,
+    Days(u32),
+    Date(NaiveDate),
+}
+impl Deadline {
+    // The end to store on rows started at start, empty without a deadline. A
+    // date runs until the end of that day.
+    pub fn end(&self, start: &str) -> String {
+        match self {
+            Deadline::Days(days) if *days > 0 => DateTime::parse_from_rfc3339(start)
+                .ok()
+                .and_then(|s| s.checked_add_days(Days::new(*days as u64)))
+                .map(|e| e.to_rfc3339())
+                .unwrap_or_default(),
+            Deadline::Date(day) => day
+                .and_time(NaiveTime::from_hms_opt(23, 59, 59).unwrap())
+                .and_local_timezone(Local)
+                .latest()
+                .map(|d| d.to_rfc3339())
+                .unwrap_or_default(),
+            _ => "".to_string(),
+        }
+    }
+}
+
+// The whole days between a row's start and end, 0 without an end.
+fn days_between(challenge: &Challenge) -> u32 {
+    match (DateTime::parse_from_rfc3339(&challenge.start), DateTime::parse_from_rfc3339(&challenge.end)) {
+        (Ok(start), Ok(end)) if end > start => ((end - start).num_minutes() as f64 / (24.0 * 60.0)).round().max(1.0) as u32,
+        _ => 0,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Criterion {
+    pub species: Species,
+    pub reserve: Reserve,
+    pub rating: Rating,
+    pub gender: Gender,
+    pub weapon: Weapon,
+    pub mods: Boolean,
+    pub shot_damage: u32,
+    pub shot_distance: u32,
+    pub tracking: u32,
+    pub total_shots: u32,
+    pub weight: f32,
+    pub score: f32,
+    pub kills: u32,
+    pub shot_damage_comparison: Comparison,
+    pub shot_damage_upper: u32,
+    pub shot_distance_comparison: Comparison,
+    pub shot_distance_upper: u32,
+    pub tracking_comparison: Comparison,
+    pub tracking_upper: u32,
+    pub weight_comparison: Comparison,
+    pub weight_upper: f32,
+    pub score_comparison: Comparison,
+    pub score_upper: f32,
+    pub furs: Vec<String>,
+    pub collect: Collect,
+    pub ammo: Ammo,
+}
+impl Default for Criterion {
+    fn default() -> Self {
+        Criterion::from(&Challenge::default())
+    }
+}
+impl From<&Challenge> for Criterion {
+    fn from(c: &Challenge) -> Self {
+        Criterion {
+            species: c.species,
+            reserve: c.reserve,
+            rating: c.rating,
+            gender: c.gender,
+            weapon: c.weapon,
+            mods: c.mods,
+            shot_damage: c.shot_damage,
+            shot_distance: c.shot_distance,
+            tracking: c.tracking,
+            total_shots: c.total_shots,
+            weight: c.weight,
+            score: c.score,
+            kills: c.kills,
+            shot_damage_comparison: c.shot_damage_comparison,
+            shot_damage_upper: c.shot_damage_upper,
+            shot_distance_comparison: c.shot_distance_comparison,
+            shot_distance_upper: c.shot_distance_upper,
+            tracking_comparison: c.tracking_comparison,
+            tracking_upper: c.tracking_upper,
+            weight_comparison: c.weight_comparison,
+            weight_upper: c.weight_upper,
+            score_comparison: c.score_comparison,
+            score_upper: c.score_upper,
+            furs: if c.fur.is_empty() { Vec::new() } else { vec![c.fur.clone()] },
+            collect: c.collect,
+            ammo: c.ammo,
+        }
+    }
+}
+impl Criterion {
+    fn to_challenge(&self, name: &str, description: &str) -> Challenge {
+        Challenge {
+            name: name.to_string(),
+            description: description.to_string(),
+            species: self.species,
+            reserve: self.reserve,
+            rating: self.rating,
+            gender: self.gender,
+            weapon: self.weapon,
+            mods: self.mods,
+            shot_damage: self.shot_damage,
+            shot_distance: self.shot_distance,
+            tracking: self.tracking,
+            total_shots: self.total_shots,
+            weight: self.weight,
+            score: self.score,
+            kills: self.kills,
+            kills_remaining: self.kills,
+            shot_damage_comparison: self.shot_damage_comparison,
+            shot_damage_upper: self.shot_damage_upper,
+            shot_distance_comparison: self.shot_distance_comparison,
+            shot_distance_upper: self.shot_distance_upper,
+            tracking_comparison: self.tracking_comparison,
+            tracking_upper: self.tracking_upper,
+            weight_comparison: self.weight_comparison,
+            weight_upper: self.weight_upper,
+            score_comparison: self.score_comparison,
+            score_upper: self.score_upper,
+            collect: self.collect,
+            ammo: self.ammo,
+            ..Challenge::default()
+        }
+    }
+
+    // Whether a single kill meets the criterion, reading All as any.
+    pub fn for_trophy(&self, trophy: &Trophy) -> bool {
+        if !self.furs.is_empty() && !self.furs.iter().any(|f| f.eq_ignore_ascii_case(&trophy.fur)) {
+            return false;
+        }
+        let mut challenge = self.to_challenge("", "");
+        if challenge.species == Species::All {
+            challenge.species = Species::Unknown;
+        }
+        if challenge.reserve == Reserve::All {
+            challenge.reserve = Reserve::Unknown;
+        }
+        if challenge.rating == Rating::All {
+            challenge.rating = Rating::Unknown;
+        }
+        if challenge.gender == Gender::All {
+            challenge.gender = Gender::Unknown;
+        }
+        challenge.for_trophy(trophy)
+    }
+
+    fn weapon_parts(&self) -> Vec<String> {
+        let mut parts = Vec::new();
+        if self.weapon.is_real() {
+            parts.push(format!("with the {}", self.weapon));
+        }
+        if self.ammo.is_real() {
+            parts.push(format!("using {}", self.ammo));
+        }
+        parts
+    }
+
+    fn at(&self, species: Species, reserve: Reserve) -> Criterion {
+        Criterion { species, reserve, ..self.clone() }
+    }
+
+    // the same limits the create form allows
+    fn problem(&self) -> Option<String> {
+        if self.kills == 0 || self.kills > 50 {
+            return Some("kills must be between 1 and 50".to_string());
+        }
+        if self.shot_damage > 100 || self.shot_distance > 1000 || self.tracking > 1000 {
+            return Some("shot damage, shot distance or tracking is out of range".to_string());
+        }
+        if let Some(problem) = weapon_problem(self.weapon, self.ammo) {
+            return Some(problem);
+        }
+        if self.furs.iter().any(|f| f.trim().is_empty()) {
+            return Some("fur names cannot be empty".to_string());
+        }
+        if self.shot_damage_upper > 100 || self.shot_distance_upper > 1000 || self.tracking_upper > 1000 {
+            return Some("shot damage, shot distance or tracking is out of range".to_string());
+        }
+        if !(0.0..=1100.0).contains(&self.score) || !(0.0..=2000.0).contains(&self.weight) {
+            return Some("score or weight is out of range".to_string());
+        }
+        if !(0.0..=1100.0).contains(&self.score_upper) || !(0.0..=2000.0).contains(&self.weight_upper) {
+            return Some("score or weight is out of range".to_string());
+        }
+        let bounds = [
+            ("shot damage", self.shot_damage_comparison, self.shot_damage as f32, self.shot_damage_upper as f32),
+            ("shot distance", self.shot_distance_comparison, self.shot_distance as f32, self.shot_distance_upper as f32),
+            ("tracking", self.tracking_comparison, self.tracking as f32, self.tracking_upper as f32),
+            ("weight", self.weight_comparison, self.weight, self.weight_upper),
+            ("score", self.score_comparison, self.score, self.score_upper),
+        ];
+        for (name, comparison, value, upper) in bounds {
+            if comparison == Comparison::Between && upper < value {
+                return Some(format!("the upper bound of {} is below its lower bound", name));
+            }
+        }
+        if self.species.is_real() && !matches!(self.reserve, Reserve::All | Reserve::Unknown) {
+            let lives_there = reserve_species().get(&self.reserve).is_some_and(|s| s.contains(&self.species));
+            if !lives_there {
+                return Some(format!("{} cannot be found on {}", self.species, self.reserve));
+            }
+        }
+        None
+    }
+}
+impl fmt::Display for Criterion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.collect != Collect::Kills {
+            let mut parts = vec![format!("{} different {}", self.kills, match self.collect {
+                Collect::Species => "species",
+                Collect::Reserve => "reserves",
+                Collect::Fur => "furs",
+                Collect::Rating => "ratings",
+                _ => "genders",
+            })];
+            if self.species.is_real() {
+                parts.push(format!("of {}", self.species));
+            }
+            if !matches!(self.reserve, Reserve::All | Reserve::Unknown) {
+                parts.push(format!("on {}", self.reserve));
+            }
+            parts.extend(self.weapon_parts());
+            return write!(f, "{}", parts.join(" "));
+        }
+        let mut parts = vec![self.kills.to_string()];
+        if self.rating != Rating::Unknown {
+            parts.push(self.rating.to_string());
+        }
+        if self.gender != Gender::Unknown {
+            parts.push(self.gender.to_string());
+        }
+        parts.push(match self.species {
+            Species::All => "of every species".to_string(),
+            Species::Unknown => "of any species".to_string(),
+            s => s.to_string(),
+        });
+        parts.push(match self.reserve {
+            Reserve::All => "on every reserve".to_string(),
+            Reserve::Unknown => "on any reserve".to_string(),
+            r => format!("on {}", r),
+        });
+        if !self.furs.is_empty() {
+            parts.push(format!("with {} fur", self.furs.join(", ")));
+        }
+        parts.extend(self.weapon_parts());
+        write!(f, "{}", parts.join(" "))
+    }
+}
+impl ChallengeDefinition {
+    pub fn problem(&self) -> Option<String> {
+        if self.format > DEFINITION_FORMAT {
+            return Some(format!("{} was made with a newer version of Trophy Lodge", self.name));
+        }
+        if self.name.trim().is_empty() {
+            return Some("a challenge needs a name".to_string());
+        }
+        if self.criteria.is_empty() {
+            return Some("a challenge needs at least one criterion".to_string());
+        }
+        if self.days > 365 {
+            return Some("a challenge cannot run for more than 365 days".to_string());
+        }
+        self.criteria
+            .iter()
+            .enumerate()
+            .find_map(|(i, c)| c.problem().map(|p| format!("criterion {}: {}", i + 1, p)))
+    }
+
+    // The rows to save for a new run of this challenge.
+    pub fn to_challenges(&self) -> Vec<Challenge> {
+        let start = Local::now().to_rfc3339();
+        let end = Deadline::Days(self.days).end(&start);
+        self.criteria
+            .iter()
+            .flat_map(|c| {
+                let mut challenge = c.to_challenge(&self.name, &self.description);
+                challenge.start = start.clone();
+                challenge.end = end.clone();
+                process_furs(&challenge, &c.furs)
+            })
+            .collect()
+    }
+}
+
+fn expands_to(criterion: &Criterion, places: &[(Species, Reserve)]) -> bool {
+    let expanded = process_challenge(&criterion.to_challenge("challenge", ""));
+    expanded.len() == places.len() && expanded.iter().all(|c| places.contains(&(c.species, c.reserve)))
+}
+
+// Folds species and reserves that process_challenge expanded back into All
+// where the rows allow it.
+fn compact(base: &Criterion, mut places: Vec<(Species, Reserve)>) -> Vec<Criterion> {
+    for (species, reserve) in [(Species::All, Reserve::All), (Species::Unknown, Reserve::All), (Species::All, Reserve::Unknown)] {
+        if places.len() > 1 && expands_to(&base.at(species, reserve), &places) {
+            return vec![base.at(species, reserve)];
+        }
+    }
+    let mut criteria = Vec::new();
+    let mut reserves: Vec<Reserve> = places.iter().map(|p| p.1).collect();
+    reserves.dedup();
+    for reserve in reserves {
+        let subset: Vec<(Species, Reserve)> = places.iter().filter(|p| p.1 == reserve).copied().collect();
+        if subset.len() > 1 && expands_to(&base.at(Species::All, reserve), &subset) {
+            criteria.push(base.at(Species::All, reserve));
+            places.retain(|p| p.1 != reserve);
+        }
+    }
+    let mut species: Vec<Species> = Vec::new();
+    for p in &places {
+        if !species.contains(&p.0) {
+            species.push(p.0);
+        }
+    }
+    for s in species {
+        let subset: Vec<(Species, Reserve)> = places.iter().filter(|p| p.0 == s).copied().collect();
+        if subset.len() > 1 && expands_to(&base.at(s, Reserve::All), &subset) {
+            criteria.push(base.at(s, Reserve::All));
+            places.retain(|p| p.0 != s);
+        }
+    }
+    criteria.extend(places.into_iter().map(|(s, r)| base.at(s, r)));
+    criteria
+}
+
+type Places = Vec<(Species, Reserve)>;
+
+// Furs asked for over the same places fold back into one criterion. A row
+// without a fur takes any fur, so it is never folded with named ones.
+fn fold_furs(base: Criterion, furs: Vec<(String, Places)>) -> Vec<Criterion> {
+    let named = |fur: &str| if fur.is_empty() { Vec::new() } else { vec![fur.to_string()] };
+    if furs.len() == 1 || furs.iter().all(|f| !f.0.is_empty() && f.1 == furs[0].1) {
+        let places = furs[0].1.clone();
+        let base = Criterion { furs: furs.into_iter().flat_map(|f| named(&f.0)).collect(), ..base };
+        return compact(&base, places);
+    }
+    furs.into_iter()
+        .flat_map(|(fur, places)| compact(&Criterion { furs: named(&fur), ..base.clone() }, places))
+        .collect()
+}
+
+pub fn into_definition(rows: &[Challenge]) -> ChallengeDefinition {
+    let mut groups: Vec<(Criterion, Vec<(String, Places)>)> = Vec::new();
+    for row in rows {
+        let base = Criterion { furs: Vec::new(), ..Criterion::from(row).at(Species::Unknown, Reserve::Unknown) };
+        let place = (row.species, row.reserve);
+        match groups.iter_mut().find(|g| g.0 == base) {
+            Some(group) => match group.1.iter_mut().find(|f| f.0 == row.fur) {
+                Some(fur) => fur.1.push(place),
+                None => group.1.push((row.fur.clone(), vec![place])),
+            },
+            None => groups.push((base, vec![(row.fur.clone(), vec![place])])),
+        }
+    }
+    ChallengeDefinition {
+        format: DEFINITION_FORMAT,
+        name: rows.first().map(|c| c.name.clone()).unwrap_or_default(),
+        description: rows.first().map(|c| c.description.clone()).unwrap_or_default(),
+        days: rows.first().map(days_between).unwrap_or_default(),
+        criteria: groups.into_iter().flat_map(|(base, furs)| fold_furs(base, furs)).collect(),
+    }
+}
 
 pub fn process_challenge(challenge: &Challenge) -> Vec<Challenge> {
     if !challenge.valid() {
         return Vec::new();
     }
+    // a collection is counted over all of its kills, so it stays one row
+    if challenge.collect != Collect::Kills {
+        let mut c = challenge.clone();
+        if c.species == Species::All {
+            c.species = Species::Unknown;
+        }
+        if c.reserve == Reserve::All {
+            c.reserve = Reserve::Unknown;
+        }
+        return vec![c];
+    }
     let mut challenges = Vec::new();
     if challenge.species == Species::All && challenge.reserve != Reserve::All {
         if challenge.reserve != Reserve::Unknown {
            for s in reserve_species().get(&challenge.reserve).unwrap_or(&Vec::new()) {
                 let mut c = challenge.clone();
-                c.species = s.clone();
+                c.species = *s;
                 challenges.push(c);
            } 
         } else {
@@ -64,30 +478,137 @@ pub fn process_challenge(challenge: &Challenge) -> Vec<Challenge> {
     challenges
 }
 
-pub fn into_summary(challenges: &Vec<Challenge>) -> ChallengeSummary {
+// One row for each fur, each expanded over species and reserves. Without
+// furs the challenge takes any fur.
+pub fn process_furs(challenge: &Challenge, furs: &[String]) -> Vec<Challenge> {
+    if furs.is_empty() {
+        return process_challenge(challenge);
+    }
+    furs.iter()
+        .flat_map(|fur| process_challenge(&Challenge { fur: fur.trim().to_string(), ..challenge.clone() }))
+        .collect()
+}
+
+// The distinct values a collection row has gathered from the stored kills
+// since it started.
+pub fn collected(challenge: &Challenge, trophies: &[Trophy]) -> Vec<String> {
+    let start = DateTime::parse_from_rfc3339(&challenge.start).ok();
+    let mut values: Vec<String> = trophies
+        .iter()
+        .filter(|t| start.is_none_or(|s| DateTime::parse_from_rfc3339(&t.date).is_ok_and(|d| d >= s)))
+        .filter(|t| challenge.for_trophy(t))
+        .filter_map(|t| challenge.collect.value(t))
+        .collect();
+    values.sort();
+    values.dedup();
+    values
+}
+
+// New challenge rows after running the stored trophies through them, and the
+// trophies that counted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryPreview {
+    pub rows: Vec<Challenge>,
+    pub counted: Vec<Trophy>,
+}
+
+// Plays the trophies dated from the rows' start through them oldest first, as
+// if each had been saved while the challenge was running.
+pub fn evaluate_history(rows: &[Challenge], trophies: &[Trophy]) -> HistoryPreview {
+    let mut rows = rows.to_vec();
+    let start = rows.first().and_then(|c| DateTime::parse_from_rfc3339(&c.start).ok());
+    let mut history: Vec<&Trophy> = trophies
+        .iter()
+        .filter(|t| start.is_none_or(|s| DateTime::parse_from_rfc3339(&t.date).is_ok_and(|d| d >= s)))
+        .collect();
+    history.sort_by(|a, b| a.date.cmp(&b.date));
+    let mut values: Vec<Vec<String>> = vec![Vec::new(); rows.len()];
+    let mut counted = Vec::new();
+    for trophy in history {
+        let mut counts = false;
+        for (row, seen) in rows.iter_mut().zip(values.iter_mut()) {
+            if !row.for_trophy(trophy) {
+                continue;
+            }
+            if row.collect == Collect::Kills {
+                if row.kills_remaining > 0 {
+                    row.kills_remaining -= 1;
+                    counts = true;
+                }
+            } else if let Some(value) = row.collect.value(trophy).filter(|v| !seen.contains(v)) {
+                if seen.len() < row.kills as usize {
+                    seen.push(value);
+                    counts = true;
+                }
+            }
+        }
+        if counts {
+            counted.push(trophy.clone());
+        }
+    }
+    HistoryPreview { rows, counted }
+}
+
+// Rows asking for a fur count once, when it has been collected, rather than
+// by kill. Collection rows count the distinct values among the trophies.
+pub fn into_summary(challenges: &Vec<Challenge>, trophies: &[Trophy]) -> ChallengeSummary {
     if challenges.is_empty() {
         return ChallengeSummary::default();
     }
 
     let mut total_kills = 0;
-    let mut total_kills_remaining = 0;
+    let mut kills_completed = 0;
+    let mut furs: Vec<FurProgress> = Vec::new();
+    let mut collected_values: Vec<String> = Vec::new();
     for c in challenges {
-        total_kills += c.kills;
-        total_kills_remaining += c.kills_remaining;
+        if c.collect != Collect::Kills {
+            let values = collected(c, trophies);
+            total_kills += c.kills;
+            kills_completed += (values.len() as u32).min(c.kills);
+            collected_values.extend(values);
+            continue;
+        }
+        if c.fur.is_empty() {
+            total_kills += c.kills;
+            kills_completed += c.kills - c.kills_remaining;
+            continue;
+        }
+        let collected = u32::from(c.kills_remaining == 0);
+        total_kills += 1;
+        kills_completed += collected;
+        match furs.iter_mut().find(|f| f.fur == c.fur) {
+            Some(f) => {
+                f.collected += collected;
+                f.total += 1;
+            }
+            None => furs.push(FurProgress { fur: c.fur.clone(), collected, total: 1 }),
+        }
     }
-    let kills_completed = total_kills - total_kills_remaining;
+
+    let percent = kills_completed as f32 / total_kills as f32;
+    let status = if percent >= 1.0 {
+        ChallengeStatus::Completed
+    } else if !challenges[0].open_at(&Local::now().to_rfc3339()) {
+        ChallengeStatus::Expired
+    } else {
+        ChallengeStatus::Active
+    };
 
     ChallengeSummary {
         name: challenges[0].name.clone().to_case(Case::Title), 
         description: challenges[0].description.clone(), 
         start: challenges[0].start.clone(),
-        percent: kills_completed  as f32 / total_kills as f32,
+        end: challenges[0].end.clone(),
+        status,
+        percent,
         is_deleted: false,
+        furs,
+        collected: collected_values,
     }
 } 
 
-pub fn convert_challenge_name(name: &String) -> String {
-    name.clone().replace(|c: char| !c.is_alphanumeric(), "_").to_lowercase() + ".csv"
+pub fn convert_challenge_name(name: &str) -> String {
+    name.replace(|c: char| !c.is_alphanumeric(), "_").to_lowercase() + ".csv"
 }
 
 pub fn create_challenge_filename(challenge: &Challenge) -> String {
@@ -101,13 +622,15 @@ mod tests {
 
     #[test]
     fn test_all_diamond_challenge() {
-        let mut challenge = Challenge::default();
-        challenge.name = "challenge".to_string();
-        challenge.species = Species::All;
-        challenge.rating = Rating::Diamond;
-        challenge.gender = Gender::Male;
-        challenge.mods = Boolean::True;
-        challenge.kills = 2;
+        let challenge = Challenge {
+            name: "challenge".to_string(),
+            species: Species::All,
+            rating: Rating::Diamond,
+            gender: Gender::Male,
+            mods: Boolean::True,
+            kills: 2,
+            ..Challenge::default()
+        };
         
         let mut expected = Vec::new();
         for s in Species::iter() {
@@ -124,10 +647,12 @@ mod tests {
 
     #[test]
     fn test_diamond_kills() {
-        let mut challenge = Challenge::default();
-        challenge.name = "challenge".to_string();
-        challenge.rating = Rating::Diamond;
-        challenge.kills = 10;
+        let challenge = Challenge {
+            name: "challenge".to_string(),
+            rating: Rating::Diamond,
+            kills: 10,
+            ..Challenge::default()
+        };
         let expected = vec![challenge.clone()];
         let actual = process_challenge(&challenge);
         assert_eq!(expected, actual); 
@@ -135,11 +660,13 @@ mod tests {
 
     #[test]
     fn test_species_on_reserve() {
-        let mut challenge = Challenge::default();
-        challenge.name = "challenge".to_string();
-        challenge.species = Species::WhitetailDeer;
-        challenge.reserve = Reserve::LaytonLakeDistrict;
-        challenge.kills = 10;
+        let challenge = Challenge {
+            name: "challenge".to_string(),
+            species: Species::WhitetailDeer,
+            reserve: Reserve::LaytonLakeDistrict,
+            kills: 10,
+            ..Challenge::default()
+        };
         let expected = vec![challenge.clone()];
         let actual = process_challenge(&challenge);
         assert_eq!(expected, actual);
@@ -147,20 +674,24 @@ mod tests {
 
     #[test]
     fn test_all_species_on_reserve() {
-        let mut challenge = Challenge::default();
-        challenge.name = "challenge".to_string();
-        challenge.species = Species::All;
-        challenge.reserve = Reserve::LaytonLakeDistrict;
-        challenge.kills = 10;
+        let challenge = Challenge {
+            name: "challenge".to_string(),
+            species: Species::All,
+            reserve: Reserve::LaytonLakeDistrict,
+            kills: 10,
+            ..Challenge::default()
+        };
         let mut expected = Vec::new();
         let rs = reserve_species();
         let r_species = rs.get(&challenge.reserve).unwrap();
         for species in r_species  {
-            let mut c = Challenge::default();
-            c.name = "challenge".to_string();
-            c.species = species.clone();
-            c.reserve = Reserve::LaytonLakeDistrict;
-            c.kills = challenge.kills;
+            let c = Challenge {
+                name: "challenge".to_string(),
+                species: *species,
+                reserve: Reserve::LaytonLakeDistrict,
+                kills: challenge.kills,
+                ..Challenge::default()
+            };
             expected.push(c);
         }
         let actual = process_challenge(&challenge);
@@ -169,10 +700,12 @@ mod tests {
 
     #[test]
     fn test_species_on_all_reserves() {
-        let mut challenge = Challenge::default();
-        challenge.name = "challenge".to_string();
-        challenge.species = Species::WhitetailDeer;
-        challenge.reserve = Reserve::All;
+        let challenge = Challenge {
+            name: "challenge".to_string(),
+            species: Species::WhitetailDeer,
+            reserve: Reserve::All,
+            ..Challenge::default()
+        };
 
         let mut expected = Vec::new();
         let reserves = vec![
@@ -195,9 +728,11 @@ mod tests {
 
     #[test]
     fn test_all_reserves_without_species() {
-        let mut challenge = Challenge::default();
-        challenge.name = "challenge".to_string();
-        challenge.reserve = Reserve::All;
+        let challenge = Challenge {
+            name: "challenge".to_string(),
+            reserve: Reserve::All,
+            ..Challenge::default()
+        };
 
         let mut expected = Vec::new();
         for r in Reserve::iter() {
@@ -216,10 +751,12 @@ mod tests {
 
     #[test]
     fn test_all_reserves_all_species() {
-        let mut challenge = Challenge::default();
-        challenge.name = "challenge".to_string();
-        challenge.reserve = Reserve::All;
-        challenge.species = Species::All;
+        let challenge = Challenge {
+            name: "challenge".to_string(),
+            reserve: Reserve::All,
+            species: Species::All,
+            ..Challenge::default()
+        };
 
         let mut expected = Vec::new();
         for r in Reserve::iter() {
@@ -244,8 +781,10 @@ mod tests {
 
     #[test]
     fn test_no_challenge() {
-        let mut challenge = Challenge::default();
-        challenge.kills = 0;
+        let challenge = Challenge {
+            kills: 0,
+            ..Challenge::default()
+        };
         let expected: Vec<Challenge> = Vec::new();
         let actual = process_challenge(&challenge);
         assert_eq!(expected, actual);
@@ -258,10 +797,14 @@ mod tests {
             name: "".to_string(),
             description: "".to_string(),
             start: "".to_string(),
+            end: "".to_string(),
+            status: ChallengeStatus::Active,
             percent: 0.0,
             is_deleted: false,
+            furs: Vec::new(),
+            collected: Vec::new(),
         };
-        let actual = into_summary(&challenges);
+        let actual = into_summary(&challenges, &[]);
         assert_eq!(expected, actual);
     }
 
@@ -271,20 +814,24 @@ mod tests {
         let description = name.clone();
         let start = Local::now().to_rfc3339();
         let mut challenges = Vec::new();
-        let mut challenge_criteria_a = Challenge::default();
-        challenge_criteria_a.species = Species::WhitetailDeer;
-        challenge_criteria_a.reserve = Reserve::LaytonLakeDistrict;
-        challenge_criteria_a.name = name.clone();
-        challenge_criteria_a.description = description.clone();
-        challenge_criteria_a.start = start.clone();
-        challenge_criteria_a.kills_remaining = 0;
-
-        let mut challenge_criteria_b = Challenge::default();
-        challenge_criteria_b.species = Species::WhitetailDeer;
-        challenge_criteria_b.reserve = Reserve::RevontuliCoast;
-        challenge_criteria_b.name = name.clone();
-        challenge_criteria_b.description = description.clone();
-        challenge_criteria_b.start = start.clone();
+        let challenge_criteria_a = Challenge {
+            species: Species::WhitetailDeer,
+            reserve: Reserve::LaytonLakeDistrict,
+            name: name.clone(),
+            description: description.clone(),
+            start: start.clone(),
+            kills_remaining: 0,
+            ..Challenge::default()
+        };
+
+        let challenge_criteria_b = Challenge {
+            species: Species::WhitetailDeer,
+            reserve: Reserve::RevontuliCoast,
+            name: name.clone(),
+            description: description.clone(),
+            start: start.clone(),
+            ..Challenge::default()
+        };
 
         challenges.extend(vec![challenge_criteria_a, challenge_criteria_b]);
 
@@ -292,17 +839,326 @@ mod tests {
             name: name.to_case(Case::Title),
             description,
             start,
+            end: "".to_string(),
+            status: ChallengeStatus::Active,
             percent: 0.5,
             is_deleted: false,
+            furs: Vec::new(),
+            collected: Vec::new(),
         }; 
-        let actual = into_summary(&challenges);
+        let actual = into_summary(&challenges, &[]);
         assert_eq!(expected, actual);
     }
 
+    fn definition(criteria: Vec<Criterion>) -> ChallengeDefinition {
+        ChallengeDefinition {
+            format: DEFINITION_FORMAT,
+            name: "Deer Tour".to_string(),
+            description: "diamonds everywhere".to_string(),
+            days: 0,
+            criteria,
+        }
+    }
+
+    #[test]
+    fn test_definition_round_trip() {
+        let diamonds = Criterion {
+            species: Species::All,
+            reserve: Reserve::LaytonLakeDistrict,
+            rating: Rating::Diamond,
+            ..Criterion::default()
+        };
+        let whitetail = Criterion {
+            species: Species::WhitetailDeer,
+            reserve: Reserve::All,
+            kills: 3,
+            ..Criterion::default()
+        };
+        let anywhere = Criterion {
+            reserve: Reserve::All,
+            gender: Gender::Female,
+            ..Criterion::default()
+        };
+        let everything = Criterion {
+            species: Species::All,
+            reserve: Reserve::All,
+            score: 10.0,
+            ..Criterion::default()
+        };
+        let single = Criterion {
+            species: Species::RedDeer,
+            reserve: Reserve::HirschfeldenHuntingReserve,
+            ..Criterion::default()
+        };
+
+        let def = definition(vec![diamonds, whitetail, anywhere, everything, single]);
+        assert_eq!(None, def.problem());
+        let rows = def.to_challenges();
+        assert!(rows.len() > 100);
+        assert!(rows.iter().all(|c| c.kills_remaining == c.kills && !c.start.is_empty()));
+        assert_eq!(def, into_definition(&rows));
+    }
+
+    #[test]
+    fn test_fur_challenge() {
+        let mut furs = Criterion {
+            species: Species::RedDeer,
+            reserve: Reserve::HirschfeldenHuntingReserve,
+            furs: vec!["Albino".to_string(), "Melanistic".to_string(), "Piebald".to_string()],
+            ..Criterion::default()
+        };
+        let any = Criterion {
+            species: Species::WhitetailDeer,
+            reserve: Reserve::All,
+            kills: 2,
+            ..Criterion::default()
+        };
+        let def = definition(vec![furs.clone(), any]);
+        assert_eq!(None, def.problem());
+        let mut rows = def.to_challenges();
+        assert_eq!(vec!["Albino", "Melanistic", "Piebald"], rows.iter().take(3).map(|c| c.fur.as_str()).collect::<Vec<&str>>());
+        assert_eq!(def, into_definition(&rows));
+
+        let mut t = crate::store::tests::trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 200.0);
+        t.fur = "albino".to_string();
+        assert!(furs.for_trophy(&t));
+        assert_eq!(vec![true, false, false], rows.iter().take(3).map(|c| c.for_trophy(&t)).collect::<Vec<bool>>());
+        t.fur = "Brown".to_string();
+        assert!(!furs.for_trophy(&t));
+
+        rows[0].kills_remaining = 0;
+        rows[2].kills_remaining = 0;
+        let summary = into_summary(&rows[..3].to_vec(), &[]);
+        assert!((summary.percent - 2.0 / 3.0).abs() < 0.001);
+        assert_eq!(FurProgress { fur: "Albino".to_string(), collected: 1, total: 1 }, summary.furs[0]);
+        assert_eq!(FurProgress { fur: "Melanistic".to_string(), collected: 0, total: 1 }, summary.furs[1]);
+
+        furs.furs.push(" ".to_string());
+        assert_eq!(Some("criterion 1: fur names cannot be empty".to_string()), definition(vec![furs]).problem());
+    }
+
+    #[test]
+    fn test_collection_challenge() {
+        let criterion = Criterion {
+            species: Species::All,
+            reserve: Reserve::All,
+            collect: Collect::Rating,
+            kills: 3,
+            ..Criterion::default()
+        };
+        assert_eq!("3 different ratings", criterion.to_string());
+        let rows = definition(vec![criterion]).to_challenges();
+        assert_eq!(1, rows.len());
+        assert_eq!((Species::Unknown, Reserve::Unknown), (rows[0].species, rows[0].reserve));
+
+        let tiers = [Rating::Gold, Rating::Gold, Rating::Diamond, Rating::Unknown];
+        let mut trophies: Vec<Trophy> = tiers.iter().enumerate().map(|(i, rating)| {
+            let mut t = crate::store::tests::trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 200.0 + i as f32);
+            t.rating = *rating;
+            t.date = Local::now().to_rfc3339();
+            t
+        }).collect();
+        assert_eq!(vec!["Diamond".to_string(), "Gold".to_string()], collected(&rows[0], &trophies));
+        let summary = into_summary(&rows, &trophies);
+        assert!((summary.percent - 2.0 / 3.0).abs() < 0.001);
+
+        trophies[1].rating = Rating::Bronze;
+        trophies[1].date = "2020-01-01T00:00:00+00:00".to_string();
+        trophies[3].rating = Rating::Silver;
+        assert_eq!(1.0, into_summary(&rows, &trophies).percent);
+        assert_eq!(vec!["Diamond", "Gold", "Silver"], into_summary(&rows, &trophies).collected);
+    }
+
+    #[test]
+    fn test_evaluate_history() {
+        let criterion = Criterion {
+            species: Species::RedDeer,
+            reserve: Reserve::HirschfeldenHuntingReserve,
+            kills: 2,
+            ..Criterion::default()
+        };
+        let species = Criterion {
+            collect: Collect::Species,
+            kills: 2,
+            ..Criterion::default()
+        };
+        let mut rows = definition(vec![criterion, species]).to_challenges();
+        for row in rows.iter_mut() {
+            row.start = "2024-01-01T00:00:00+00:00".to_string();
+        }
+
+        let kill = |species: Species, date: &str| {
+            let mut t = crate::store::tests::trophy(species, Reserve::HirschfeldenHuntingReserve, 100.0);
+            t.date = date.to_string();
+            t
+        };
+        let trophies = vec![
+            kill(Species::RedDeer, "2024-01-04T00:00:00+00:00"),
+            kill(Species::RedDeer, "2023-12-01T00:00:00+00:00"),
+            kill(Species::RedDeer, "2024-01-02T00:00:00+00:00"),
+            kill(Species::RedDeer, "2024-01-03T00:00:00+00:00"),
+            kill(Species::RoeDeer, "2024-01-05T00:00:00+00:00"),
+            kill(Species::WildBoar, "2024-01-06T00:00:00+00:00"),
+        ];
+        let preview = evaluate_history(&rows, &trophies);
+        let dates: Vec<&str> = preview.counted.iter().map(|t| &t.date[..10]).collect();
+        assert_eq!(vec!["2024-01-02", "2024-01-03", "2024-01-05"], dates);
+        assert_eq!(0, preview.rows[0].kills_remaining);
+        assert_eq!(1.0, into_summary(&preview.rows, &trophies).percent);
+    }
+
+    #[test]
+    fn test_deadline() {
+        let criterion = Criterion {
+            species: Species::RedDeer,
+            kills: 2,
+            ..Criterion::default()
+        };
+        let mut def = definition(vec![criterion]);
+        def.days = 7;
+        let mut rows = def.to_challenges();
+        assert!(!rows[0].end.is_empty());
+        assert_eq!(def, into_definition(&rows));
+
+        let start = "2024-01-01T00:00:00+00:00";
+        assert_eq!("2024-01-08T00:00:00+00:00", Deadline::Days(7).end(start));
+        assert_eq!("", Deadline::Days(0).end(start));
+        assert_eq!("", Deadline::None.end(start));
+        let last_day = Deadline::Date(NaiveDate::from_ymd_opt(2024, 1, 31).unwrap()).end(start);
+        assert!(last_day.starts_with("2024-01-31T23:59:59"));
+        for row in rows.iter_mut() {
+            row.start = start.to_string();
+            row.end = Deadline::Days(7).end(start);
+        }
+
+        let kill = |date: &str| {
+            let mut t = crate::store::tests::trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 100.0);
+            t.date = date.to_string();
+            t
+        };
+        assert!(rows[0].for_trophy(&kill("2024-01-08T00:00:00+00:00")));
+        assert!(!rows[0].for_trophy(&kill("2024-01-08T00:00:01+00:00")));
+        let preview = evaluate_history(&rows, &[kill("2024-01-03T00:00:00+00:00"), kill("2024-01-09T00:00:00+00:00")]);
+        assert_eq!(1, preview.counted.len());
+        let summary = into_summary(&preview.rows, &[]);
+        assert_eq!(ChallengeStatus::Expired, summary.status);
+        assert_eq!(0.5, summary.percent);
+
+        let done = evaluate_history(&rows, &[kill("2024-01-03T00:00:00+00:00"), kill("2024-01-04T00:00:00+00:00")]);
+        assert_eq!(ChallengeStatus::Completed, into_summary(&done.rows, &[]).status);
+        def.days = 400;
+        assert_eq!(Some("a challenge cannot run for more than 365 days".to_string()), def.problem());
+    }
+
+    #[test]
+    fn test_partial_reserve_stays_expanded() {
+        let criterion = Criterion {
+            species: Species::All,
+            reserve: Reserve::LaytonLakeDistrict,
+            ..Criterion::default()
+        };
+        let mut rows = definition(vec![criterion]).to_challenges();
+        rows.truncate(2);
+        let def = into_definition(&rows);
+        assert_eq!(2, def.criteria.len());
+        assert_eq!(rows[1].species, def.criteria[1].species);
+    }
+
+    #[test]
+    fn test_definition_problems() {
+        assert_eq!(Some("a challenge needs at least one criterion".to_string()), definition(vec![]).problem());
+        let criterion = Criterion {
+            kills: 0,
+            ..Criterion::default()
+        };
+        assert_eq!(Some("criterion 1: kills must be between 1 and 50".to_string()), definition(vec![criterion]).problem());
+        let criterion = Criterion {
+            species: Species::Lion,
+            reserve: Reserve::LaytonLakeDistrict,
+            ..Criterion::default()
+        };
+        assert_eq!(Some("criterion 1: Lion cannot be found on Layton Lake District".to_string()), definition(vec![criterion]).problem());
+        let criterion = Criterion {
+            score: 200.0,
+            score_upper: 150.0,
+            score_comparison: Comparison::Between,
+            ..Criterion::default()
+        };
+        assert_eq!(Some("criterion 1: the upper bound of score is below its lower bound".to_string()), definition(vec![criterion]).problem());
+        let criterion = Criterion {
+            weapon: Weapon::Ranger243,
+            ammo: Ammo::Slug12,
+            ..Criterion::default()
+        };
+        assert_eq!(Some("criterion 1: 12 GA Slug cannot be fired from the Ranger .243".to_string()), definition(vec![criterion]).problem());
+        let mut newer = definition(vec![Criterion::default()]);
+        newer.format = DEFINITION_FORMAT + 1;
+        assert!(newer.problem().unwrap().contains("newer version"));
+        newer.format = DEFINITION_FORMAT;
+        newer.name = " ".to_string();
+        assert_eq!(Some("a challenge needs a name".to_string()), newer.problem());
+    }
+
+    #[test]
+    fn test_for_trophy_criteria() {
+        let mut t = crate::store::tests::trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 200.0);
+        t.shot_damage = 75.0;
+        t.shot_distance = 150.0;
+        t.tracking = 300.0;
+        t.weapon = Weapon::Ranger243;
+        t.ammo = Ammo::PolymerTip243;
+        assert!(Challenge::default().for_trophy(&t));
+
+        let check = |edit: &dyn Fn(&mut Challenge), expected: bool| {
+            let mut challenge = Challenge::default();
+            edit(&mut challenge);
+            assert_eq!(expected, challenge.for_trophy(&t), "{:?}", challenge);
+        };
+        check(&|c| c.species = Species::RedDeer, true);
+        check(&|c| c.species = Species::RoeDeer, false);
+        check(&|c| c.reserve = Reserve::HirschfeldenHuntingReserve, true);
+        check(&|c| c.reserve = Reserve::LaytonLakeDistrict, false);
+        check(&|c| c.rating = Rating::Gold, true);
+        check(&|c| c.rating = Rating::Diamond, false);
+        check(&|c| c.gender = Gender::Male, true);
+        check(&|c| c.gender = Gender::Female, false);
+        check(&|c| c.weapon = Weapon::Ranger243, true);
+        check(&|c| c.weapon = Weapon::Whitlock3030, false);
+        check(&|c| c.ammo = Ammo::PolymerTip243, true);
+        check(&|c| c.ammo = Ammo::SoftPoint243, false);
+
+        check(&|c| c.shot_damage = 70, true);
+        check(&|c| c.shot_damage = 80, false);
+        check(&|c| { c.shot_damage = 70; c.shot_damage_comparison = Comparison::AtMost }, false);
+        check(&|c| { c.shot_damage = 70; c.shot_damage_upper = 80; c.shot_damage_comparison = Comparison::Between }, true);
+        check(&|c| c.shot_distance = 100, true);
+        check(&|c| c.shot_distance = 200, false);
+        check(&|c| { c.shot_distance = 200; c.shot_distance_comparison = Comparison::AtMost }, true);
+        check(&|c| { c.shot_distance = 160; c.shot_distance_upper = 200; c.shot_distance_comparison = Comparison::Between }, false);
+        check(&|c| c.tracking = 400, true);
+        check(&|c| c.tracking = 200, false);
+        check(&|c| { c.tracking = 200; c.tracking_comparison = Comparison::AtLeast }, true);
+        check(&|c| { c.tracking_upper = 250; c.tracking_comparison = Comparison::Between }, false);
+        check(&|c| c.score = 150.0, true);
+        check(&|c| c.score = 250.0, false);
+        check(&|c| { c.score = 250.0; c.score_comparison = Comparison::AtMost }, true);
+        check(&|c| { c.score = 150.0; c.score_upper = 200.0; c.score_comparison = Comparison::Between }, true);
+        check(&|c| c.weight = 180.0, true);
+        check(&|c| c.weight = 181.0, false);
+        check(&|c| { c.weight = 180.0; c.weight_comparison = Comparison::AtMost }, false);
+        check(&|c| { c.weight = 100.0; c.weight_upper = 180.0; c.weight_comparison = Comparison::Between }, false);
+
+        check(&|c| c.mods = Boolean::True, true);
+        t.mods = Boolean::True;
+        assert!(!Challenge::default().for_trophy(&t));
+    }
+
     #[test]
     fn test_challenge_filename() {
-        let mut challenge = Challenge::default();
-        challenge.name = "My_test-challenge for you".to_string();
+        let challenge = Challenge {
+            name: "My_test-challenge for you".to_string(),
+            ..Challenge::default()
+        };
         let expected = "my_test_challenge_for_you.csv".to_string();
         let actual = create_challenge_filename(&challenge);
         assert_eq!(expected, actual);
@@ -0,0 +1,508 @@
+use crate::data;
+use crate::models::*;
+use crate::store::*;
+use crate::game_monitor::MonitorMode;
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use strum::IntoEnumIterator;
+
+#[derive(Parser)]
+#[command(name = "trophy_lodge", version, about = "Trophy Lodge for theHunter: Call of the Wild")]
+pub struct Cli {
+    #[arg(long, global = true, default_value = data::DEFAULT_DATA_DIR)]
+    pub data_dir: PathBuf,
+    #[arg(long, conflicts_with = "replay")]
+    pub record: Option<PathBuf>,
+    #[arg(long)]
+    pub replay: Option<PathBuf>,
+    /// Serve trophies, grinds, challenges and live events as JSON on localhost while the app runs
+    #[arg(long, value_name = "PORT", num_args = 0..=1, default_missing_value = "7878")]
+    pub api: Option<u16>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+impl Cli {
+    pub fn monitor_mode(&self) -> MonitorMode {
+        match (&self.record, &self.replay) {
+            (Some(path), _) => MonitorMode::Record(path.clone()),
+            (_, Some(path)) => MonitorMode::Replay(path.clone()),
+            _ => MonitorMode::Live,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// List trophies using the same filters as the trophy table
+    Trophies(TrophyArgs),
+    /// Show grind progress
+    Grinds(FormatArgs),
+    /// Show challenge progress
+    Challenges(FormatArgs),
+    /// Create, pause, resume or delete a grind
+    Grind {
+        #[command(subcommand)]
+        action: GrindAction,
+    },
+    /// Share a challenge as a toml or json file, or start one from such a file
+    Challenge {
+        #[command(subcommand)]
+        action: ChallengeAction,
+    },
+    /// Count trophies, diamonds and great ones per reserve, or per species of one reserve
+    Summary {
+        #[arg(long, value_parser = parse_variant::<Reserve>)]
+        reserve: Option<Reserve>,
+        #[command(flatten)]
+        format: FormatArgs,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum GrindAction {
+    /// Start a new grind that counts kills of a species on a reserve, optionally only with a weapon or ammo
+    Create {
+        name: String,
+        #[arg(long, value_parser = parse_variant::<Species>)]
+        species: Species,
+        #[arg(long, value_parser = parse_variant::<Reserve>)]
+        reserve: Reserve,
+        #[arg(long, value_parser = parse_variant::<Weapon>)]
+        weapon: Option<Weapon>,
+        #[arg(long, value_parser = parse_variant::<Ammo>)]
+        ammo: Option<Ammo>,
+    },
+    /// Stop counting kills for a grind
+    Pause { name: String },
+    /// Count kills for a paused grind again
+    Resume { name: String },
+    /// Delete a grind and its kills
+    Delete { name: String },
+}
+
+#[derive(Subcommand)]
+pub enum ChallengeAction {
+    /// Write a challenge definition, to the shared folder unless an output is given
+    Export {
+        name: String,
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Start a challenge from a definition file
+    Import {
+        file: PathBuf,
+        #[arg(long, value_parser = parse_variant::<data::OnConflict>, default_value = "cancel")]
+        on_conflict: data::OnConflict,
+    },
+}
+
+#[derive(Args)]
+pub struct TrophyArgs {
+    #[arg(long, value_parser = parse_variant::<Species>)]
+    species: Option<Species>,
+    #[arg(long, value_parser = parse_variant::<Reserve>)]
+    reserve: Option<Reserve>,
+    #[arg(long, value_parser = parse_variant::<Rating>)]
+    rating: Option<Rating>,
+    #[arg(long, value_parser = parse_variant::<Gender>)]
+    gender: Option<Gender>,
+    #[arg(long, value_parser = parse_variant::<Weapon>)]
+    weapon: Option<Weapon>,
+    #[arg(long, value_parser = parse_variant::<Ammo>)]
+    ammo: Option<Ammo>,
+    #[arg(long)]
+    grind: Option<String>,
+    #[arg(long, value_parser = parse_variant::<SortBy>, default_value = "date")]
+    sort_by: SortBy,
+    #[arg(long)]
+    limit: Option<usize>,
+    #[command(flatten)]
+    format: FormatArgs,
+}
+impl TrophyArgs {
+    fn filter(&self) -> TrophyFilter {
+        TrophyFilter {
+            species: self.species.unwrap_or(Species::All),
+            reserve: self.reserve.unwrap_or(Reserve::All),
+            rating: self.rating.unwrap_or(Rating::All),
+            gender: self.gender.unwrap_or(Gender::All),
+            weapon: self.weapon.unwrap_or(Weapon::All),
+            ammo: self.ammo.unwrap_or(Ammo::All),
+            grind: self.grind.clone().unwrap_or_default(),
+            sort_by: self.sort_by,
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct FormatArgs {
+    #[arg(long, value_enum, default_value_t = Format::Table)]
+    format: Format,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, ValueEnum)]
+pub enum Format {
+    Table,
+    Json,
+    Csv,
+}
+
+fn normalize(value: &str) -> String {
+    value.chars().filter(|c| c.is_alphanumeric()).flat_map(|c| c.to_lowercase()).collect()
+}
+
+// Matches the displayed name of a variant, ignoring case, spaces and
+// punctuation, so "red-deer", "Red Deer" and "reddeer" all work.
+pub fn parse_variant<T: IntoEnumIterator + fmt::Display>(value: &str) -> Result<T, String> {
+    let wanted = normalize(value);
+    T::iter()
+        .find(|v| normalize(&v.to_string()) == wanted)
+        .ok_or_else(|| format!("expected one of: {}", T::iter().map(|v| v.to_string()).collect::<Vec<String>>().join(", ")))
+}
+
+#[derive(Serialize)]
+struct TrophyRow {
+    date: String,
+    species: String,
+    reserve: String,
+    rating: String,
+    score: f32,
+    weight: f32,
+    fur: String,
+    gender: String,
+    shot_distance: f32,
+    weapon: String,
+    grind: String,
+}
+impl From<&Trophy> for TrophyRow {
+    fn from(t: &Trophy) -> Self {
+        TrophyRow {
+            date: t.date.clone(),
+            species: t.species.to_string(),
+            reserve: t.reserve.to_string(),
+            rating: t.rating.to_string(),
+            score: t.score,
+            weight: t.weight,
+            fur: t.fur.clone(),
+            gender: t.gender.to_string(),
+            shot_distance: t.shot_distance,
+            weapon: t.weapon.to_string(),
+            grind: t.grind.clone().unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct GrindRow {
+    name: String,
+    species: String,
+    reserve: String,
+    weapon: String,
+    ammo: String,
+    active: bool,
+    start: String,
+    kills: i64,
+}
+
+#[derive(Serialize)]
+struct ChallengeRow {
+    name: String,
+    description: String,
+    start: String,
+    end: String,
+    status: String,
+    percent: u32,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct SummaryRow {
+    name: String,
+    trophies: usize,
+    diamonds: usize,
+    great_ones: usize,
+}
+impl SummaryRow {
+    fn new(name: String, trophies: &[&Trophy]) -> Self {
+        SummaryRow {
+            name,
+            trophies: trophies.len(),
+            diamonds: trophies.iter().filter(|t| t.rating == Rating::Diamond).count(),
+            great_ones: trophies.iter().filter(|t| t.rating == Rating::GreatOne).count(),
+        }
+    }
+}
+
+fn summary(trophies: &[Trophy], reserve: Option<Reserve>) -> Vec<SummaryRow> {
+    match reserve {
+        Some(reserve) => Species::iter()
+            .map(|s| {
+                let matched: Vec<&Trophy> = trophies.iter().filter(|t| t.reserve == reserve && t.species == s).collect();
+                SummaryRow::new(s.to_string(), &matched)
+            })
+            .filter(|r| r.trophies > 0)
+            .collect(),
+        None => Reserve::iter()
+            .map(|r| {
+                let matched: Vec<&Trophy> = trophies.iter().filter(|t| t.reserve == r).collect();
+                SummaryRow::new(r.to_string(), &matched)
+            })
+            .filter(|r| r.trophies > 0)
+            .collect(),
+    }
+}
+
+fn to_csv<T: Serialize>(rows: &[T]) -> DataResult<Vec<u8>> {
+    let mut wtr = csv::Writer::from_writer(Vec::new());
+    for row in rows {
+        wtr.serialize(row)?;
+    }
+    Ok(wtr.into_inner().map_err(|e| e.into_error())?)
+}
+
+// Lays the csv form of the rows out in aligned columns.
+fn to_table<T: Serialize>(rows: &[T]) -> DataResult<String> {
+    let csv = to_csv(rows)?;
+    let mut rdr = csv::ReaderBuilder::new().has_headers(false).from_reader(csv.as_slice());
+    let records: Vec<csv::StringRecord> = rdr.records().collect::<Result<_, _>>()?;
+    let mut widths: Vec<usize> = Vec::new();
+    for record in &records {
+        for (i, field) in record.iter().enumerate() {
+            let width = field.chars().count();
+            match widths.get_mut(i) {
+                Some(w) => *w = (*w).max(width),
+                None => widths.push(width),
+            }
+        }
+    }
+    let mut table = String::new();
+    for record in &records {
+        let line: Vec<String> = record.iter().zip(&widths).map(|(f, w)| format!("{:<w$}", f, w = w)).collect();
+        table.push_str(line.join("  ").trim_end());
+        table.push('\n');
+    }
+    Ok(table)
+}
+
+fn write_rows<T: Serialize>(out: &mut dyn Write, format: Format, rows: &[T]) -> DataResult<()> {
+    match format {
+        Format::Table => out.write_all(to_table(rows)?.as_bytes())?,
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut *out, rows)?;
+            writeln!(out)?;
+        }
+        Format::Csv => out.write_all(&to_csv(rows)?)?,
+    }
+    Ok(())
+}
+
+pub fn run(command: &Command, store: &dyn TrophyStore, data_dir: &Path, out: &mut dyn Write) -> DataResult<()> {
+    match command {
+        Command::Trophies(args) => {
+            let mut trophies = data::filter_data(&args.filter(), store.read_trophies()?);
+            if let Some(limit) = args.limit {
+                trophies.truncate(limit);
+            }
+            match args.format.format {
+                Format::Table => write_rows(out, Format::Table, &trophies.iter().map(TrophyRow::from).collect::<Vec<_>>()),
+                format => write_rows(out, format, &trophies),
+            }
+        }
+        Command::Grinds(args) => {
+            let rows: Vec<GrindRow> = store.read_grinds()?
+                .into_iter()
+                .map(|g| GrindRow {
+                    name: g.name,
+                    species: g.species.to_string(),
+                    reserve: g.reserve.to_string(),
+                    weapon: g.weapon.to_string(),
+                    ammo: g.ammo.to_string(),
+                    active: g.active,
+                    start: g.start,
+                    kills: g.kills,
+                })
+                .collect();
+            write_rows(out, args.format, &rows)
+        }
+        Command::Challenges(args) => {
+            let rows: Vec<ChallengeRow> = data::get_challenges(store)?
+                .into_iter()
+                .map(|c| ChallengeRow {
+                    name: c.name,
+                    description: c.description,
+                    start: c.start,
+                    end: c.end,
+                    status: c.status.to_string(),
+                    percent: (c.percent * 100.0).round() as u32,
+                })
+                .collect();
+            write_rows(out, args.format, &rows)
+        }
+        Command::Grind { action } => {
+            let (name, done) = match action {
+                GrindAction::Create { name, species, reserve, weapon, ammo } => {
+                    let weapon = weapon.unwrap_or(Weapon::Unknown);
+                    let ammo = ammo.unwrap_or(Ammo::Unknown);
+                    (name, data::add_grind(store, name, *species, *reserve, weapon, ammo).map(|_| "created"))
+                }
+                GrindAction::Pause { name } => (name, data::stop_grind(store, name).map(|_| "paused")),
+                GrindAction::Resume { name } => (name, data::start_grind(store, name).map(|_| "resumed")),
+                GrindAction::Delete { name } => (name, data::remove_grind(store, name).map(|_| "deleted")),
+            };
+            writeln!(out, "Grind {} {}", name, done?)?;
+            Ok(())
+        }
+        Command::Challenge { action: ChallengeAction::Export { name, output } } => {
+            let path = output.clone().unwrap_or_else(|| data::shared_path(data_dir, name));
+            data::export_challenge(store, name, &path)?;
+            writeln!(out, "Challenge {} exported to {}", name, path.display())?;
+            Ok(())
+        }
+        Command::Challenge { action: ChallengeAction::Import { file, on_conflict } } => {
+            let name = data::import_challenge(store, &data::read_definition(file)?, *on_conflict)?;
+            writeln!(out, "Challenge {} started", name)?;
+            Ok(())
+        }
+        Command::Summary { reserve, format } => write_rows(out, format.format, &summary(&store.read_trophies()?, *reserve)),
+    }
+}
+
+pub fn run_command(command: &Command, data_dir: &Path) -> i32 {
+    let opened = match data::open(data_dir) {
+        Ok(opened) => opened,
+        Err(e) => {
+            eprintln!("Unable to open data directory {}: {}", data_dir.display(), e);
+            return 1;
+        }
+    };
+    for note in &opened.notes {
+        eprintln!("{}", note);
+    }
+    match run(command, opened.store.as_ref(), data_dir, &mut std::io::stdout().lock()) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sqlite_store::SqliteStore;
+    use crate::store::tests::trophy;
+
+    fn output(store: &dyn TrophyStore, args: &[&str]) -> String {
+        let cli = Cli::try_parse_from(std::iter::once("trophy_lodge").chain(args.iter().copied())).unwrap();
+        let mut out = Vec::new();
+        run(&cli.command.unwrap(), store, Path::new("."), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_parse_variant() {
+        assert_eq!(Ok(Species::RedDeer), parse_variant::<Species>("red-deer"));
+        assert_eq!(Ok(Rating::GreatOne), parse_variant::<Rating>("Great One"));
+        assert_eq!(Ok(SortBy::ShotDistance), parse_variant::<SortBy>("shot_distance"));
+        assert!(parse_variant::<Gender>("other").is_err());
+        assert!(Cli::try_parse_from(["trophy_lodge", "trophies", "--species", "dragon"]).is_err());
+    }
+
+    #[test]
+    fn test_api_flag() {
+        assert_eq!(None, Cli::try_parse_from(["trophy_lodge"]).unwrap().api);
+        assert_eq!(Some(7878), Cli::try_parse_from(["trophy_lodge", "--api"]).unwrap().api);
+        assert_eq!(Some(9000), Cli::try_parse_from(["trophy_lodge", "--api", "9000"]).unwrap().api);
+    }
+
+    #[test]
+    fn test_trophies() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let mut diamond = trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 250.0);
+        diamond.rating = Rating::Diamond;
+        store.add_trophy(&diamond).unwrap();
+        store.add_trophy(&trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 200.0)).unwrap();
+        store.add_trophy(&trophy(Species::RoeDeer, Reserve::HirschfeldenHuntingReserve, 20.0)).unwrap();
+
+        let table = output(&store, &["trophies", "--species", "red deer", "--sort-by", "score"]);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(3, lines.len());
+        assert!(lines[0].starts_with("date  "));
+        assert!(lines[1].contains("Diamond"));
+
+        let json: Vec<Trophy> = serde_json::from_str(&output(&store, &["trophies", "--rating", "gold", "--format", "json"])).unwrap();
+        assert_eq!(2, json.len());
+
+        let csv = output(&store, &["trophies", "--limit", "1", "--format", "csv"]);
+        assert_eq!(2, csv.lines().count());
+        assert!(csv.starts_with("id,species,reserve"));
+    }
+
+    #[test]
+    fn test_grind_commands() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        assert_eq!("Grind deer created\n", output(&store, &["grind", "create", "deer", "--species", "red deer", "--reserve", "hirschfelden hunting reserve"]));
+        assert_eq!("Grind deer paused\n", output(&store, &["grind", "pause", "deer"]));
+        assert!(!store.read_grinds().unwrap()[0].active);
+        assert_eq!("Grind deer resumed\n", output(&store, &["grind", "resume", "deer"]));
+        assert!(store.read_grinds().unwrap()[0].active);
+
+        let run_args = |args: &[&str]| {
+            let cli = Cli::try_parse_from(std::iter::once("trophy_lodge").chain(args.iter().copied())).unwrap();
+            run(&cli.command.unwrap(), &store, Path::new("."), &mut Vec::new()).map_err(|e| e.to_string())
+        };
+        assert_eq!(Err("a grind named deer already exists".to_string()), run_args(&["grind", "create", "deer", "--species", "roe deer", "--reserve", "hirschfelden hunting reserve"]));
+        assert_eq!(Err("a grind needs a species".to_string()), run_args(&["grind", "create", "any", "--species", "all", "--reserve", "hirschfelden hunting reserve"]));
+        assert_eq!(Err("there is no grind named elk".to_string()), run_args(&["grind", "pause", "elk"]));
+        assert!(Cli::try_parse_from(["trophy_lodge", "grind", "create", "deer", "--species", "red deer"]).is_err());
+
+        assert_eq!("Grind deer deleted\n", output(&store, &["grind", "delete", "deer"]));
+        assert!(store.read_grinds().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_challenge_commands() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("roe.json");
+        let file_arg = file.to_str().unwrap();
+        let store = SqliteStore::open_in_memory().unwrap();
+        let challenge = Challenge {
+            name: "Roe".to_string(),
+            species: Species::RoeDeer,
+            ..Challenge::default()
+        };
+        store.add_challenge(&[challenge]).unwrap();
+
+        assert_eq!(format!("Challenge Roe exported to {}\n", file_arg), output(&store, &["challenge", "export", "Roe", "--output", file_arg]));
+        assert!(Cli::try_parse_from(["trophy_lodge", "challenge", "import", file_arg, "--on-conflict", "merge"]).is_err());
+        assert_eq!("Challenge Roe (2) started\n", output(&store, &["challenge", "import", file_arg, "--on-conflict", "rename"]));
+        assert_eq!(2, store.read_challenges().unwrap().len());
+    }
+
+    #[test]
+    fn test_summary_and_progress() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let mut great_one = trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 300.0);
+        great_one.rating = Rating::GreatOne;
+        store.add_trophy(&great_one).unwrap();
+        store.add_trophy(&trophy(Species::RoeDeer, Reserve::HirschfeldenHuntingReserve, 20.0)).unwrap();
+        let trophies = store.read_trophies().unwrap();
+        assert_eq!(vec![SummaryRow { name: "Hirschfelden Hunting Reserve".to_string(), trophies: 2, diamonds: 0, great_ones: 1 }], summary(&trophies, None));
+        assert_eq!(2, summary(&trophies, Some(Reserve::HirschfeldenHuntingReserve)).len());
+
+        let challenge = Challenge {
+            name: "Two Roe Deer".to_string(),
+            species: Species::RoeDeer,
+            kills: 2,
+            kills_remaining: 1,
+            ..Challenge::default()
+        };
+        store.add_challenge(&[challenge]).unwrap();
+        let csv = output(&store, &["challenges", "--format", "csv"]);
+        assert_eq!("name,description,start,end,status,percent\nTwo Roe Deer,,,,Active,50\n", csv);
+        assert!(output(&store, &["summary", "--reserve", "layton lake district"]).is_empty());
+    }
+}
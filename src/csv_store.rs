@@ -0,0 +1,403 @@
+use crate::models::*;
+use crate::challenges::*;
+use crate::store::*;
+use crate::backup::*;
+use crate::migrations::{upgrade_csv_headers, upgrade_csv_record};
+use std::path::{Path, PathBuf};
+use std::fs::{self, OpenOptions};
+use std::sync::Mutex;
+use csv::{ByteRecord, StringRecord};
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+
+fn skipped_row(path: &Path, line: u64, e: csv::Error) -> SkippedRow {
+    let error = match e.kind() {
+        csv::ErrorKind::Deserialize { err, .. } => err.to_string(),
+        _ => e.to_string(),
+    };
+    SkippedRow { file: path.to_path_buf(), line, error }
+}
+
+// Returns the rows that could be read along with the ones that were skipped.
+pub fn read_csv<T: DeserializeOwned>(path: &Path) -> DataResult<(Vec<T>, Vec<SkippedRow>)> {
+    if !path.exists() {
+        return Ok((Vec::new(), Vec::new()));
+    }
+
+    let file = OpenOptions::new()
+        .read(true)
+        .open(path)?;
+    let mut rdr = csv::Reader::from_reader(file);
+    let original = rdr.headers()?.clone();
+    let headers = upgrade_csv_headers(&original);
+    let mut items = Vec::new();
+    let mut skipped = Vec::new();
+    let mut record = StringRecord::new();
+    loop {
+        let line = rdr.position().line();
+        match rdr.read_record(&mut record) {
+            Ok(false) => break,
+            Ok(true) => {
+                let mut upgraded = upgrade_csv_record(&original, &record);
+                upgraded.set_position(record.position().cloned());
+                match upgraded.deserialize(Some(&headers)) {
+                    Ok(i) => items.push(i),
+                    Err(e) => skipped.push(skipped_row(path, line, e)),
+                }
+            }
+            Err(e) if e.is_io_error() => return Err(e.into()),
+            Err(e) => skipped.push(skipped_row(path, line, e)),
+        }
+    }
+    Ok((items, skipped))
+}
+
+// Splits a csv file into its header, the rows starting on the given lines
+// and everything else, keeping the original bytes of each row.
+pub fn split_rows(path: &Path, lines: &[u64]) -> DataResult<(Vec<u8>, Vec<u8>, Vec<u8>)> {
+    let bytes = fs::read(path)?;
+    let mut rdr = csv::Reader::from_reader(bytes.as_slice());
+    rdr.byte_headers()?;
+    let header = bytes[..rdr.position().byte() as usize].to_vec();
+    let mut rest = Vec::new();
+    let mut selected = Vec::new();
+    let mut record = ByteRecord::new();
+    loop {
+        let start = rdr.position().clone();
+        match rdr.read_byte_record(&mut record) {
+            Ok(false) => break,
+            Err(e) if e.is_io_error() => return Err(e.into()),
+            _ => {}
+        }
+        let row = &bytes[start.byte() as usize..rdr.position().byte() as usize];
+        let target = if lines.contains(&start.line()) { &mut selected } else { &mut rest };
+        target.extend_from_slice(row);
+        if !row.ends_with(b"\n") {
+            target.push(b'\n');
+        }
+    }
+    Ok((header, rest, selected))
+}
+
+pub fn quarantine_path(path: &Path) -> PathBuf {
+    path.with_extension("quarantine.csv")
+}
+
+pub fn is_quarantine(path: &Path) -> bool {
+    path.to_string_lossy().ends_with(".quarantine.csv")
+}
+
+// Moves the rows starting on the given lines into a .quarantine.csv file next
+// to the original so they can be repaired by hand.
+pub fn quarantine_rows(path: &Path, lines: &[u64]) -> DataResult<PathBuf> {
+    let (header, rest, selected) = split_rows(path, lines)?;
+    let quarantine = quarantine_path(path);
+    let mut contents = if quarantine.exists() { fs::read(&quarantine)? } else { header.clone() };
+    contents.extend(selected);
+    write_atomic(&quarantine, &contents)?;
+    write_atomic(path, &[header, rest].concat())?;
+    Ok(quarantine)
+}
+
+pub fn create_csv<T: Serialize>(path: &Path, items: Vec<T>) -> DataResult<()> {
+    let mut wtr = csv::WriterBuilder::new().has_headers(true).from_writer(Vec::new());
+    for item in items {
+        wtr.serialize(item)?;
+    }
+    let bytes = wtr.into_inner().map_err(|e| e.into_error())?;
+    write_atomic(path, &bytes)?;
+    Ok(())
+}
+
+fn csv_valid(path: &Path) -> bool {
+    let Ok(mut rdr) = csv::Reader::from_path(path) else {
+        return false;
+    };
+    rdr.headers().is_ok() && rdr.records().all(|r| r.is_ok())
+}
+
+pub fn append_csv<T: Serialize>(path: &Path, items: Vec<T>) -> DataResult<()> {
+    let file_exists = path.exists();
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    let mut wtr = csv::WriterBuilder::new().has_headers(!file_exists).from_writer(file);
+    for item in items {
+        wtr.serialize(item)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+// The original ./data layout: trophies.csv, grinds.csv and one csv per
+// challenge holding its expanded criteria.
+pub struct CsvStore {
+    trophies: PathBuf,
+    grinds: PathBuf,
+    challenges: PathBuf,
+    history: PathBuf,
+    skipped: Mutex<Vec<SkippedRow>>,
+}
+impl CsvStore {
+    pub fn new(root: &Path) -> DataResult<Self> {
+        let store = CsvStore {
+            trophies: root.join("trophies.csv"),
+            grinds: root.join("grinds.csv"),
+            challenges: root.join("challenges"),
+            history: root.join("challenge_history.csv"),
+            skipped: Mutex::new(Vec::new()),
+        };
+        fs::create_dir_all(&store.challenges)?;
+        Ok(store)
+    }
+
+    fn challenge_files(&self) -> DataResult<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&self.challenges)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|x| x == "csv") {
+                files.push(path);
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    // Restores any file that is missing or cut short from its backup and
+    // returns the backups that were used.
+    pub fn recover(&self) -> DataResult<Vec<PathBuf>> {
+        let mut files = vec![self.trophies.clone(), self.grinds.clone(), self.history.clone()];
+        for entry in fs::read_dir(&self.challenges)? {
+            let name = entry?.file_name().to_string_lossy().to_string();
+            if let Some((stem, _)) = name.split_once(".csv") {
+                let file = self.challenges.join(format!("{}.csv", stem));
+                if !files.contains(&file) {
+                    files.push(file);
+                }
+            }
+        }
+        let mut recovered = Vec::new();
+        for file in files {
+            if let Some(backup) = recover(&file, csv_valid)? {
+                recovered.push(backup);
+            }
+        }
+        Ok(recovered)
+    }
+
+    // reads a file and remembers the rows it had to skip
+    fn read<T: DeserializeOwned>(&self, path: &Path) -> DataResult<Vec<T>> {
+        let (items, skipped) = read_csv(path)?;
+        self.set_problems(path, &skipped)?;
+        Ok(items)
+    }
+
+    fn challenge_file(&self, name: &str) -> PathBuf {
+        self.challenges.join(convert_challenge_name(name))
+    }
+
+    fn update_grinds(&self, update: impl Fn(&mut Grind)) -> DataResult<()> {
+        let mut grinds = self.read_grinds()?;
+        for g in grinds.iter_mut() {
+            update(g);
+        }
+        create_csv(&self.grinds, grinds)
+    }
+}
+impl TrophyStore for CsvStore {
+    fn read_trophies(&self) -> DataResult<Vec<Trophy>> {
+        self.read(&self.trophies)
+    }
+
+    fn trophy_exists(&self, trophy: &Trophy) -> DataResult<bool> {
+        Ok(self.read_trophies()?.iter().any(|t| t.id == trophy.id || t.identity() == trophy.id))
+    }
+
+    fn add_trophy(&self, trophy: &Trophy) -> DataResult<()> {
+        append_csv(&self.trophies, vec![trophy])
+    }
+
+    fn replace_trophies(&self, trophies: &[Trophy]) -> DataResult<()> {
+        create_csv(&self.trophies, trophies.to_vec())
+    }
+
+    fn read_grinds(&self) -> DataResult<Vec<Grind>> {
+        self.read(&self.grinds)
+    }
+
+    fn add_grind(&self, grind: &Grind) -> DataResult<()> {
+        append_csv(&self.grinds, vec![grind])
+    }
+
+    fn remove_grind(&self, name: &str) -> DataResult<()> {
+        let grinds: Vec<Grind> = self.read_grinds()?.into_iter().filter(|g| g.name != name).collect();
+        create_csv(&self.grinds, grinds)
+    }
+
+    fn set_grind_active(&self, name: &str, active: bool) -> DataResult<()> {
+        self.update_grinds(|g| {
+            if g.name == name {
+                g.active = active;
+            }
+        })
+    }
+
+    fn add_grind_kill(&self, name: &str, _trophy: &Trophy) -> DataResult<()> {
+        self.update_grinds(|g| {
+            if g.name == name {
+                g.kills += 1;
+            }
+        })
+    }
+
+    fn read_challenges(&self) -> DataResult<Vec<Vec<Challenge>>> {
+        let mut challenges = Vec::new();
+        for file in self.challenge_files()? {
+            let criteria = self.read::<Challenge>(&file)?;
+            if !criteria.is_empty() {
+                challenges.push(criteria);
+            }
+        }
+        Ok(challenges)
+    }
+
+    fn read_challenge(&self, name: &str) -> DataResult<Vec<Challenge>> {
+        let file = self.challenge_file(name);
+        if !file.exists() {
+            return Ok(Vec::new());
+        }
+        self.read::<Challenge>(&file)
+    }
+
+    fn challenge_exists(&self, name: &str) -> DataResult<bool> {
+        Ok(self.challenge_file(name).exists())
+    }
+
+    fn add_challenge(&self, criteria: &[Challenge]) -> DataResult<()> {
+        match criteria.first() {
+            Some(c) => create_csv(&self.challenges.join(create_challenge_filename(c)), criteria.to_vec()),
+            None => Ok(()),
+        }
+    }
+
+    fn delete_challenge(&self, name: &str) -> DataResult<()> {
+        remove_with_backups(&self.challenge_file(name))?;
+        Ok(())
+    }
+
+    fn record_challenge_kill(&self, trophy: &Trophy) -> DataResult<Vec<String>> {
+        let mut update_happened = Vec::new();
+        for file in self.challenge_files()? {
+            let mut challenge_kills = self.read::<Challenge>(&file)?;
+            let mut updated = false;
+            for challenge in challenge_kills.iter_mut() {
+                if challenge.for_trophy(trophy) && challenge.kills_remaining > 0 {
+                    if challenge.collect == Collect::Kills {
+                        challenge.kills_remaining -= 1;
+                    }
+                    updated = true;
+                }
+            }
+            if updated {
+                let name = challenge_kills[0].name.clone();
+                create_csv(&file, challenge_kills)?;
+                update_happened.push(name);
+            }
+        }
+        Ok(update_happened)
+    }
+
+    fn read_challenge_history(&self) -> DataResult<Vec<ChallengeRun>> {
+        let mut runs: Vec<ChallengeRun> = self.read(&self.history)?;
+        runs.sort_by(|a, b| (&a.start, &a.name).cmp(&(&b.start, &b.name)));
+        Ok(runs)
+    }
+
+    fn add_challenge_history(&self, run: &ChallengeRun) -> DataResult<()> {
+        if self.read_challenge_history()?.iter().any(|r| r.name == run.name && r.start == run.start) {
+            return Ok(());
+        }
+        append_csv(&self.history, vec![run])
+    }
+
+    fn read_problems(&self) -> DataResult<Vec<SkippedRow>> {
+        Ok(self.skipped.lock().unwrap().clone())
+    }
+
+    fn set_problems(&self, file: &Path, problems: &[SkippedRow]) -> DataResult<()> {
+        let mut skipped = self.skipped.lock().unwrap();
+        skipped.retain(|s| s.file != file);
+        skipped.extend_from_slice(problems);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_store() {
+        let root = tempfile::tempdir().unwrap();
+        let store = CsvStore::new(root.path()).unwrap();
+        crate::store::tests::check_store(&store);
+    }
+
+    #[test]
+    fn test_recover_cut_short_file() {
+        let root = tempfile::tempdir().unwrap();
+        let store = CsvStore::new(root.path()).unwrap();
+        let trophy = crate::store::tests::trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 200.5);
+        store.replace_trophies(std::slice::from_ref(&trophy)).unwrap();
+        store.replace_trophies(&[trophy.clone(), trophy.clone()]).unwrap();
+        let challenge = Challenge {
+            name: "Red Deer".to_string(),
+            ..Challenge::default()
+        };
+        store.add_challenge(std::slice::from_ref(&challenge)).unwrap();
+        store.add_challenge(std::slice::from_ref(&challenge)).unwrap();
+        assert!(store.recover().unwrap().is_empty());
+
+        let contents = fs::read_to_string(&store.trophies).unwrap();
+        fs::write(&store.trophies, &contents[..contents.len() - 20]).unwrap();
+        fs::remove_file(store.challenge_file("Red Deer")).unwrap();
+
+        let recovered = store.recover().unwrap();
+        assert_eq!(2, recovered.len());
+        assert_eq!(1, store.read_trophies().unwrap().len());
+        assert_eq!(vec![vec![challenge]], store.read_challenges().unwrap());
+    }
+
+    #[test]
+    fn test_skipped_rows() {
+        let root = tempfile::tempdir().unwrap();
+        let store = CsvStore::new(root.path()).unwrap();
+        let grind = crate::store::tests::grind;
+        store.add_grind(&grind("first")).unwrap();
+        store.add_grind(&grind("second")).unwrap();
+        store.add_grind(&grind("third")).unwrap();
+        let contents = fs::read_to_string(&store.grinds).unwrap();
+        let mut lines: Vec<&str> = contents.lines().collect();
+        let broken = lines[2].replace("RedDeer", "Dragon");
+        lines[2] = &broken;
+        lines.insert(3, "cut,short");
+        fs::write(&store.grinds, lines.join("\n")).unwrap();
+
+        let grinds = store.read_grinds().unwrap();
+        assert_eq!(vec!["first", "third"], grinds.iter().map(|g| g.name.as_str()).collect::<Vec<&str>>());
+        let problems = store.read_problems().unwrap();
+        assert_eq!(vec![3, 4], problems.iter().map(|p| p.line).collect::<Vec<u64>>());
+        assert_eq!(store.grinds, problems[0].file);
+        assert!(problems[0].error.contains("Dragon"));
+
+        let quarantine = quarantine_rows(&store.grinds, &[3, 4]).unwrap();
+        assert_eq!(2, store.read_grinds().unwrap().len());
+        assert!(store.read_problems().unwrap().is_empty());
+        let quarantined = fs::read_to_string(&quarantine).unwrap();
+        assert_eq!(3, quarantined.lines().count());
+        assert!(quarantined.ends_with("cut,short\n"));
+        let (_, skipped) = read_csv::<Grind>(&quarantine).unwrap();
+        assert_eq!(vec![2, 3], skipped.iter().map(|p| p.line).collect::<Vec<u64>>());
+    }
+}
@@ -1,227 +1,896 @@
 use crate::models::*;
 use crate::challenges::*;
-use std::path::Path;
-use std::fs::{self, OpenOptions};
-use std::sync::mpsc::Sender;
-use serde::de::DeserializeOwned;
-use serde::ser::Serialize;
+use crate::profiles::profiles_dir;
+use crate::catalog::catalog_dir;
+use crate::store::*;
+use crate::csv_store::*;
+use crate::sqlite_store::SqliteStore;
+use crate::backup::*;
+use crate::migrations::{rekey, AppliedMigration};
+use std::path::{Path, PathBuf};
+use std::fs;
+use std::sync::Arc;
+use crate::events::*;
+use chrono::prelude::*;
+use std::fmt;
+use strum_macros::EnumIter;
 
-const TROPHIES: &str = "./data/trophies.csv";
-pub const GRINDS: &str = "./data/grinds.csv";
-const CHALLENGES: &str = "./data/challenges";
+pub const DEFAULT_DATA_DIR: &str = "./data";
+const DATABASE: &str = "lodge.db";
+const IMPORTED: &str = "imported";
+const EXPORT: &str = "export";
+const TROPHY_DUPLICATES: &str = "trophies.duplicates.csv";
+const SHARED: &str = "shared";
 
-pub fn init() {
-    let trophy_path = Path::new(TROPHIES).parent().unwrap();
-    let grind_path = Path::new(GRINDS).parent().unwrap();
-    let challenge_path = Path::new(CHALLENGES);
-    if !trophy_path.exists() {
-        std::fs::create_dir_all(trophy_path).unwrap();
+#[derive(PartialEq, Debug, Clone, Copy, EnumIter)]
+pub enum OnConflict {
+    Cancel,
+    Replace,
+    Rename,
+}
+impl fmt::Display for OnConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
     }
-    if !grind_path.exists() {
-        std::fs::create_dir_all(grind_path).unwrap();
+}
+
+pub struct Opened {
+    pub store: Arc<dyn TrophyStore>,
+    pub notes: Vec<String>,
+    pub schema_version: u32,
+    pub migrations: Vec<AppliedMigration>,
+}
+
+pub fn open(root: &Path) -> DataResult<Opened> {
+    fs::create_dir_all(profiles_dir(root))?;
+    fs::create_dir_all(catalog_dir(root))?;
+    let mut notes = Vec::new();
+    let database = root.join(DATABASE);
+    if let Some(backup) = recover(&database, SqliteStore::check)? {
+        notes.push(format!("The database could not be read and was restored from {}", backup.display()));
     }
-    if !challenge_path.exists() {
-        std::fs::create_dir_all(challenge_path).unwrap();
+    let import = !database.exists();
+    rotate_if_changed(&database)?;
+    let store = SqliteStore::open(&database)?;
+    notes.extend(store.migrate()?);
+    if import {
+        notes.extend(import_csv(root, &store)?);
     }
+    let schema_version = store.schema_version()?;
+    let migrations = store.migration_log()?;
+    Ok(Opened { store: Arc::new(store), notes, schema_version, migrations })
 }
 
-fn read_csv<T: DeserializeOwned>(path: &str) -> Vec<T> {
-    if !Path::new(path).exists() {
-        return Vec::new();
+// One-shot import of the csv files used before the database. They are moved
+// into the imported folder first so they are not imported again, and rows
+// that cannot be read are recorded against the moved files.
+fn import_csv(root: &Path, store: &dyn TrophyStore) -> DataResult<Vec<String>> {
+    let mut notes: Vec<String> = CsvStore::new(root)?.recover()?
+        .iter()
+        .map(|b| format!("Restored {} before importing it", b.display()))
+        .collect();
+    let imported = root.join(IMPORTED);
+    fs::create_dir_all(&imported)?;
+    for entry in fs::read_dir(root)? {
+        let name = entry?.file_name();
+        let file = name.to_string_lossy();
+        if file.starts_with("trophies.csv") || file.starts_with("grinds.csv") || file == "challenges" {
+            fs::rename(root.join(&name), imported.join(&name))?;
+        }
     }
 
-    let file = OpenOptions::new()
-        .read(true)
-        .open(path)
-        .unwrap();
-    let mut rdr = csv::Reader::from_reader(file);
-    let mut items = Vec::new();
-    for item in rdr.deserialize() {
-        if let Ok(i) = item {
-            items.push(i);
-        }
+    let legacy = CsvStore::new(&imported)?;
+    copy_store(&legacy, store)?;
+    let (trophies, duplicates) = rekey(store.read_trophies()?);
+    if !duplicates.is_empty() {
+        let path = imported.join(TROPHY_DUPLICATES);
+        notes.push(format!("{} duplicate trophies were moved to {}", duplicates.len(), path.display()));
+        append_csv(&path, duplicates)?;
     }
-    items
-}
+    store.replace_trophies(&trophies)?;
 
-pub fn create_csv<T: Serialize>(path: &str, items: Vec<T>) {
-    if Path::new(path).exists() {
-        std::fs::remove_file(path).unwrap();
+    let problems = legacy.read_problems()?;
+    if !problems.is_empty() {
+        notes.push(format!("{} rows could not be imported, see the Data tab", problems.len()));
+    }
+    let mut files: Vec<&Path> = problems.iter().map(|p| p.file.as_path()).collect();
+    files.dedup();
+    for file in files {
+        let rows: Vec<SkippedRow> = problems.iter().filter(|p| p.file == file).cloned().collect();
+        store.set_problems(file, &rows)?;
     }
+    Ok(notes)
+}
+
+fn skipped_rows(path: &Path) -> DataResult<Vec<SkippedRow>> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    Ok(if name.starts_with("trophies") {
+        read_csv::<Trophy>(path)?.1
+    } else if name.starts_with("grinds") {
+        read_csv::<Grind>(path)?.1
+    } else {
+        read_csv::<Challenge>(path)?.1
+    })
+}
+
+// Moves the rows of a file that could not be read into its quarantine file.
+pub fn quarantine(store: &dyn TrophyStore, file: &Path) -> DataResult<PathBuf> {
+    let lines: Vec<u64> = store.read_problems()?
+        .iter()
+        .filter(|p| p.file == file)
+        .map(|p| p.line)
+        .collect();
+    let quarantine = quarantine_rows(file, &lines)?;
+    store.set_problems(file, &[])?;
+    store.set_problems(&quarantine, &skipped_rows(&quarantine)?)?;
+    Ok(quarantine)
+}
 
-    if items.len() == 0 {
-        return;
+// Imports the rows of a quarantine file that can now be read. Rows that still
+// fail stay in the file, which is removed once it is empty.
+pub fn import_repaired(store: &dyn TrophyStore, file: &Path) -> DataResult<usize> {
+    let name = file.file_name().unwrap_or_default().to_string_lossy();
+    let (count, skipped) = if name.starts_with("trophies") {
+        let (trophies, skipped) = read_csv::<Trophy>(file)?;
+        let count = trophies.len();
+        for t in rekey(trophies).0 {
+            store.add_trophy(&t)?;
+        }
+        (count, skipped)
+    } else if name.starts_with("grinds") {
+        let (grinds, skipped) = read_csv::<Grind>(file)?;
+        for g in &grinds {
+            store.add_grind(g)?;
+        }
+        (grinds.len(), skipped)
+    } else {
+        let (criteria, skipped) = read_csv::<Challenge>(file)?;
+        store.add_challenge(&criteria)?;
+        (criteria.len(), skipped)
+    };
+    if skipped.is_empty() {
+        remove_with_backups(file)?;
+    } else {
+        let lines: Vec<u64> = skipped.iter().map(|s| s.line).collect();
+        let (header, _, rows) = split_rows(file, &lines)?;
+        write_atomic(file, &[header, rows].concat())?;
     }
+    store.set_problems(file, &skipped_rows(file)?)?;
+    Ok(count)
+}
 
-    let file = OpenOptions::new()
-        .create(true)
-        .write(true)
-        .open(path)
-        .unwrap();
-    let mut wtr = csv::WriterBuilder::new().has_headers(true).from_writer(file);
-    for item in items {
-        wtr.serialize(item).unwrap();
+pub fn export_csv(store: &dyn TrophyStore, root: &Path) -> DataResult<PathBuf> {
+    let export = root.join(EXPORT);
+    if export.exists() {
+        fs::remove_dir_all(&export)?;
     }
-    wtr.flush().unwrap();
+    copy_store(store, &CsvStore::new(&export)?)?;
+    Ok(export)
 }
 
-fn append_csv<T: Serialize>(path: &str, items: Vec<T>) {
-    let file_exists = Path::new(path).exists();
-    if !file_exists {
-        std::fs::File::create(path).unwrap();
+// Stores a new kill with the grinds it counts for and records it against the
+// challenges. Returns false without changing anything when it is already
+// stored.
+pub fn save_trophy(store: &dyn TrophyStore, trophy: &mut Trophy, events: &EventBus) -> DataResult<bool> {
+    if store.trophy_exists(trophy)? {
+        return Ok(false);
     }
-    let file = OpenOptions::new()
-        .append(true)
-        .open(path)
-        .unwrap();
-    let mut wtr = csv::WriterBuilder::new().has_headers(!file_exists).from_writer(file);
-    for item in items {
-        wtr.serialize(item).unwrap();
+    let grinds = grinds_to_add(store, trophy)?;
+    if !grinds.is_empty() {
+        trophy.grind = Some(grinds.join("/"));
     }
-    wtr.flush().unwrap();
+    store.add_trophy(trophy)?;
+    for g in grinds {
+        store.add_grind_kill(&g, trophy)?;
+        events.publish(Event::GrindKill { grind: g });
+    }
+    let mut trophies = None;
+    for challenge in store.record_challenge_kill(trophy)? {
+        let rows = store.read_challenge(&challenge)?;
+        if rows.is_empty() {
+            continue;
+        }
+        let summary = summarize(store, &rows, &mut trophies)?;
+        events.publish(if summary.percent >= 1.0 {
+            Event::ChallengeCompleted { challenge }
+        } else {
+            Event::ChallengeProgress { challenge, percent: summary.percent }
+        });
+        if summary.status != ChallengeStatus::Active {
+            store.add_challenge_history(&finished_run(&rows, summary))?;
+        }
+    }
+    Ok(true)
 }
 
-pub fn read_trophies() -> Vec<Trophy> {
-    read_csv(TROPHIES)
+// Collections are counted from the stored trophies, so those are read once
+// and only when a collection needs them.
+fn summarize(store: &dyn TrophyStore, rows: &Vec<Challenge>, trophies: &mut Option<Vec<Trophy>>) -> DataResult<ChallengeSummary> {
+    if trophies.is_none() && rows.iter().any(|c| c.collect != Collect::Kills) {
+        *trophies = Some(store.read_trophies()?);
+    }
+    Ok(into_summary(rows, trophies.as_deref().unwrap_or_default()))
 }
 
-pub fn trophy_exists(trophy: &Trophy) -> bool {
-    let trophies = read_trophies();
-    for t in trophies {
-        if t.id == trophy.id {
-            return true;
-        }
+pub fn add_grind(store: &dyn TrophyStore, name: &str, species: Species, reserve: Reserve, weapon: Weapon, ammo: Ammo) -> DataResult<Grind> {
+    let grind = Grind {
+        name: name.to_string(),
+        species,
+        reserve,
+        weapon,
+        ammo,
+        active: true,
+        start: Local::now().to_rfc3339(),
+        kills: 0,
+        is_deleted: false,
+    };
+    if let Some(problem) = grind.problem(&store.read_grinds()?) {
+        return Err(DataError::Invalid(problem));
     }
-    false
+    store.add_grind(&grind)?;
+    Ok(grind)
 }
 
-pub fn save_trophy(trophy: &Trophy, grind_tx: &Sender<GrindKill>, challenge_tx: &Sender<ChallengeKill>) {
-    append_csv(TROPHIES, vec![trophy]);
-    let grinds = grinds_to_add(&trophy.species, &trophy.reserve);
-    for g in grinds {
-        add_kill(&g);
-        grind_tx.send(GrindKill { name: g.clone() }).unwrap();
+fn find_grind(store: &dyn TrophyStore, name: &str) -> DataResult<Grind> {
+    store.read_grinds()?
+        .into_iter()
+        .find(|g| g.name == name)
+        .ok_or_else(|| DataError::Invalid(format!("there is no grind named {}", name)))
+}
+
+pub fn start_grind(store: &dyn TrophyStore, name: &str) -> DataResult<()> {
+    find_grind(store, name)?;
+    store.set_grind_active(name, true)
+}
+
+pub fn stop_grind(store: &dyn TrophyStore, name: &str) -> DataResult<()> {
+    find_grind(store, name)?;
+    store.set_grind_active(name, false)
+}
+
+pub fn remove_grind(store: &dyn TrophyStore, name: &str) -> DataResult<()> {
+    find_grind(store, name)?;
+    store.remove_grind(name)
+}
+
+pub fn grinds_to_add(store: &dyn TrophyStore, trophy: &Trophy) -> DataResult<Vec<String>> {
+    Ok(store.read_grinds()?
+        .iter()
+        .filter(|g| g.for_trophy(trophy))
+        .map(|r| r.name.clone())
+        .collect())
+}
+
+pub fn filter_data(trophy_filter: &TrophyFilter, mut data: Vec<Trophy>) -> Vec<Trophy> {
+    if trophy_filter.species != Species::All {
+        data.retain(|x| x.species == trophy_filter.species);
+    }
+    if trophy_filter.reserve != Reserve::All {
+        data.retain(|x| x.reserve == trophy_filter.reserve);
+    }
+    if trophy_filter.rating != Rating::All {
+        data.retain(|x| x.rating == trophy_filter.rating);
+    }
+    if trophy_filter.gender != Gender::All {
+        data.retain(|x| x.gender == trophy_filter.gender);
     }
-    let challenge_kills = update_challenges(&trophy);
-    if challenge_kills.len() > 0 {
-        challenge_tx.send(ChallengeKill { name: challenge_kills[0].clone() }).unwrap();
+    if trophy_filter.weapon != Weapon::All {
+        data.retain(|x| x.weapon == trophy_filter.weapon);
+    }
+    if trophy_filter.ammo != Ammo::All {
+        data.retain(|x| x.ammo == trophy_filter.ammo);
+    }
+    if !trophy_filter.grind.is_empty() {
+        data.retain(|x| {
+            if let Some(grinds) = &x.grind {
+                for grind in grinds.split("/") {
+                    let same = grind == trophy_filter.grind;
+                    if same {
+                        return true;
+                    }
+                }
+            }
+            false
+        });
     }
+    match trophy_filter.sort_by {
+        SortBy::Date => data.sort_by(|a, b| b.date.cmp(&a.date)),
+        SortBy::Score => data.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap()),
+        SortBy::Weight => data.sort_by(|a, b| b.weight.partial_cmp(&a.weight).unwrap()),
+        SortBy::Rating => data.sort_by_key(|x| std::cmp::Reverse(x.rating)),
+        SortBy::ShotDistance => data.sort_by(|a, b| b.shot_distance.partial_cmp(&a.shot_distance).unwrap()),
+    }    
+    data
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GreatOne {
+    pub trophy: Trophy,
+    pub grind: Option<String>,
+    pub grind_kills: usize,
+    pub grind_days: Option<i64>,
 }
 
-pub fn get_grinds() -> Vec<Grind>{
-    read_csv(GRINDS)
+fn counted_grinds(trophy: &Trophy) -> Vec<&str> {
+    trophy.grind.as_deref().map(|g| g.split('/').collect()).unwrap_or_default()
 }
 
-pub fn grinds_to_add(species: &Species, reserve: &Reserve) -> Vec<String> {
-    get_grinds()
+// Every Great One, newest first, with how far into its grind it came. The
+// kill count is the trophies stored for that grind up to and including it.
+pub fn great_ones(trophies: &[Trophy], grinds: &[Grind]) -> Vec<GreatOne> {
+    let date = |t: &Trophy| DateTime::parse_from_rfc3339(&t.date).ok();
+    let mut great_ones: Vec<GreatOne> = trophies
         .iter()
-        .filter(|g| g.species == *species && g.reserve == *reserve && g.active == true)
-        .map(|r| r.name.clone())
-        .collect()
+        .filter(|t| t.rating == Rating::GreatOne)
+        .map(|t| {
+            let counted = counted_grinds(t);
+            let grind = counted
+                .iter()
+                .find(|name| grinds.iter().any(|g| g.name == **name))
+                .or(counted.first())
+                .map(|name| name.to_string());
+            let grind_kills = grind.as_ref().map_or(0, |name| {
+                trophies
+                    .iter()
+                    .filter(|k| counted_grinds(k).contains(&name.as_str()) && date(k) <= date(t))
+                    .count()
+            });
+            let start = grind.as_ref().and_then(|name| grinds.iter().find(|g| g.name == *name));
+            let grind_days = start
+                .and_then(|g| DateTime::parse_from_rfc3339(&g.start).ok())
+                .zip(date(t))
+                .map(|(start, killed)| killed.signed_duration_since(start).num_days());
+            GreatOne { trophy: t.clone(), grind, grind_kills, grind_days }
+        })
+        .collect();
+    great_ones.sort_by(|a, b| b.trophy.date.cmp(&a.trophy.date));
+    great_ones
 }
 
-pub fn add_grind(grind: Grind) {
-    append_csv(GRINDS, vec![grind]);
+pub fn get_challenges(store: &dyn TrophyStore) -> DataResult<Vec<ChallengeSummary>> {
+    let trophies = store.read_trophies()?;
+    Ok(store.read_challenges()?.iter().map(|rows| into_summary(rows, &trophies)).collect())
 }
 
-pub fn remove_grind(name: String) {
-    let grinds = get_grinds();
-    let mut new_grinds = Vec::new();
-    for g in grinds {
-        if g.name != name {
-            new_grinds.push(g);
-        }
+// A completed run is dated now, an expired one by its end.
+fn finished_run(rows: &[Challenge], summary: ChallengeSummary) -> ChallengeRun {
+    ChallengeRun {
+        name: rows[0].name.clone(),
+        description: summary.description,
+        start: summary.start,
+        finished: match summary.status {
+            ChallengeStatus::Expired => summary.end.clone(),
+            _ => Local::now().to_rfc3339(),
+        },
+        end: summary.end,
+        status: summary.status,
+        percent: summary.percent,
     }
-    create_csv(GRINDS, new_grinds);
 }
 
-pub fn set_grind_active(name: String, active: bool) {
-    let mut grinds = get_grinds();
-    let mut new_grinds = Vec::new();
-    for g in grinds.iter_mut() {
-        if g.name == name {
-            g.active = active;
+// Records every challenge that has been completed or has expired since the
+// last call and returns the new runs. Saving a kill records the challenges
+// it completes, so this picks up the ones that expired in the meantime.
+pub fn update_history(store: &dyn TrophyStore, challenges: &[Vec<Challenge>], trophies: &[Trophy]) -> DataResult<Vec<ChallengeRun>> {
+    let history = store.read_challenge_history()?;
+    let mut added = Vec::new();
+    for rows in challenges.iter().filter(|rows| !rows.is_empty()) {
+        let summary = into_summary(rows, trophies);
+        if summary.status == ChallengeStatus::Active || history.iter().any(|r| r.name == rows[0].name && r.start == summary.start) {
+            continue;
         }
-        new_grinds.push(g);
+        let run = finished_run(rows, summary);
+        store.add_challenge_history(&run)?;
+        added.push(run);
     }
-    create_csv(GRINDS, new_grinds);
+    Ok(added)
 }
 
-pub fn stop_grind(name: String) {
-    set_grind_active(name, false);
+// Reads the challenges and trophies once to record finished runs and
+// summarise what is left.
+pub fn refresh_challenges(store: &dyn TrophyStore) -> DataResult<Vec<ChallengeSummary>> {
+    let challenges = store.read_challenges()?;
+    let trophies = store.read_trophies()?;
+    update_history(store, &challenges, &trophies)?;
+    Ok(challenges.iter().map(|rows| into_summary(rows, &trophies)).collect())
 }
 
-pub fn start_grind(name: String) {
-    set_grind_active(name, true);
+pub fn save_challenge(store: &dyn TrophyStore, challenge: &Challenge, furs: &[String], deadline: Deadline) -> DataResult<()> {
+    let mut rows = process_furs(challenge, furs);
+    for row in rows.iter_mut() {
+        row.end = deadline.end(&row.start);
+    }
+    store.add_challenge(&rows)
 }
 
-fn add_kill(name: &String) {
-    let mut grinds = get_grinds();
-    let mut new_grinds = Vec::new();
-    for g in grinds.iter_mut() {
-        if g.name == *name {
-            g.kills += 1;
-        }
-        new_grinds.push(g);
+// What a new challenge would take from the stored trophies dated from the
+// given day, or from the oldest one when no day is given. The challenge is
+// started on that day so collections count the same trophies, and a time
+// limit runs from there.
+pub fn preview_challenge(store: &dyn TrophyStore, challenge: &Challenge, furs: &[String], since: Option<NaiveDate>, deadline: Deadline) -> DataResult<HistoryPreview> {
+    let trophies = store.read_trophies()?;
+    let start = match since {
+        Some(day) => day.and_time(NaiveTime::MIN).and_local_timezone(Local).earliest().map(|d| d.to_rfc3339()),
+        None => trophies.iter().map(|t| t.date.clone()).min(),
+    };
+    let mut rows = process_furs(challenge, furs);
+    for row in rows.iter_mut() {
+        row.kills_remaining = row.kills;
+        row.start = start.clone().unwrap_or_else(|| Local::now().to_rfc3339());
+        row.end = deadline.end(&row.start);
     }
-    create_csv(GRINDS, new_grinds);
+    Ok(evaluate_history(&rows, &trophies))
+}
+
+pub fn commit_challenge(store: &dyn TrophyStore, preview: &HistoryPreview) -> DataResult<()> {
+    store.add_challenge(&preview.rows)
+}
+
+// The furs stored for a species, for filling in a fur challenge.
+pub fn known_furs(trophies: &[Trophy], species: Species) -> Vec<String> {
+    let mut furs: Vec<String> = trophies
+        .iter()
+        .filter(|t| t.species == species && !t.fur.is_empty() && t.fur != "Unknown")
+        .map(|t| t.fur.clone())
+        .collect();
+    furs.sort();
+    furs.dedup();
+    furs
+}
+
+fn invalid(path: &Path, e: impl fmt::Display) -> DataError {
+    DataError::Invalid(format!("{}: {}", path.display(), e))
 }
 
-pub fn get_challenges() -> Vec<ChallengeSummary> {
-    let mut challenge_summaries = Vec::new();
-    for entry in fs::read_dir(CHALLENGES).unwrap() {
-        let entry = entry.unwrap();
-        let path = entry.path();
-        let file = path.to_str().unwrap();
-        let challenges = read_csv::<Challenge>(file);
-        challenge_summaries.push(into_summary(&challenges));
+// Challenge definitions are written as toml unless the file ends in .json.
+pub fn read_definition(path: &Path) -> DataResult<ChallengeDefinition> {
+    let contents = fs::read_to_string(path)?;
+    if path.extension().is_some_and(|x| x == "json") {
+        serde_json::from_str(&contents).map_err(|e| invalid(path, e))
+    } else {
+        toml::from_str(&contents).map_err(|e| invalid(path, e))
     }
-    challenge_summaries
 }
 
-pub fn challenge_exists(challenge: &Challenge) -> bool {
-    let filename = Path::new(&CHALLENGES.to_string()).join(&create_challenge_filename(challenge));
-    Path::new(filename.to_str().unwrap()).exists()
+pub fn write_definition(path: &Path, definition: &ChallengeDefinition) -> DataResult<()> {
+    let contents = if path.extension().is_some_and(|x| x == "json") {
+        serde_json::to_string_pretty(definition)?
+    } else {
+        toml::to_string(definition).map_err(|e| invalid(path, e))?
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write_atomic(path, contents.as_bytes())?;
+    Ok(())
 }
 
-pub fn save_challenge(challenge: &Challenge) {
-    let challenges = process_challenge(challenge);
-    let filename = Path::new(&CHALLENGES.to_string()).join(&create_challenge_filename(challenge));
-    create_csv(filename.to_str().unwrap(), challenges);
+pub fn shared_path(root: &Path, name: &str) -> PathBuf {
+    root.join(SHARED).join(convert_challenge_name(name).replace(".csv", ".toml"))
 }
 
-pub fn delete_challenge(name: &String) {
-    let filename = Path::new(&CHALLENGES.to_string()).join(convert_challenge_name(name));
-    fs::remove_file(filename).unwrap_or_default();
+pub fn export_challenge(store: &dyn TrophyStore, name: &str, path: &Path) -> DataResult<()> {
+    let key = convert_challenge_name(name);
+    let rows = store.read_challenges()?
+        .into_iter()
+        .find(|c| c.first().is_some_and(|c| convert_challenge_name(&c.name) == key))
+        .ok_or_else(|| DataError::Invalid(format!("there is no challenge named {}", name)))?;
+    write_definition(path, &into_definition(&rows))
 }
 
-fn update_challenges(trophy: &Trophy) -> Vec<String> {
-    let challenges = get_challenges();
-    let mut update_happened = Vec::new();
-    for c in challenges {
-        let challenge_name = convert_challenge_name(&c.name);
-        let filename = Path::new(&CHALLENGES.to_string()).join(&challenge_name);
-        let mut challenge_kills = read_csv::<Challenge>(filename.to_str().unwrap());
-        let mut updated = false;
-        for challenge in challenge_kills.iter_mut() {
-            if challenge.for_trophy(trophy) {
-                if challenge.kills_remaining > 0 {
-                    challenge.kills_remaining -= 1;
-                    updated = true;
+// Starts a challenge from a definition and returns the name it was saved as.
+pub fn import_challenge(store: &dyn TrophyStore, definition: &ChallengeDefinition, on_conflict: OnConflict) -> DataResult<String> {
+    if let Some(problem) = definition.problem() {
+        return Err(DataError::Invalid(problem));
+    }
+    let mut definition = definition.clone();
+    if store.challenge_exists(&definition.name)? {
+        match on_conflict {
+            OnConflict::Cancel => return Err(DataError::Invalid(format!("a challenge named {} already exists", definition.name))),
+            OnConflict::Replace => store.delete_challenge(&definition.name)?,
+            OnConflict::Rename => {
+                let base = definition.name.clone();
+                let mut n = 2;
+                while store.challenge_exists(&definition.name)? {
+                    definition.name = format!("{} ({})", base, n);
+                    n += 1;
                 }
             }
-        } 
-        if updated {
-            create_csv(filename.to_str().unwrap(), challenge_kills);
-            update_happened.push(c.name.clone()); 
-        }       
+        }
     }
-    update_happened
+    store.add_challenge(&definition.to_challenges())?;
+    Ok(definition.name)
 }
 
-pub fn upgrade_data() {
-    let mut trophies = fs::read_to_string(TROPHIES).unwrap();
-    trophies = trophies.replace("true", "True");
-    trophies = trophies.replace("false", "False");
-    fs::write(TROPHIES, trophies).unwrap();
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::{grind, trophy};
+
+    #[test]
+    fn test_great_ones() {
+        let mut deer = grind("deer");
+        deer.kills = 4;
+        let kill = |day: u32, rating: Rating, grind: Option<&str>| {
+            let mut t = trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, day as f32);
+            t.date = format!("2024-01-{:02}T12:00:00+00:00", day);
+            t.rating = rating;
+            t.grind = grind.map(|g| g.to_string());
+            t
+        };
+        let trophies = vec![
+            kill(2, Rating::Gold, Some("deer")),
+            kill(3, Rating::Silver, Some("deer/roe")),
+            kill(11, Rating::GreatOne, Some("roe/deer")),
+            kill(12, Rating::Diamond, Some("deer")),
+            kill(20, Rating::GreatOne, None),
+        ];
+        let great_ones = great_ones(&trophies, &[deer]);
+        assert_eq!(2, great_ones.len());
+        assert_eq!(None, great_ones[0].grind);
+        assert_eq!(0, great_ones[0].grind_kills);
+        assert_eq!(Some("deer".to_string()), great_ones[1].grind);
+        assert_eq!(3, great_ones[1].grind_kills);
+        assert_eq!(Some(10), great_ones[1].grind_days);
+    }
+
+    #[test]
+    fn test_known_furs() {
+        let mut trophies: Vec<Trophy> = [200.0, 210.0, 220.0].iter().map(|s| trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, *s)).collect();
+        trophies[1].fur = "Albino".to_string();
+        trophies[2].fur = "Unknown".to_string();
+        assert_eq!(vec!["Albino", "Piebald"], known_furs(&trophies, Species::RedDeer));
+        assert!(known_furs(&trophies, Species::RoeDeer).is_empty());
+    }
+
+    #[test]
+    fn test_identity() {
+        let a = trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 200.5);
+        assert_eq!(16, a.identity().len());
+        assert_eq!(a.identity(), trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 200.5).identity());
+        let mut later = trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 200.5);
+        later.session_score = 11;
+        assert_ne!(a.identity(), later.identity());
+        assert_ne!(a.identity(), trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 200.50002).identity());
+
+        // the old id summed values, so these two collided
+        let mut b = trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 200.5);
+        b.weight = 180.0;
+        b.score = 201.0;
+        assert_ne!(a.identity(), b.identity());
+    }
+
+    #[test]
+    fn test_identity_survives_csv() {
+        let mut t = trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 213.37901);
+        t.weight = 191.23457;
+        t.id = t.identity();
+        let mut wtr = csv::Writer::from_writer(vec![]);
+        wtr.serialize(&t).unwrap();
+        let csv = wtr.into_inner().unwrap();
+        let mut rdr = csv::Reader::from_reader(csv.as_slice());
+        let loaded: Trophy = rdr.deserialize().next().unwrap().unwrap();
+        assert_eq!(t.id, loaded.identity());
+    }
+
+    #[test]
+    fn test_save_trophy() {
+        let root = tempfile::tempdir().unwrap();
+        let store = open(root.path()).unwrap().store;
+        assert!(root.path().join("profiles").exists());
+        store.add_grind(&grind("Red Deer")).unwrap();
+        let challenge = Challenge {
+            name: "Two Red Deer".to_string(),
+            species: Species::RedDeer,
+            reserve: Reserve::HirschfeldenHuntingReserve,
+            kills: 2,
+            kills_remaining: 2,
+            ..Challenge::default()
+        };
+        save_challenge(store.as_ref(), &challenge, &[], Deadline::None).unwrap();
+
+        let events = EventBus::new();
+        let rx = events.subscribe();
+        let mut t = trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 200.5);
+        assert!(save_trophy(store.as_ref(), &mut t, &events).unwrap());
+
+        assert!(store.trophy_exists(&t).unwrap());
+        assert_eq!(Some("Red Deer".to_string()), t.grind);
+        assert_eq!(Ok(Event::GrindKill { grind: "Red Deer".to_string() }), rx.try_recv());
+        assert_eq!(Ok(Event::ChallengeProgress { challenge: "Two Red Deer".to_string(), percent: 0.5 }), rx.try_recv());
+        assert_eq!(1, store.read_grinds().unwrap()[0].kills);
+        let summaries = get_challenges(store.as_ref()).unwrap();
+        assert_eq!(1, summaries.len());
+        assert_eq!(0.5, summaries[0].percent);
+
+        assert!(!save_trophy(store.as_ref(), &mut t, &events).unwrap());
+        assert!(rx.try_recv().is_err());
+        assert_eq!(1, store.read_grinds().unwrap()[0].kills);
+
+        let mut other = trophy(Species::RoeDeer, Reserve::HirschfeldenHuntingReserve, 20.0);
+        save_trophy(store.as_ref(), &mut other, &events).unwrap();
+        assert!(rx.try_recv().is_err());
+        assert_eq!(0.5, get_challenges(store.as_ref()).unwrap()[0].percent);
+
+        let mut second = trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 190.0);
+        save_trophy(store.as_ref(), &mut second, &events).unwrap();
+        assert_eq!(Ok(Event::GrindKill { grind: "Red Deer".to_string() }), rx.try_recv());
+        assert_eq!(Ok(Event::ChallengeCompleted { challenge: "Two Red Deer".to_string() }), rx.try_recv());
+        let history = store.read_challenge_history().unwrap();
+        assert_eq!(1, history.len());
+        assert_eq!(ChallengeStatus::Completed, history[0].status);
+        let challenges = store.read_challenges().unwrap();
+        assert!(update_history(store.as_ref(), &challenges, &store.read_trophies().unwrap()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_weapon_grind_and_filter() {
+        let root = tempfile::tempdir().unwrap();
+        let store = open(root.path()).unwrap().store;
+        let err = add_grind(store.as_ref(), "Bow Deer", Species::RedDeer, Reserve::HirschfeldenHuntingReserve, Weapon::Bearclaw, Ammo::Slug12);
+        assert!(err.unwrap_err().to_string().contains("12 GA Slug cannot be fired from the Bearclaw Lite CB-60"));
+        add_grind(store.as_ref(), "Bow Deer", Species::RedDeer, Reserve::HirschfeldenHuntingReserve, Weapon::Bearclaw, Ammo::Unknown).unwrap();
+        add_grind(store.as_ref(), "Any Deer", Species::RedDeer, Reserve::HirschfeldenHuntingReserve, Weapon::Unknown, Ammo::Unknown).unwrap();
+
+        let mut rifle = trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 200.5);
+        rifle.weapon = Weapon::Ranger243;
+        rifle.ammo = Ammo::SoftPoint243;
+        let mut bow = trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 190.0);
+        bow.weapon = Weapon::Bearclaw;
+        bow.ammo = Ammo::BroadheadArrow;
+        assert_eq!(vec!["Any Deer".to_string()], grinds_to_add(store.as_ref(), &rifle).unwrap());
+        assert_eq!(vec!["Bow Deer".to_string(), "Any Deer".to_string()], grinds_to_add(store.as_ref(), &bow).unwrap());
+
+        let mut filter = TrophyFilter {
+            weapon: Weapon::Bearclaw,
+            ..TrophyFilter::default()
+        };
+        assert_eq!(vec![bow.clone()], filter_data(&filter, vec![rifle.clone(), bow.clone()]));
+        filter.weapon = Weapon::All;
+        filter.ammo = Ammo::SoftPoint243;
+        assert_eq!(vec![rifle.clone()], filter_data(&filter, vec![rifle, bow]));
+    }
+
+    #[test]
+    fn test_expired_challenge() {
+        let root = tempfile::tempdir().unwrap();
+        let store = open(root.path()).unwrap().store;
+        let challenge = Challenge {
+            name: "Red Deer Week".to_string(),
+            species: Species::RedDeer,
+            kills: 2,
+            kills_remaining: 2,
+            start: "2024-01-01T00:00:00+00:00".to_string(),
+            ..Challenge::default()
+        };
+        save_challenge(store.as_ref(), &challenge, &[], Deadline::Days(7)).unwrap();
+        assert_eq!("2024-01-08T00:00:00+00:00", store.read_challenges().unwrap()[0][0].end);
+
+        let events = EventBus::new();
+        let rx = events.subscribe();
+        let mut late = trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 200.5);
+        late.date = "2024-01-09T00:00:00+00:00".to_string();
+        save_trophy(store.as_ref(), &mut late, &events).unwrap();
+        assert!(rx.try_recv().is_err());
+        assert_eq!(2, store.read_challenges().unwrap()[0][0].kills_remaining);
+        assert!(store.read_challenge_history().unwrap().is_empty());
+
+        let summary = &refresh_challenges(store.as_ref()).unwrap()[0];
+        assert_eq!(ChallengeStatus::Expired, summary.status);
+        let history = store.read_challenge_history().unwrap();
+        assert_eq!(1, history.len());
+        assert_eq!(ChallengeStatus::Expired, history[0].status);
+        assert_eq!("2024-01-08T00:00:00+00:00", history[0].finished);
+        let challenges = store.read_challenges().unwrap();
+        assert!(update_history(store.as_ref(), &challenges, &[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_collection_challenge() {
+        let root = tempfile::tempdir().unwrap();
+        let store = open(root.path()).unwrap().store;
+        let challenge = Challenge {
+            name: "Two Species".to_string(),
+            reserve: Reserve::HirschfeldenHuntingReserve,
+            collect: Collect::Species,
+            kills: 2,
+            kills_remaining: 2,
+            start: "2023-12-31T00:00:00+00:00".to_string(),
+            ..Challenge::default()
+        };
+        save_challenge(store.as_ref(), &challenge, &[], Deadline::None).unwrap();
+
+        let mut old = trophy(Species::RoeDeer, Reserve::HirschfeldenHuntingReserve, 20.0);
+        old.date = "2023-06-01T00:00:00+00:00".to_string();
+        store.add_trophy(&old).unwrap();
+        let events = EventBus::new();
+        let rx = events.subscribe();
+        for score in [200.5, 190.0] {
+            let mut t = trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, score);
+            save_trophy(store.as_ref(), &mut t, &events).unwrap();
+            assert_eq!(Ok(Event::ChallengeProgress { challenge: "Two Species".to_string(), percent: 0.5 }), rx.try_recv());
+        }
+        assert_eq!(vec!["Red Deer".to_string()], get_challenges(store.as_ref()).unwrap()[0].collected);
+
+        let mut roe_deer = trophy(Species::RoeDeer, Reserve::HirschfeldenHuntingReserve, 25.0);
+        save_trophy(store.as_ref(), &mut roe_deer, &events).unwrap();
+        assert_eq!(Ok(Event::ChallengeCompleted { challenge: "Two Species".to_string() }), rx.try_recv());
+        assert_eq!(2, store.read_challenges().unwrap()[0][0].kills_remaining);
+    }
+
+    #[test]
+    fn test_retroactive_challenge() {
+        let root = tempfile::tempdir().unwrap();
+        let store = open(root.path()).unwrap().store;
+        for (score, date) in [(200.0, "2024-01-01T12:00:00+00:00"), (210.0, "2024-03-01T12:00:00+00:00"), (220.0, "2024-04-01T12:00:00+00:00")] {
+            let mut t = trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, score);
+            t.date = date.to_string();
+            store.add_trophy(&t).unwrap();
+        }
+        let challenge = Challenge {
+            name: "Three Red Deer".to_string(),
+            species: Species::RedDeer,
+            kills: 3,
+            ..Challenge::default()
+        };
+
+        let preview = preview_challenge(store.as_ref(), &challenge, &[], NaiveDate::from_ymd_opt(2024, 2, 1), Deadline::None).unwrap();
+        assert_eq!(vec![210.0, 220.0], preview.counted.iter().map(|t| t.score).collect::<Vec<f32>>());
+        assert!(store.read_challenges().unwrap().is_empty());
+
+        let preview = preview_challenge(store.as_ref(), &challenge, &[], None, Deadline::None).unwrap();
+        assert_eq!(3, preview.counted.len());
+        assert_eq!("2024-01-01T12:00:00+00:00", preview.rows[0].start);
+        commit_challenge(store.as_ref(), &preview).unwrap();
+        assert_eq!(1.0, get_challenges(store.as_ref()).unwrap()[0].percent);
+    }
+
+    #[test]
+    fn test_imports_csv_once() {
+        let root = tempfile::tempdir().unwrap();
+        let legacy = CsvStore::new(root.path()).unwrap();
+        let mut old = trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 200.5);
+        old.id = "381".to_string();
+        old.fur = "True Black".to_string();
+        let mut duplicate = old.clone();
+        duplicate.id = "381.00003".to_string();
+        legacy.add_trophy(&old).unwrap();
+        legacy.add_trophy(&duplicate).unwrap();
+        legacy.add_grind(&grind("Red Deer")).unwrap();
+        let trophies = root.path().join("trophies.csv");
+        let contents = fs::read_to_string(&trophies).unwrap();
+        fs::write(&trophies, contents.replace("True", "true")).unwrap();
+
+        let opened = open(root.path()).unwrap();
+        assert!(opened.notes.iter().any(|n| n.starts_with("1 duplicate trophies")));
+        let store = opened.store;
+        let imported = store.read_trophies().unwrap();
+        assert_eq!(1, imported.len());
+        assert_eq!("true Black", imported[0].fur);
+        assert_eq!(imported[0].identity(), imported[0].id);
+        assert_eq!(1, store.read_grinds().unwrap().len());
+        assert!(!trophies.exists());
+        assert!(root.path().join(IMPORTED).join("trophies.csv").exists());
+        assert!(root.path().join(IMPORTED).join(TROPHY_DUPLICATES).exists());
+        drop(store);
+
+        let store = open(root.path()).unwrap().store;
+        assert_eq!(1, store.read_trophies().unwrap().len());
+    }
+
+    #[test]
+    fn test_restores_database() {
+        let root = tempfile::tempdir().unwrap();
+        let store = open(root.path()).unwrap().store;
+        store.add_grind(&grind("Red Deer")).unwrap();
+        drop(store);
+        let opened = open(root.path()).unwrap();
+        assert!(opened.notes.is_empty());
+        drop(opened);
+
+        fs::write(root.path().join(DATABASE), b"not a database").unwrap();
+        let opened = open(root.path()).unwrap();
+        assert_eq!(1, opened.notes.len());
+        assert_eq!(1, opened.store.read_grinds().unwrap().len());
+    }
+
+    #[test]
+    fn test_migrates_and_exports() {
+        let root = tempfile::tempdir().unwrap();
+        let opened = open(root.path()).unwrap();
+        assert_eq!(8, opened.schema_version);
+        assert_eq!(8, opened.migrations.len());
+        assert_eq!(8, opened.notes.len());
+        assert!(!root.path().join("lodge.db.v0").exists());
+        drop(opened);
+
+        let opened = open(root.path()).unwrap();
+        assert!(opened.notes.is_empty());
+        assert_eq!(8, opened.migrations.len());
+        let path = export_csv(opened.store.as_ref(), root.path()).unwrap();
+        assert!(path.join("trophies.csv").exists());
+    }
+
+    #[test]
+    fn test_quarantine_and_repair() {
+        let root = tempfile::tempdir().unwrap();
+        let legacy = CsvStore::new(root.path()).unwrap();
+        for score in [200.5, 150.0, 100.0] {
+            legacy.add_trophy(&trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, score)).unwrap();
+        }
+        let trophies = root.path().join("trophies.csv");
+        let contents = fs::read_to_string(&trophies).unwrap();
+        let mut lines: Vec<String> = contents.lines().map(String::from).collect();
+        lines[1] = lines[1].replace("Piebald,", "Piebald,not a date,");
+        lines[2] = lines[2].replace("HirschfeldenHuntingReserve", "Atlantis");
+        fs::write(&trophies, lines.join("\n")).unwrap();
+
+        let store = open(root.path()).unwrap().store;
+        assert_eq!(1, store.read_trophies().unwrap().len());
+        let file = root.path().join(IMPORTED).join("trophies.csv");
+        let problems = store.read_problems().unwrap();
+        assert_eq!(vec![2, 3], problems.iter().map(|p| p.line).collect::<Vec<u64>>());
+        assert!(problems.iter().all(|p| p.file == file));
+        drop(store);
+
+        let store = open(root.path()).unwrap().store;
+        assert_eq!(2, store.read_problems().unwrap().len());
+        let quarantine = quarantine(store.as_ref(), &file).unwrap();
+        let problems = store.read_problems().unwrap();
+        assert_eq!(vec![2, 3], problems.iter().map(|p| p.line).collect::<Vec<u64>>());
+        assert!(problems.iter().all(|p| p.file == quarantine));
+        let (rows, skipped) = read_csv::<Trophy>(&file).unwrap();
+        assert_eq!((1, 0), (rows.len(), skipped.len()));
+
+        let contents = fs::read_to_string(&quarantine).unwrap();
+        fs::write(&quarantine, contents.replace("Atlantis", "HirschfeldenHuntingReserve")).unwrap();
+        assert_eq!(1, import_repaired(store.as_ref(), &quarantine).unwrap());
+        assert_eq!(vec![2], store.read_problems().unwrap().iter().map(|p| p.line).collect::<Vec<u64>>());
+        let contents = fs::read_to_string(&quarantine).unwrap();
+        fs::write(&quarantine, contents.replace("Piebald,not a date,", "Piebald,")).unwrap();
+        assert_eq!(1, import_repaired(store.as_ref(), &quarantine).unwrap());
+        assert!(store.read_problems().unwrap().is_empty());
+        assert!(!quarantine.exists());
+        assert_eq!(3, store.read_trophies().unwrap().len());
+    }
+
+    #[test]
+    fn test_share_challenge() {
+        let root = tempfile::tempdir().unwrap();
+        let store = open(root.path()).unwrap().store;
+        let challenge = Challenge {
+            name: "Every Deer".to_string(),
+            species: Species::All,
+            reserve: Reserve::HirschfeldenHuntingReserve,
+            rating: Rating::Gold,
+            kills: 2,
+            kills_remaining: 1,
+            ..Challenge::default()
+        };
+        save_challenge(store.as_ref(), &challenge, &[], Deadline::None).unwrap();
+
+        let toml = shared_path(root.path(), "every deer");
+        assert!(export_challenge(store.as_ref(), "Missing", &toml).is_err());
+        export_challenge(store.as_ref(), "every deer", &toml).unwrap();
+        let json = root.path().join("every_deer.json");
+        export_challenge(store.as_ref(), "Every Deer", &json).unwrap();
+        let definition = read_definition(&toml).unwrap();
+        assert_eq!(definition, read_definition(&json).unwrap());
+        assert_eq!(1, definition.criteria.len());
+        assert_eq!(Species::All, definition.criteria[0].species);
+        assert_eq!(2, definition.criteria[0].kills);
+
+        assert!(import_challenge(store.as_ref(), &definition, OnConflict::Cancel).is_err());
+        assert_eq!("Every Deer (2)", import_challenge(store.as_ref(), &definition, OnConflict::Rename).unwrap());
+        assert_eq!("Every Deer", import_challenge(store.as_ref(), &definition, OnConflict::Replace).unwrap());
+        let challenges = store.read_challenges().unwrap();
+        assert_eq!(2, challenges.len());
+        assert!(challenges.iter().flatten().all(|c| c.kills_remaining == 2));
+
+        fs::write(&toml, "name = \"Broken\"\ncriteria = []\n").unwrap();
+        let broken = read_definition(&toml).unwrap();
+        assert!(import_challenge(store.as_ref(), &broken, OnConflict::Rename).is_err());
+        fs::write(&toml, "not toml").unwrap();
+        assert!(read_definition(&toml).unwrap_err().to_string().contains("every_deer.toml"));
+    }
+}
@@ -0,0 +1,89 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use serde::Serialize;
+use crate::models::*;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    Status { message: String },
+    User { name: String },
+    GameAttached { base_address: usize, harvest_address: usize, profile: String },
+    GameClosed { replay: bool },
+    TrophyStored { trophy: Trophy },
+    Duplicate { trophy: Trophy },
+    GrindKill { grind: String },
+    ChallengeProgress { challenge: String, percent: f32 },
+    ChallengeCompleted { challenge: String },
+    Alert { rule: String, message: String, trophy: Trophy },
+}
+impl Event {
+    pub fn status(message: impl Into<String>) -> Event {
+        Event::Status { message: message.into() }
+    }
+
+    // The line shown in the status bar, for the events that change it.
+    pub fn status_line(&self) -> Option<String> {
+        match self {
+            Event::Status { message } => Some(message.clone()),
+            Event::GameAttached { base_address, harvest_address, profile } => {
+                Some(format!("Attached to game: {:X} {:X}, {}. Waiting for kill.", base_address, harvest_address, profile))
+            }
+            Event::GameClosed { replay: true } => Some("Replay has finished. No longer tracking.".to_string()),
+            Event::GameClosed { replay: false } => Some("Game has been closed. No longer tracking.".to_string()),
+            Event::TrophyStored { trophy } => Some(format!("Stored {} trophy from {}", trophy.species, trophy.reserve)),
+            Event::Duplicate { .. } => Some("Trophy is already saved".to_string()),
+            Event::ChallengeCompleted { challenge } => Some(format!("Challenge {} completed", challenge)),
+            Event::Alert { message, .. } => Some(message.clone()),
+            _ => None,
+        }
+    }
+}
+
+// Fans every published event out to all current subscribers. Subscribers
+// that have gone away are dropped on the next publish.
+#[derive(Clone, Default)]
+pub struct EventBus {
+    subscribers: Arc<Mutex<Vec<Sender<Event>>>>,
+}
+impl EventBus {
+    pub fn new() -> Self {
+        EventBus::default()
+    }
+
+    pub fn subscribe(&self) -> Receiver<Event> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    pub fn publish(&self, event: Event) {
+        self.subscribers.lock().unwrap().retain(|tx| tx.send(event.clone()).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_publish() {
+        let bus = EventBus::new();
+        let gui = bus.subscribe();
+        let overlay = bus.subscribe();
+        bus.publish(Event::GrindKill { grind: "deer".to_string() });
+        assert_eq!(Ok(Event::GrindKill { grind: "deer".to_string() }), gui.try_recv());
+        assert_eq!(Ok(Event::GrindKill { grind: "deer".to_string() }), overlay.try_recv());
+
+        drop(overlay);
+        bus.publish(Event::GameClosed { replay: false });
+        assert_eq!(1, bus.subscribers.lock().unwrap().len());
+        assert_eq!(Some("Game has been closed. No longer tracking.".to_string()), gui.try_recv().unwrap().status_line());
+    }
+
+    #[test]
+    fn test_serialize() {
+        let event = Event::ChallengeProgress { challenge: "Two Red Deer".to_string(), percent: 0.5 };
+        assert_eq!(r#"{"type":"challenge_progress","challenge":"Two Red Deer","percent":0.5}"#, serde_json::to_string(&event).unwrap());
+    }
+}
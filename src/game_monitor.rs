@@ -1,81 +1,49 @@
-
-use proc_mem::Process;
-use std::sync::mpsc::Sender;
 use std::thread;
 use std::time::Duration;
+use std::sync::Arc;
 use std::str::FromStr;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::convert::From;
 use crate::models::*;
 use crate::data;
+use crate::events::*;
+use crate::store::*;
+use crate::memory::*;
+use crate::snapshot::*;
+use crate::profiles::*;
+use crate::signatures::*;
 use chrono::prelude::*;
-use convert_case::{Case, Casing};
+#[cfg(windows)]
+use proc_mem::Process;
+#[cfg(windows)]
 use windows::Win32::Foundation::CloseHandle;
+#[cfg(windows)]
 use windows::Win32::System::Threading::{OpenProcess, PROCESS_QUERY_INFORMATION, PROCESS_VM_READ};
+#[cfg(windows)]
 use windows::Win32::System::ProcessStatus::GetModuleFileNameExW;
 
-struct Pointer {
-    pub base_address: usize,
-}
-impl Pointer {
-    fn new(base_address: usize) -> Self {
-        Self { base_address }
-    }
-    fn add(mut self, proc: &Process, offset: usize) -> Self {
-        self.base_address = read_usize(proc, self.base_address, offset);
-        self
-    }
-    fn address(self) -> usize {
-        self.base_address
-    }
-}
-
-#[derive(Debug)]
 struct Game {
-    pub proc: Process,
+    pub source: Box<dyn MemorySource>,
     pub base_address: usize,
+    pub module_size: usize,
     pub directory: Option<String>,
+    pub module_hash: Option<String>,
+    pub profile: Option<OffsetProfile>,
 }
 
-struct Offsets {
-    species: usize,
-    gender: usize,
-    weight: usize,
-    tracking: usize,
-    xp: usize,
-    cash: usize,
-    score: usize,
-    rating: usize,
-    integrity: usize,
-    fur_offset: usize,
-    session_score: usize,
-    reserve: usize,
-    weapon_score: usize,
-    shot_distance: usize,
-    shot_damage: usize,
+pub enum MonitorMode {
+    Live,
+    Record(PathBuf),
+    Replay(PathBuf),
 }
-impl Offsets {
-    fn new() -> Self {
-        Self {
-            species: 0x0,
-            gender: 0x20,
-            weight: 0x24,
-            tracking: 0x28,
-            xp: 0x34,
-            cash: 0x38,
-            score: 0x3C,
-            rating: 0xAC,
-            integrity: 0x4C,
-            fur_offset: 0x50,
-            session_score: 0xB0,
-            reserve: 0x60, // pointer
-            weapon_score: 0x18,
-            shot_distance: 0x1C,
-            shot_damage: 0x20,
-        }
-    }
+
+pub struct MonitorConfig {
+    pub mode: MonitorMode,
+    pub profiles: PathBuf,
+    pub store: Arc<dyn TrophyStore>,
 }
 
+#[cfg(windows)]
 fn get_process_path(pid: u32) -> Option<String> {
     unsafe {
         if let Ok(process_handle) = OpenProcess(PROCESS_QUERY_INFORMATION | PROCESS_VM_READ, false, pid) {
@@ -90,78 +58,31 @@ fn get_process_path(pid: u32) -> Option<String> {
     }
 }
 
+#[cfg(windows)]
 fn get_game() -> Option<Game> {
     let mut game: Option<Game> = None;
     let game_name = "theHunterCotW_F.exe";
     if let Ok(cotw_proc) = Process::with_name(game_name) {
-        if let Ok(cotw) = cotw_proc.module(game_name) {   
-            let cotw_pid = cotw_proc.pid().clone();             
-            game = Some(Game { 
-                proc: cotw_proc, 
+        if let Ok(cotw) = cotw_proc.module(game_name) {
+            let cotw_pid = *cotw_proc.pid();
+            let directory = get_process_path(cotw_pid);
+            let module_hash = directory.as_ref().and_then(|d| module_hash(Path::new(d)));
+            game = Some(Game {
+                source: Box::new(cotw_proc),
                 base_address: cotw.base_address(),
-                directory: get_process_path(cotw_pid),
+                module_size: *cotw.base_size(),
+                directory,
+                module_hash,
+                profile: None,
             });
         }
     }
     game
 }
 
-fn read_mem<T: Default>(cotw: &Process, address: usize, offset: usize) -> Option<T> {
-    if let Ok(result) = cotw.read_mem::<T>(address + offset) {
-        Some(result)
-    } else {
-        None
-    }
-}
-
-fn read_int(cotw: &Process, address: usize, offset: usize) -> i32 {
-    if let Some(x) = read_mem::<i32>(cotw, address, offset) {
-        x
-    } else {
-        -1
-    }
-}
-
-fn read_float(cotw: &Process, address: usize, offset: usize) -> f32 {
-    if let Some(x) = read_mem::<f32>(cotw, address, offset) {
-        x
-    } else {
-        f32::default()
-    }
-}
-
-fn read_byte(cotw: &Process, address: usize, offset: usize) -> u8 {
-    if let Some(x) = read_mem::<u8>(cotw, address, offset) {
-        x
-    } else {
-        u8::default()
-    }
-}
-
-fn read_usize(cotw: &Process, address: usize, offset: usize) -> usize {
-    if let Some(x) = read_mem::<usize>(cotw, address, offset) {
-        x
-    } else {
-        usize::default()
-    }
-}
-
-fn read_string(cotw: &Process, address: usize, offset: usize, format: bool) -> String {
-    let mut result = String::new();
-    let mut s_offset = 0x0;
-    loop {
-        let byte = read_byte(&cotw, address, offset + s_offset);
-        if byte == 0 {
-            break;
-        }
-        result.push(byte as char);
-        s_offset += 0x1;
-    }
-    if format {
-        result.replace("-", " ").to_case(Case::Title)
-    } else {
-        result
-    }
+#[cfg(not(windows))]
+fn get_game() -> Option<Game> {
+    None
 }
 
 fn using_mods(game_dir: &Option<String>) -> bool {
@@ -174,21 +95,22 @@ fn using_mods(game_dir: &Option<String>) -> bool {
     }
 }
 
-fn get_shot_base_address(base_address: usize, proc: &Process) -> usize {  
-    Pointer::new(base_address).add(proc, 0x023C46A8).add(proc, 0xD8).add(proc, 0x2E0).address()
+fn get_username(profile: &OffsetProfile, base_address: usize, mem: &dyn MemorySource) -> String {
+    let username_address = profile.username.resolve(mem, base_address);
+    read_string(mem, username_address, 0x0, false)
 }
 
-fn find_fur(proc: &Process, fur_lookup: usize, fur_lookup_offset: usize, mut max_fur_count: usize, fur_name_key: i32) -> Option<String> {
+fn find_fur(mem: &dyn MemorySource, fur_lookup: usize, fur_lookup_offset: usize, mut max_fur_count: usize, fur_name_key: i32) -> Option<String> {
     let fur_key_size = 0x08;
     let mut current_offset = fur_lookup + fur_lookup_offset;
 
     while max_fur_count > 0 {
-        let current_fur_key = read_int(proc, current_offset, 0x0);
+        let current_fur_key = read_int(mem, current_offset, 0x0);
         if fur_name_key == current_fur_key {
-            let fur_lookup_address = read_usize(proc, fur_lookup, 0x0);            
-            let fur_name_offset = read_int(proc, current_offset, 0x04);
+            let fur_lookup_address = read_usize(mem, fur_lookup, 0x0);
+            let fur_name_offset = read_int(mem, current_offset, 0x04);
             let fur_name_address = fur_lookup_address + fur_name_offset as usize;
-            return Some(read_string(&proc, fur_name_address as usize, 0x0, true));
+            return Some(read_string(mem, fur_name_address, 0x0, true));
         }
         current_offset += fur_key_size;
         max_fur_count -= 1;
@@ -196,152 +118,581 @@ fn find_fur(proc: &Process, fur_lookup: usize, fur_lookup_offset: usize, mut max
     None
 }
 
-fn get_fur2(proc: &Process, base_address: usize, fur_name_key: i32) -> Option<String> {
-    let fur_lookup = Pointer::new(base_address).add(proc, 0x0227B640).add(proc, 0x0).add(proc, 0x0).address() + 0x10;
-    find_fur(proc, fur_lookup, 0x150, 0x2B2, fur_name_key)
+// Harvest rating byte of a Great One, the code after no rating (4).
+const GREAT_ONE_RATING: u8 = 5;
+
+// Codes outside the known ones are misreads, not Great Ones.
+fn decode_rating(byte: u8) -> Rating {
+    match byte {
+        0 => Rating::Diamond,
+        1 => Rating::Gold,
+        2 => Rating::Silver,
+        3 => Rating::Bronze,
+        4 => Rating::None,
+        GREAT_ONE_RATING => Rating::GreatOne,
+        _ => Rating::Unknown,
+    }
 }
 
-fn get_fur(proc: &Process, base_address: usize, fur_name_key: i32) -> Option<String> {
-    let fur_lookup = Pointer::new(base_address).add(proc, 0x0227B640).add(proc, 0x0).add(proc, 0x10).address() + 0x10;
-    find_fur(proc, fur_lookup, 0x160, 0x030000, fur_name_key)
+fn get_fur_name(profile: &OffsetProfile, mem: &dyn MemorySource, base_address: usize, fur_name_key: i32) -> String {
+    for lookup in profile.fur_lookups.iter() {
+        let fur_lookup = lookup.table.resolve(mem, base_address);
+        if let Some(fur_name) = find_fur(mem, fur_lookup, lookup.keys_offset, lookup.max_count, fur_name_key) {
+            return fur_name;
+        }
+    }
+    "Unknown".to_string()
 }
 
-fn get_fur_name(proc: &Process, base_address: usize, fur_name_key: i32) -> String {
-    if let Some(fur_name) = get_fur(proc, base_address, fur_name_key) {
-        fur_name
-    } else if let Some(fur_name) = get_fur2(proc, base_address, fur_name_key) {
-        fur_name
+fn valid_string(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_whitespace() || c.is_ascii_alphabetic())
+}
+
+// Weapon and ammo names hold digits and punctuation, so they are checked for
+// anything printable rather than with valid_string.
+fn get_shot_name(mem: &dyn MemorySource, shot_info: usize, offset: usize) -> String {
+    let name = read_string(mem, Pointer::new(shot_info).add(mem, offset).address(), 0x0, false);
+    if !name.is_empty() && name.chars().all(|c| c.is_ascii_graphic() || c == ' ') {
+        name
     } else {
-        "Unknown".to_string()
+        read_string(mem, shot_info, offset, false)
     }
 }
 
-fn valid_string(s: &String) -> bool {
-    s != "" && s.chars().all(|c| c.is_whitespace() || c.is_ascii_alphabetic())
+struct Kill {
+    trophy: Trophy,
+    species_name: String,
+    reserve_name: String,
 }
 
-pub fn monitor(status_tx: Sender<String>, trophy_tx: Sender<Trophy>, user_tx: Sender<String>, grind_tx: Sender<GrindKill>, challenge_tx: Sender<ChallengeKill>) {
-    let mut last_weight = 0f32;
-    let game_proc: Process;
-    let game_directory: Option<String>;
-    let base_address: usize;
-    loop {
-        if let Some(game) = get_game() {
-            game_proc = game.proc;
-            game_directory = game.directory;
-            base_address = game.base_address;
-            break;
+enum Poll {
+    Closed,
+    Waiting,
+    Kill(Kill),
+}
+
+struct KillScanner {
+    base_address: usize,
+    harvest_base_address: usize,
+    directory: Option<String>,
+    profile: OffsetProfile,
+    last_weight: f32,
+    game_open: bool,
+}
+impl KillScanner {
+    fn new(profile: OffsetProfile, base_address: usize, harvest_base_address: usize, directory: Option<String>) -> Self {
+        Self {
+            base_address,
+            harvest_base_address,
+            directory,
+            profile,
+            last_weight: 0f32,
+            game_open: false,
         }
-        status_tx.send("Waiting for game...".to_string()).unwrap();
-        thread::sleep(Duration::from_secs(2));
     }
 
+    fn poll(&mut self, mem: &dyn MemorySource) -> Poll {
+        let offsets = &self.profile.fields;
+        let harvest_base_address = self.harvest_base_address;
+
+        let session_score = read_int(mem, harvest_base_address, offsets.session_score);
+        println!("Session score: {}", session_score);
+        if session_score == -1 && self.game_open {
+            return Poll::Closed;
+        }
+        if session_score != -1 {
+            self.game_open = true;
+        }
+
+        let weight = read_float(mem, harvest_base_address, offsets.weight);
+        if !self.game_open || weight == self.last_weight || weight <= 0.0e-15 {
+            return Poll::Waiting;
+        }
+        self.last_weight = weight;
+        Poll::Kill(self.read_kill(mem))
+    }
+
+    // Reads everything a trophy is built from. Recording calls this on polls
+    // without a kill too, so every snapshot holds the harvest, shot and fur
+    // data a replay that starts from it will read.
+    fn read_kill(&self, mem: &dyn MemorySource) -> Kill {
+        let profile = &self.profile;
+        let offsets = &profile.fields;
+        let base_address = self.base_address;
+        let harvest_base_address = self.harvest_base_address;
+
+        let session_score = read_int(mem, harvest_base_address, offsets.session_score);
+        let weight = read_float(mem, harvest_base_address, offsets.weight);
+        let shot_info_base_address = profile.shot.resolve(mem, base_address);
+        let mut trophy_species = read_string(mem, harvest_base_address, offsets.species, true);
+        if !valid_string(&trophy_species) {
+            trophy_species = read_string(mem, read_usize(mem, harvest_base_address, 0x0), 0x0, true);
+        }
+        let species = Species::from_str(&trophy_species).unwrap_or(Species::Unknown);
+        let trophy_reserve = Pointer::new(harvest_base_address).add(mem, offsets.reserve).address();
+        let mut trophy_reserve = read_string(mem, trophy_reserve, 0x0, true);
+        if !valid_string(&trophy_reserve) {
+            trophy_reserve = read_string(mem, harvest_base_address, offsets.reserve, true);
+        }
+        let reserve = Reserve::from_str(&trophy_reserve).unwrap_or(Reserve::Unknown);
+        let trophy_rating = read_byte(mem, harvest_base_address, offsets.rating);
+        let score = read_float(mem, harvest_base_address, offsets.score);
+        let tracking = read_float(mem, base_address, offsets.tracking);
+        let cash = read_int(mem, harvest_base_address, offsets.cash);
+        let xp = read_int(mem, harvest_base_address, offsets.xp);
+        let rating = decode_rating(trophy_rating);
+        let trophy_gender = read_int(mem, harvest_base_address, offsets.gender);
+        let gender = match trophy_gender {
+            1 => Gender::Male,
+            _ => Gender::Female,
+        };
+        let fur_key = read_int(mem, harvest_base_address, offsets.fur_offset);
+        let fur = get_fur_name(profile, mem, base_address, fur_key);
+        let weapon = offsets.weapon.map_or(Weapon::Unknown, |offset| Weapon::from_name(&get_shot_name(mem, shot_info_base_address, offset)));
+        let ammo = offsets.ammo.map_or(Ammo::Unknown, |offset| Ammo::from_name(&get_shot_name(mem, shot_info_base_address, offset)));
+
+        let mut trophy = Trophy {
+            id: String::new(),
+            species,
+            reserve,
+            rating,
+            score,
+            weight,
+            fur,
+            date: Local::now().to_rfc3339(),
+            gender,
+            cash,
+            xp,
+            session_score,
+            integrity: Boolean::from(read_byte(mem, harvest_base_address, offsets.integrity) == 1),
+            tracking,
+            weapon_score: read_float(mem, shot_info_base_address, offsets.weapon_score),
+            shot_distance: read_float(mem, shot_info_base_address, offsets.shot_distance),
+            shot_damage: read_float(mem, shot_info_base_address, offsets.shot_damage) * 100.0,
+            weapon,
+            ammo,
+            mods: Boolean::from(using_mods(&self.directory)),
+            grind: None,
+        };
+        trophy.id = trophy.identity();
+        Kill {
+            trophy,
+            species_name: trophy_species,
+            reserve_name: trophy_reserve,
+        }
+    }
+}
+
+fn profile_for(game: &Game, profiles: &Path) -> (OffsetProfile, String) {
+    if let Some(profile) = &game.profile {
+        return (profile.clone(), format!("using {} offsets from snapshot", profile.name));
+    }
+    let profile_match = select_profile(&load_profiles(profiles), &game.module_hash);
+    let mut status = profile_match.status(&game.module_hash);
+    let (profile, failed) = resolve_signatures(profile_match.profile(), game.source.as_ref(), game.base_address, game.module_size);
+    if !failed.is_empty() {
+        let failed: Vec<String> = failed.iter().map(|t| t.to_string()).collect();
+        status = format!("{}, signatures not found: {}", status, failed.join(", "));
+    }
+    (profile, status)
+}
+
+fn get_replay(path: &Path) -> Option<Game> {
+    match read_snapshots(path) {
+        Ok(snapshots) if !snapshots.is_empty() => Some(Game {
+            source: Box::new(ReplaySource::new(&snapshots)),
+            base_address: snapshots[0].base_address,
+            module_size: 0,
+            directory: snapshots[0].directory.clone(),
+            module_hash: snapshots[0].module_hash.clone(),
+            profile: snapshots[0].profile.clone(),
+        }),
+        Ok(_) => None,
+        Err(e) => {
+            println!("Unable to read snapshots from {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+pub fn monitor(config: MonitorConfig, events: EventBus) {
+    let MonitorConfig { mode, profiles, store } = config;
+    let mut game: Game;
+    let poll_interval: Duration;
+    if let MonitorMode::Replay(path) = &mode {
+        match get_replay(path) {
+            Some(g) => game = g,
+            None => {
+                events.publish(Event::status(format!("No snapshots to replay in {}", path.display())));
+                return;
+            }
+        }
+        poll_interval = Duration::from_secs(1);
+    } else {
+        loop {
+            if let Some(g) = get_game() {
+                game = g;
+                break;
+            }
+            events.publish(Event::status("Waiting for game..."));
+            thread::sleep(Duration::from_secs(2));
+        }
+        poll_interval = Duration::from_secs(3);
+    }
+    let (profile, profile_status) = profile_for(&game, &profiles);
+    if let MonitorMode::Record(dir) = &mode {
+        game.source = Box::new(RecordingSource::new(game.source, dir.clone(), game.base_address, game.directory.clone(), game.module_hash.clone(), Some(profile.clone())));
+    }
+    let mem = game.source.as_ref();
+    let base_address = game.base_address;
+
     let mut harvest_base_address: usize;
     loop {
-        harvest_base_address = Pointer::new(base_address).add(&game_proc, 0x023F0FB8).address() + 0x288;
+        harvest_base_address = profile.harvest.resolve(mem, base_address);
         if harvest_base_address > 1000 {
-            status_tx.send(format!("Attached to game: {:X} {:X}. Waiting for kill.", base_address, harvest_base_address)).unwrap();
+            events.publish(Event::GameAttached { base_address, harvest_address: harvest_base_address, profile: profile_status.clone() });
             break;
         }
+        if let MonitorMode::Replay(_) = mode {
+            events.publish(Event::status("Replay does not contain a loaded game."));
+            return;
+        }
 
-        status_tx.send("Waiting for game to be fully loaded...".to_string()).unwrap();
+        events.publish(Event::status("Waiting for game to be fully loaded..."));
         thread::sleep(Duration::from_secs(2));
     }
 
-    let offsets = Offsets::new();
-    let mut game_open = false;
+    let mut scanner = KillScanner::new(profile.clone(), base_address, harvest_base_address, game.directory);
+    let mut username = String::new();
     loop {
-        let session_score = read_int(&game_proc, harvest_base_address, offsets.session_score);
-        println!("Session score: {}", session_score);
-        if session_score == -1 && game_open {
-            status_tx.send("Game has been closed. No longer tracking.".to_string()).unwrap();
+        let poll = scanner.poll(mem);
+        if let Poll::Closed = poll {
+            events.publish(Event::GameClosed { replay: matches!(mode, MonitorMode::Replay(_)) });
             break;
-        } 
-        if session_score != -1 {
-            game_open = true;
         }
-        let username_address = Pointer::new(base_address).add(&game_proc, 0x023C46A0).address() + 0x390;
-        let username = read_string(&game_proc, username_address, 0x0, false);
-        user_tx.send(username).unwrap_or_default();
-        
-        let weight = read_float(&game_proc, harvest_base_address, offsets.weight);
-        if game_open && weight != last_weight && weight > 0.0e-15 { 
-            last_weight = weight;
-            let shot_info_base_address = get_shot_base_address(base_address, &game_proc);
-            let mut trophy_species = read_string(&game_proc, harvest_base_address, offsets.species, true);
-            if !valid_string(&trophy_species) {
-                trophy_species = read_string(&game_proc, read_usize(&game_proc, harvest_base_address, 0x0), 0x0, true);
-            }
-            let species = Species::from_str(&trophy_species).unwrap_or(Species::Unknown);
-            let trophy_reserve = Pointer::new(harvest_base_address).add(&game_proc, offsets.reserve).address();
-            let mut trophy_reserve = read_string(&game_proc, trophy_reserve, 0x0, true);
-            if !valid_string(&trophy_reserve) {
-                trophy_reserve = read_string(&game_proc, harvest_base_address, offsets.reserve, true);
-            }
-            let reserve = Reserve::from_str(&trophy_reserve).unwrap_or(Reserve::Unknown);
-            let trophy_rating = read_byte(&game_proc, harvest_base_address, offsets.rating);
-            let score = read_float(&game_proc, harvest_base_address, offsets.score);
-            let tracking = read_float(&game_proc, base_address, offsets.tracking);
-            let cash = read_int(&game_proc, harvest_base_address, offsets.cash);
-            let xp = read_int(&game_proc, harvest_base_address, offsets.xp);
-            let rating = match trophy_rating {
-                0 => Rating::Diamond,
-                1 => Rating::Gold,
-                2 => Rating::Silver,
-                3 => Rating::Bronze,
-                4 => Rating::None,
-                _ => Rating::GreatOne,
-            };
-            let trophy_gender = read_int(&game_proc, harvest_base_address, offsets.gender);
-            let gender = match trophy_gender {
-                1 => Gender::Male,
-                _ => Gender::Female,
-            };            
-            let fur_key = read_int(&game_proc, harvest_base_address, offsets.fur_offset);
-            let fur = get_fur_name(&game_proc, base_address, fur_key);
-            let grind_trophy = data::grinds_to_add(&species, &reserve);
-            let grind = if grind_trophy.len() > 0 {
-                Some(grind_trophy.join("/"))
-            } else {
-                None
-            };
-
-            let trophy = Trophy {
-                id: score + weight + tracking + cash as f32 + xp as f32,
-                species,
-                reserve, 
-                rating,
-                score,
-                weight,
-                fur,
-                date: Local::now().to_rfc3339(),
-                gender,
-                cash,
-                xp,
-                session_score,
-                integrity: Boolean::from(read_byte(&game_proc, harvest_base_address, offsets.integrity) == 1),
-                tracking,
-                weapon_score: read_float(&game_proc, shot_info_base_address, offsets.weapon_score),
-                shot_distance: read_float(&game_proc, shot_info_base_address, offsets.shot_distance),
-                shot_damage: read_float(&game_proc, shot_info_base_address, offsets.shot_damage) * 100.0,
-                mods: Boolean::from(using_mods(&game_directory)),
-                grind,
-            };
+        let name = get_username(&profile, base_address, mem);
+        if name != username {
+            username = name.clone();
+            events.publish(Event::User { name });
+        }
+
+        if let (MonitorMode::Record(_), Poll::Waiting) = (&mode, &poll) {
+            scanner.read_kill(mem);
+        }
+
+        if let Poll::Kill(kill) = poll {
+            let mut trophy = kill.trophy;
             if trophy.valid() {
-                if !data::trophy_exists(&trophy) {
-                    data::save_trophy(&trophy, &grind_tx, &challenge_tx);
-                    trophy_tx.send(trophy).unwrap_or_default();
-                    status_tx.send(format!("Stored {} trophy from {}", trophy_species, trophy_reserve)).unwrap_or_default();
-                } else {
-                    status_tx.send("Trophy is already saved".to_string()).unwrap_or_default();
+                match data::save_trophy(store.as_ref(), &mut trophy, &events) {
+                    Ok(true) => events.publish(Event::TrophyStored { trophy }),
+                    Ok(false) => events.publish(Event::Duplicate { trophy }),
+                    Err(e) => events.publish(Event::status(format!("Unable to save {} trophy: {}", kill.species_name, e))),
                 }
             } else {
-                status_tx.send(format!("Problem processing trophy with name {trophy_species}")).unwrap_or_default();
-                println!("{}", trophy_species);
-                println!("{}", trophy_reserve);
+                events.publish(Event::status(format!("Problem processing trophy with name {}", kill.species_name)));
+                println!("{}", kill.species_name);
+                println!("{}", kill.reserve_name);
                 println!("{:?}", trophy);
-            }                
+            }
         }
-        thread::sleep(Duration::from_secs(3));
+        mem.end_frame();
+        thread::sleep(poll_interval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: usize = 0x1_4000_0000;
+    const HARVEST: usize = 0x5000_0000;
+    const SHOT: usize = 0x5100_0000;
+    const FUR_TABLE: usize = 0x5200_0000;
+    const FUR_NAMES: usize = 0x5300_0000;
+
+    fn game_image() -> MemoryImage {
+        let offsets = OffsetProfile::built_in().fields;
+        let mut image = MemoryImage::new();
+
+        image.write_usize(BASE + 0x023F0FB8, HARVEST - 0x288);
+        image.write_string(HARVEST + offsets.species, "red-deer");
+        image.write_int(HARVEST + offsets.session_score, 10);
+        image.write_int(HARVEST + offsets.gender, 1);
+        image.write_float(HARVEST + offsets.weight, 180.5);
+        image.write_int(HARVEST + offsets.xp, 900);
+        image.write_int(HARVEST + offsets.cash, 4500);
+        image.write_float(HARVEST + offsets.score, 210.25);
+        image.write_byte(HARVEST + offsets.rating, 0);
+        image.write_byte(HARVEST + offsets.integrity, 1);
+        image.write_int(HARVEST + offsets.fur_offset, 77);
+        image.write_usize(HARVEST + offsets.reserve, HARVEST + 0x400);
+        image.write_string(HARVEST + 0x400, "hirschfelden-hunting-reserve");
+        image.write_float(BASE + offsets.tracking, 120.0);
+
+        image.write_usize(BASE + 0x023C46A8, 0x5400_0000);
+        image.write_usize(0x5400_0000 + 0xD8, 0x5500_0000);
+        image.write_usize(0x5500_0000 + 0x2E0, SHOT);
+        image.write_float(SHOT + offsets.weapon_score, 0.9);
+        image.write_float(SHOT + offsets.shot_distance, 150.0);
+        image.write_float(SHOT + offsets.shot_damage, 0.75);
+
+        image.write_usize(BASE + 0x0227B640, 0x5600_0000);
+        image.write_usize(0x5600_0000, 0x5700_0000);
+        image.write_usize(0x5700_0000 + 0x10, FUR_TABLE - 0x10);
+        image.write_usize(FUR_TABLE, FUR_NAMES);
+        image.write_int(FUR_TABLE + 0x160, 5);
+        image.write_int(FUR_TABLE + 0x164, 0x0);
+        image.write_int(FUR_TABLE + 0x168, 77);
+        image.write_int(FUR_TABLE + 0x16C, 0x20);
+        image.write_int(FUR_TABLE + 0x170, 0);
+        image.write_string(FUR_NAMES, "light-brown");
+        image.write_string(FUR_NAMES + 0x20, "piebald");
+
+        image.write_usize(BASE + 0x023C46A0, 0x5800_0000);
+        image.write_string(0x5800_0000 + 0x390, "hunter");
+        image
+    }
+
+    #[test]
+    fn test_find_fur() {
+        let image = game_image();
+        assert_eq!(Some("Piebald".to_string()), find_fur(&image, FUR_TABLE, 0x160, 3, 77));
+        assert_eq!(Some("Light Brown".to_string()), find_fur(&image, FUR_TABLE, 0x160, 3, 5));
+        assert_eq!(None, find_fur(&image, FUR_TABLE, 0x160, 1, 77));
+        assert_eq!("Unknown", get_fur_name(&OffsetProfile::built_in(), &image, BASE, 1234));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_pointer_addresses() {
+        let image = game_image();
+        let profile = OffsetProfile::built_in();
+        assert_eq!(HARVEST, profile.harvest.resolve(&image, BASE));
+        assert_eq!(SHOT, profile.shot.resolve(&image, BASE));
+        assert_eq!("hunter", get_username(&profile, BASE, &image));
+    }
+
+    #[test]
+    fn test_profile_fur_fallback() {
+        let image = game_image();
+        let mut profile = OffsetProfile::built_in();
+        profile.fur_lookups.insert(0, FurLookup {
+            table: PointerPath { base: 0x10, chain: vec![], offset: 0x0 },
+            keys_offset: 0x0,
+            max_count: 4,
+        });
+        assert_eq!("Piebald", get_fur_name(&profile, &image, BASE, 77));
+    }
+
+    #[test]
+    fn test_detects_kill() {
+        let image = game_image();
+        let mut scanner = KillScanner::new(OffsetProfile::built_in(), BASE, HARVEST, None);
+        let kill = match scanner.poll(&image) {
+            Poll::Kill(kill) => kill,
+            _ => panic!("expected a kill"),
+        };
+        let trophy = kill.trophy;
+        assert_eq!("Red Deer", kill.species_name);
+        assert_eq!("Hirschfelden Hunting Reserve", kill.reserve_name);
+        assert_eq!(Species::RedDeer, trophy.species);
+        assert_eq!(Reserve::HirschfeldenHuntingReserve, trophy.reserve);
+        assert_eq!(Rating::Diamond, trophy.rating);
+        assert_eq!(Gender::Male, trophy.gender);
+        assert_eq!("Piebald", trophy.fur);
+        assert_eq!(210.25, trophy.score);
+        assert_eq!(180.5, trophy.weight);
+        assert_eq!(150.0, trophy.shot_distance);
+        assert_eq!(75.0, trophy.shot_damage);
+        assert_eq!(Boolean::True, trophy.integrity);
+        assert_eq!(Boolean::False, trophy.mods);
+        assert_eq!(trophy.identity(), trophy.id);
+        assert!(trophy.valid());
+    }
+
+    #[test]
+    fn test_reads_weapon_and_ammo() {
+        let mut image = game_image();
+        let mut profile = OffsetProfile::built_in();
+        assert_eq!(Weapon::Unknown, match KillScanner::new(profile.clone(), BASE, HARVEST, None).poll(&image) {
+            Poll::Kill(kill) => kill.trophy.weapon,
+            _ => panic!("expected a kill"),
+        });
+
+        profile.fields.weapon = Some(0x40);
+        profile.fields.ammo = Some(0x48);
+        image.write_usize(SHOT + 0x40, SHOT + 0x100);
+        image.write_string(SHOT + 0x100, "Ranger .243");
+        image.write_string(SHOT + 0x48, "243 polymer tip");
+        let trophy = match KillScanner::new(profile, BASE, HARVEST, None).poll(&image) {
+            Poll::Kill(kill) => kill.trophy,
+            _ => panic!("expected a kill"),
+        };
+        assert_eq!(Weapon::Ranger243, trophy.weapon);
+        assert_eq!(Ammo::PolymerTip243, trophy.ammo);
+    }
+
+    #[test]
+    fn test_decode_rating() {
+        assert_eq!(Rating::Diamond, decode_rating(0));
+        assert_eq!(Rating::None, decode_rating(4));
+        assert_eq!(Rating::GreatOne, decode_rating(GREAT_ONE_RATING));
+        assert_eq!(Rating::Unknown, decode_rating(6));
+        assert_eq!(Rating::Unknown, decode_rating(0xFF));
+    }
+
+    #[test]
+    fn test_same_kill_is_ignored() {
+        let image = game_image();
+        let mut scanner = KillScanner::new(OffsetProfile::built_in(), BASE, HARVEST, None);
+        assert!(matches!(scanner.poll(&image), Poll::Kill(_)));
+        assert!(matches!(scanner.poll(&image), Poll::Waiting));
+    }
+
+    #[test]
+    fn test_waits_for_weight() {
+        let mut image = game_image();
+        image.write_float(HARVEST + OffsetProfile::built_in().fields.weight, 0.0);
+        let mut scanner = KillScanner::new(OffsetProfile::built_in(), BASE, HARVEST, None);
+        assert!(matches!(scanner.poll(&image), Poll::Waiting));
+    }
+
+    #[test]
+    fn test_game_closed() {
+        let image = game_image();
+        let mut scanner = KillScanner::new(OffsetProfile::built_in(), BASE, HARVEST, None);
+        assert!(matches!(scanner.poll(&image), Poll::Kill(_)));
+        let closed = MemoryImage::new();
+        assert!(matches!(scanner.poll(&closed), Poll::Closed));
+    }
+
+    #[test]
+    fn test_replays_recorded_kill() {
+        let recorder = RecordingSource::new(Box::new(game_image()), PathBuf::from("unused"), BASE, None, None, None);
+        let mut scanner = KillScanner::new(OffsetProfile::built_in(), BASE, HARVEST, None);
+        let recorded = match scanner.poll(&recorder) {
+            Poll::Kill(kill) => kill.trophy,
+            _ => panic!("expected a kill"),
+        };
+        let username = get_username(&OffsetProfile::built_in(), BASE, &recorder);
+
+        let snapshots = vec![Snapshot::new(BASE, None, None, None, &recorder.take_frame().unwrap())];
+        let replay = ReplaySource::new(&snapshots);
+        let mut scanner = KillScanner::new(OffsetProfile::built_in(), BASE, HARVEST, None);
+        let replayed = match scanner.poll(&replay) {
+            Poll::Kill(kill) => kill.trophy,
+            _ => panic!("expected a kill"),
+        };
+        assert_eq!(username, get_username(&OffsetProfile::built_in(), BASE, &replay));
+        assert_eq!(recorded.species, replayed.species);
+        assert_eq!(recorded.fur, replayed.fur);
+        assert_eq!(recorded.score, replayed.score);
+        assert_eq!(recorded.shot_distance, replayed.shot_distance);
+        assert_eq!(recorded.id, replayed.id);
+
+        replay.end_frame();
+        assert!(matches!(scanner.poll(&replay), Poll::Closed));
+    }
+
+    #[test]
+    fn test_replays_recorded_great_one() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut image = game_image();
+        image.write_byte(HARVEST + OffsetProfile::built_in().fields.rating, GREAT_ONE_RATING);
+        let recorder = RecordingSource::new(Box::new(image), dir.path().to_path_buf(), BASE, None, None, None);
+        let mut scanner = KillScanner::new(OffsetProfile::built_in(), BASE, HARVEST, None);
+        assert!(matches!(scanner.poll(&recorder), Poll::Kill(_)));
+        recorder.end_frame();
+
+        let replay = ReplaySource::new(&read_snapshots(dir.path()).unwrap());
+        let mut scanner = KillScanner::new(OffsetProfile::built_in(), BASE, HARVEST, None);
+        let replayed = match scanner.poll(&replay) {
+            Poll::Kill(kill) => kill.trophy,
+            _ => panic!("expected a kill"),
+        };
+        assert_eq!(Rating::GreatOne, replayed.rating);
+        assert_eq!(Species::RedDeer, replayed.species);
+    }
+
+    #[test]
+    fn test_replays_kill_recorded_while_waiting() {
+        let recorder = RecordingSource::new(Box::new(game_image()), PathBuf::from("unused"), BASE, None, None, None);
+        let mut scanner = KillScanner::new(OffsetProfile::built_in(), BASE, HARVEST, None);
+        scanner.game_open = true;
+        scanner.last_weight = 180.5;
+        assert!(matches!(scanner.poll(&recorder), Poll::Waiting));
+        scanner.read_kill(&recorder);
+
+        let snapshots = vec![Snapshot::new(BASE, None, None, None, &recorder.take_frame().unwrap())];
+        let replay = ReplaySource::new(&snapshots);
+        let mut scanner = KillScanner::new(OffsetProfile::built_in(), BASE, HARVEST, None);
+        let replayed = match scanner.poll(&replay) {
+            Poll::Kill(kill) => kill.trophy,
+            _ => panic!("expected a kill"),
+        };
+        assert_eq!(Reserve::HirschfeldenHuntingReserve, replayed.reserve);
+        assert_eq!("Piebald", replayed.fur);
+        assert_eq!(150.0, replayed.shot_distance);
+        assert_eq!(75.0, replayed.shot_damage);
+    }
+
+    #[test]
+    fn test_profile_from_signatures() {
+        let mut image = MemoryImage::new();
+        image.write(BASE, &[0xCC; 0x2000]);
+        for (address, bytes) in game_image().regions() {
+            image.write(address, &bytes);
+        }
+        image.write(BASE + 0x1000, &[0x48, 0x8B, 0x05, 0x00, 0x01, 0x00, 0x00]);
+        image.write(BASE + 0x1100, &[0x48, 0x8B, 0x0D, 0x00, 0x02, 0x00, 0x00]);
+        image.write_usize(BASE + 0x1107 + 0x200, HARVEST - 0x288);
+        let mut profile = OffsetProfile::built_in();
+        profile.harvest.base = 0x0;
+        profile.signatures = vec![
+            Signature { target: SignatureTarget::Harvest, pattern: "48 8B 0D ?? ?? ?? ??".to_string(), offset: 3, instruction_length: 7 },
+            Signature { target: SignatureTarget::Shot, pattern: "48 8B 15 ?? ?? ?? ??".to_string(), offset: 3, instruction_length: 7 },
+        ];
+        let game = Game {
+            source: Box::new(image),
+            base_address: BASE,
+            module_size: 0x2000,
+            directory: None,
+            module_hash: None,
+            profile: None,
+        };
+        let (resolved, failed) = resolve_signatures(&profile, game.source.as_ref(), game.base_address, game.module_size);
+        assert_eq!(vec![SignatureTarget::Shot], failed);
+        assert_eq!(HARVEST, resolved.harvest.resolve(game.source.as_ref(), BASE));
+
+        let (_, status) = profile_for(&game, Path::new("does_not_exist"));
+        assert_eq!("game build unknown, using Built-in offsets", status);
+        let game = Game { profile: Some(resolved.clone()), ..game };
+        let (from_snapshot, status) = profile_for(&game, Path::new("does_not_exist"));
+        assert_eq!(resolved, from_snapshot);
+        assert_eq!("using Built-in offsets from snapshot", status);
+    }
+
+    #[test]
+    fn test_saves_kill_once() {
+        let root = tempfile::tempdir().unwrap();
+        let store = data::open(root.path()).unwrap().store;
+        store.add_grind(&crate::store::tests::grind("Deer")).unwrap();
+        let events = EventBus::new();
+        let mut scanner = KillScanner::new(OffsetProfile::built_in(), BASE, HARVEST, None);
+        let mut trophy = match scanner.poll(&game_image()) {
+            Poll::Kill(kill) => kill.trophy,
+            _ => panic!("expected a kill"),
+        };
+        assert!(data::save_trophy(store.as_ref(), &mut trophy, &events).unwrap());
+        assert_eq!(Some("Deer".to_string()), trophy.grind);
+        assert!(!data::save_trophy(store.as_ref(), &mut trophy, &events).unwrap());
+        assert_eq!(1, store.read_trophies().unwrap().len());
+        assert_eq!(1, store.read_grinds().unwrap()[0].kills);
+    }
+
+    #[test]
+    fn test_not_open_is_not_closed() {
+        let closed = MemoryImage::new();
+        let mut scanner = KillScanner::new(OffsetProfile::built_in(), BASE, HARVEST, None);
+        assert!(matches!(scanner.poll(&closed), Poll::Waiting));
+    }
+}
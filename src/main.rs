@@ -3,9 +3,24 @@
 mod models;
 mod data;
 mod game_monitor;
+mod memory;
+mod snapshot;
+mod profiles;
+mod signatures;
 mod challenges;
+mod store;
+mod csv_store;
+mod sqlite_store;
+mod backup;
+mod migrations;
+mod cli;
+mod catalog;
+mod server;
+mod events;
+mod overlay;
+mod webhooks;
+mod alerts;
 
-use data::delete_challenge;
 use egui::*;
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
@@ -14,16 +29,77 @@ use std::fmt;
 use strum::{IntoEnumIterator, VariantArray};
 use models::*;
 use egui_extras::{Column, Size, StripBuilder, TableBuilder};
-use std::sync::mpsc;
 use std::sync::mpsc::Receiver;
 use chrono::prelude::*;
 use std::thread;
+use std::path::{Path, PathBuf};
+use game_monitor::MonitorConfig;
+use events::{Event, EventBus};
+use store::{SkippedRow, TrophyStore};
+use std::sync::Arc;
+use clap::Parser;
 
+const NOTIFICATION_SECONDS: f64 = 8.0;
 const ICON: &[u8] = include_bytes!("../static/icon.png");
 const SMALL_FONT: f32 = 14.0;
 const MEDIUM_FONT: f32 = 16.0;
 
+#[cfg(windows)]
+fn attach_console() {
+    use windows::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+    // release builds use the windows subsystem, so borrow the console of the
+    // shell that started us for command output
+    unsafe {
+        let _ = AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(windows))]
+fn attach_console() {}
+
 fn main() -> Result<(), eframe::Error> {
+    let cli = cli::Cli::parse();
+    if let Some(command) = &cli.command {
+        attach_console();
+        std::process::exit(cli::run_command(command, &cli.data_dir));
+    }
+    let mode = cli.monitor_mode();
+    let data_dir = cli.data_dir;
+    let mut opened = match data::open(&data_dir) {
+        Ok(opened) => opened,
+        Err(e) => {
+            eprintln!("Unable to open data directory {}: {}", data_dir.display(), e);
+            std::process::exit(1);
+        }
+    };
+    let events = EventBus::new();
+    let hooks = webhooks::load_webhooks(&webhooks::webhooks_path(&data_dir)).unwrap_or_else(|e| {
+        opened.notes.push(format!("Unable to read webhooks: {}", e));
+        Vec::new()
+    });
+    let alerts_path = alerts::alerts_path(&data_dir);
+    let alerts = alerts::Alerts::open(&alerts_path).unwrap_or_else(|e| {
+        opened.notes.push(format!("Unable to read alert rules: {}", e));
+        alerts::Alerts::empty(&alerts_path)
+    });
+    let webhook_names: Vec<String> = hooks.iter().map(|w| w.name.clone()).collect();
+    webhooks::spawn_sender(hooks.clone(), &events);
+    alerts::spawn_alerts(alerts.clone(), hooks, &events);
+    let _api = cli.api.and_then(|port| match server::ApiServer::start(opened.store.clone(), events.clone(), port) {
+        Ok(api) => {
+            opened.notes.push(format!("Serving the API on {} and the stream overlay on {}/overlay", api.url(), api.url()));
+            Some(api)
+        }
+        Err(e) => {
+            opened.notes.push(format!("Unable to serve the API on port {}: {}", port, e));
+            None
+        }
+    });
+    let config = MonitorConfig {
+        mode,
+        profiles: profiles::profiles_dir(&data_dir),
+        store: opened.store.clone(),
+    };
     let icon_data = eframe::icon_data::from_png_bytes(ICON).expect("Failed to load icon");
     let options = eframe::NativeOptions {
         viewport: ViewportBuilder::default().with_inner_size([1250.0, 950.0]).with_icon(icon_data),
@@ -33,17 +109,13 @@ fn main() -> Result<(), eframe::Error> {
         "Trophy Lodge 🎯",
         options,
         Box::new(|cc| {
-            let (status_tx, status_rx) = mpsc::channel::<String>();
-            let (user_tx, user_rx) = mpsc::channel::<String>();
-            let (trophy_tx, trophy_rx) = mpsc::channel::<Trophy>();
-            let (grind_tx, grind_rx) = mpsc::channel::<GrindKill>();
-            let (challenge_tx, challenge_rx) = mpsc::channel::<ChallengeKill>();
+            let events_rx = events.subscribe();
             thread::spawn(move || {
-                game_monitor::monitor(status_tx, trophy_tx, user_tx, grind_tx, challenge_tx);
+                game_monitor::monitor(config, events);
             });
 
             egui_extras::install_image_loaders(&cc.egui_ctx);
-            Box::new(MyApp::new(cc, status_rx, trophy_rx, user_rx, grind_rx, challenge_rx))
+            Box::new(MyApp::new(cc, opened, data_dir, events_rx, alerts, webhook_names))
         }),
     )
 }
@@ -83,40 +155,34 @@ where I: Iterator<Item = T>,
         });
 }
 
-fn filter_data(trophy_filter: &TrophyFilter, mut data: Vec<Trophy>) -> Vec<Trophy> {
-    if trophy_filter.species != Species::All {
-        data.retain(|x| x.species == trophy_filter.species);
-    }
-    if trophy_filter.reserve != Reserve::All {
-        data.retain(|x| x.reserve == trophy_filter.reserve);
-    }
-    if trophy_filter.rating != Rating::All {
-        data.retain(|x| x.rating == trophy_filter.rating);
-    }
-    if trophy_filter.gender != Gender::All {
-        data.retain(|x| x.gender == trophy_filter.gender);
-    }
-    if trophy_filter.grind != "" {
-        data.retain(|x| {
-            if let Some(grinds) = &x.grind {
-                for grind in grinds.split("/") {
-                    let same = grind.to_string() == trophy_filter.grind.clone();
-                    if same {
-                        return true;
-                    }
-                }
-            }
-            false
-        });
+// A numeric criterion: how it compares, its value and, for Between, the
+// upper bound.
+fn bound_slider<T: emath::Numeric>(ui: &mut Ui, label: &str, comparison: &mut Comparison, value: &mut T, upper: &mut T, range: std::ops::RangeInclusive<T>) {
+    ui.label(label);
+    ui.horizontal(|ui| {
+        ComboBox::new(format!("{}_comparison", label.to_lowercase()), "")
+            .width(70.0)
+            .selected_text(comparison.to_string())
+            .show_ui(ui, |ui| {
+                combo_options(ui, *comparison, Comparison::iter(), |x| *comparison = x);
+            });
+        ui.add(Slider::new(value, range.clone()));
+        if *comparison == Comparison::Between {
+            ui.label("and");
+            ui.add(Slider::new(upper, range));
+        }
+    });
+}
+
+// The typed end date when there is one, otherwise the days slider.
+fn parse_deadline(days: u32, ends: &str) -> Result<challenges::Deadline, String> {
+    let ends = ends.trim();
+    if ends.is_empty() {
+        return Ok(if days > 0 { challenges::Deadline::Days(days) } else { challenges::Deadline::None });
     }
-    match trophy_filter.sort_by {
-        SortBy::Date => data.sort_by(|a, b| b.date.cmp(&a.date)),
-        SortBy::Score => data.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap()),
-        SortBy::Weight => data.sort_by(|a, b| b.weight.partial_cmp(&a.weight).unwrap()),
-        SortBy::Rating => data.sort_by(|a, b| b.rating.cmp(&a.rating)),
-        SortBy::ShotDistance => data.sort_by(|a, b| b.shot_distance.partial_cmp(&a.shot_distance).unwrap()),
-    }    
-    data
+    NaiveDate::parse_from_str(ends, "%Y-%m-%d")
+        .map(challenges::Deadline::Date)
+        .map_err(|_| format!("{} is not a date like 2024-01-31", ends))
 }
 
 fn col_label(ui: &mut Ui, value: String) {
@@ -151,14 +217,25 @@ fn get_species(reserve: Reserve) -> Vec<Species> {
     }
 }
 
-fn show_reserve_summary<F: FnMut(Reserve)>(ui: &mut Ui, reserve: &Reserve, trophies: &Vec<Trophy>, mut capture: F) {
+fn get_ammo(weapon: Weapon) -> Vec<Ammo> {
+    if weapon.is_real() {
+        let mut ammo = weapon.ammo();
+        ammo.insert(0, Ammo::All);
+        ammo.push(Ammo::Unknown);
+        ammo
+    } else {
+        Ammo::iter().collect()
+    }
+}
+
+fn show_reserve_summary<F: FnMut(Reserve)>(ui: &mut Ui, reserve: &Reserve, trophies: &[Trophy], mut capture: F) {
     ui.vertical(|ui| {
         ui.horizontal(|ui| {
             ui.add_space(20.0);
-            let image = Image::new(reserve_image(&reserve)).fit_to_original_size(1.0).tint(Color32::from_white_alpha(180));
+            let image = Image::new(reserve_image(reserve)).fit_to_original_size(1.0).tint(Color32::from_white_alpha(180));
             let reserve_btn = ImageButton::new(image);
             if ui.add(reserve_btn).clicked() {
-                capture(reserve.clone());
+                capture(*reserve);
             };
         });
         ui.add_space(5.0);
@@ -175,7 +252,7 @@ fn show_reserve_summary<F: FnMut(Reserve)>(ui: &mut Ui, reserve: &Reserve, troph
                 great_ones += 1;
             }
         }
-        Grid::new(format!("{}_summary_metrics", reserve.to_string()))
+        Grid::new(format!("{}_summary_metrics", reserve))
         .num_columns(2)
         .striped(false)
         .spacing([5.0, 5.0])
@@ -202,13 +279,13 @@ fn show_reserve_summary<F: FnMut(Reserve)>(ui: &mut Ui, reserve: &Reserve, troph
     });    
 }
 
-fn show_species_summary(ui: &mut Ui, reserve: &Reserve, species: &Species, trophies: &Vec<Trophy>) {
+fn show_species_summary(ui: &mut Ui, reserve: &Reserve, species: &Species, trophies: &[Trophy]) {
     ui.vertical(|ui| {
         ui.vertical_centered(|ui| {
             ui.add(Label::new(RichText::new(species.to_string()).small()).wrap(false));
         });
         ui.vertical_centered(|ui| {
-            let image = Image::new(species_image(&species)).fit_to_original_size(1.0);
+            let image = Image::new(species_image(species)).fit_to_original_size(1.0);
             let reserve_btn = ImageButton::new(image);
             let _ = ui.add(reserve_btn);
         });
@@ -228,7 +305,7 @@ fn show_species_summary(ui: &mut Ui, reserve: &Reserve, species: &Species, troph
                 }
             }
         }
-        Grid::new(format!("{}_summary_metrics", species.to_string()))
+        Grid::new(format!("{}_summary_metrics", species))
         .num_columns(2)
         .striped(false)
         .spacing([5.0, 5.0])
@@ -260,7 +337,8 @@ enum Sidebar {
     Trophies,
     Grinds,
     Challenges,
-    Upgrade,
+    Alerts,
+    Data,
 }
 
 #[derive(PartialEq)]
@@ -273,11 +351,13 @@ enum ChallengeTab {
 enum TrophyTab {
     Lodge,
     Table,
+    GreatOnes,
 }
 
 struct MyApp {
+    store: Arc<dyn TrophyStore>,
+    data_dir: PathBuf,
     menu: Sidebar,
-    user_rx: Receiver<String>,
     username: String,
     trophy_tab: TrophyTab,
     trophy_reserve: Reserve,
@@ -286,36 +366,75 @@ struct MyApp {
     trophy_filter: TrophyFilter,
     trophy_cols: Vec<String>,
     selected_cols: Vec<String>,
-    status_rx: Receiver<String>,
     status_msg: String,
-    trophy_rx: Receiver<Trophy>,
     grinds: Vec<Grind>,
     grind_name: String,
     grind_species: Species,
     grind_reserve: Reserve,
-    grind_rx: Receiver<GrindKill>,
+    grind_weapon: Weapon,
+    grind_ammo: Ammo,
     challenge_tab: ChallengeTab,
     challenge: Challenge,
+    challenge_furs: String,
+    challenge_history: bool,
+    challenge_since: String,
+    challenge_preview: Option<challenges::HistoryPreview>,
+    challenge_days: u32,
+    challenge_ends: String,
     challenges: Vec<ChallengeSummary>,
-    challenge_rx: Receiver<ChallengeKill>,
-    upgrade_msg: String,
+    challenge_runs: Vec<ChallengeRun>,
+    events: Receiver<Event>,
+    schema_version: u32,
+    migrations: Vec<migrations::AppliedMigration>,
+    data_msg: String,
+    problems: Vec<SkippedRow>,
+    import_path: String,
+    import_conflict: data::OnConflict,
+    catalog: catalog::Catalog,
+    catalog_filter: catalog::CatalogFilter,
+    alerts: alerts::Alerts,
+    alert_rules: Vec<alerts::AlertRule>,
+    alert_rule: alerts::AlertRule,
+    alert_actions: alerts::ActionChoices,
+    alert_furs: String,
+    webhook_names: Vec<String>,
+    notifications: Vec<(String, f64)>,
 }
 impl MyApp {
     fn new(
         cc: &eframe::CreationContext<'_>, 
-        status_rx: Receiver<String>, 
-        trophy_rx: Receiver<Trophy>, 
-        user_rx: Receiver<String>, 
-        grind_rx: Receiver<GrindKill>,
-        challenge_rx: Receiver<ChallengeKill>,
+        opened: data::Opened,
+        data_dir: PathBuf,
+        events: Receiver<Event>,
+        alerts: alerts::Alerts,
+        webhook_names: Vec<String>,
     ) -> Self {
-        data::init();
-
         let ctx = &cc.egui_ctx;
-        let trophies: Vec<Trophy> = data::read_trophies();
+        let store = opened.store;
+        let mut status_msg = opened.notes.join(". ");
+        let trophies: Vec<Trophy> = store.read_trophies().unwrap_or_else(|e| {
+            status_msg = format!("Unable to read trophies: {}", e);
+            Vec::new()
+        });
         let trophy_filter = TrophyFilter::default();
-        let filtered_trophies = filter_data(&trophy_filter, trophies.clone());
-        let grinds = data::get_grinds();
+        let filtered_trophies = data::filter_data(&trophy_filter, trophies.clone());
+        let grinds = store.read_grinds().unwrap_or_else(|e| {
+            status_msg = format!("Unable to read grinds: {}", e);
+            Vec::new()
+        });
+        let challenges = data::refresh_challenges(store.as_ref()).unwrap_or_else(|e| {
+            status_msg = format!("Unable to read challenges: {}", e);
+            Vec::new()
+        });
+        let challenge_runs = store.read_challenge_history().unwrap_or_else(|e| {
+            status_msg = format!("Unable to read challenge history: {}", e);
+            Vec::new()
+        });
+        let problems = store.read_problems().unwrap_or_else(|e| {
+            status_msg = format!("Unable to read data problems: {}", e);
+            Vec::new()
+        });
+        let catalog = catalog::load_catalog(&catalog::catalog_dir(&data_dir));
         set_style(ctx);
 
         let mut selected_cols = default_cols();
@@ -334,8 +453,9 @@ impl MyApp {
         }
 
         Self { 
+            store,
+            data_dir,
             menu: Sidebar::Trophies, 
-            user_rx,
             username: String::from("Unknown User"),
             trophy_tab,
             trophies,
@@ -344,24 +464,102 @@ impl MyApp {
             trophy_filter,
             trophy_cols: available_cols(),
             selected_cols,
-            status_rx,
-            status_msg: "".to_string(),
-            trophy_rx,
+            status_msg,
             grinds,
             grind_name: "".to_string(),
             grind_species: Species::Unknown,
             grind_reserve: Reserve::Unknown,
-            grind_rx,
+            grind_weapon: Weapon::Unknown,
+            grind_ammo: Ammo::Unknown,
             challenge_tab: ChallengeTab::Create,
             challenge: Challenge::default(),
-            challenges: data::get_challenges(),
-            challenge_rx,
-            upgrade_msg: "".to_string(),
+            challenge_furs: String::new(),
+            challenge_history: false,
+            challenge_since: String::new(),
+            challenge_preview: None,
+            challenge_days: 0,
+            challenge_ends: String::new(),
+            challenges,
+            challenge_runs,
+            events,
+            schema_version: opened.schema_version,
+            migrations: opened.migrations,
+            data_msg: "".to_string(),
+            problems,
+            import_path: "".to_string(),
+            import_conflict: data::OnConflict::Cancel,
+            catalog,
+            catalog_filter: catalog::CatalogFilter::default(),
+            alert_rules: alerts.rules(),
+            alerts,
+            alert_rule: alerts::AlertRule::default(),
+            alert_actions: alerts::ActionChoices::from_actions(&alerts::AlertRule::default().actions),
+            alert_furs: String::new(),
+            webhook_names,
+            notifications: Vec::new(),
         }
     }
+
+    fn handle_events(&mut self, ctx: &Context) {
+        while let Ok(event) = self.events.try_recv() {
+            if let Some(status) = event.status_line() {
+                self.status_msg = status;
+            }
+            match event {
+                Event::User { name } if !name.is_empty() => self.username = name,
+                Event::TrophyStored { trophy } => {
+                    self.trophies.push(trophy);
+                    self.filtered_trophies = data::filter_data(&self.trophy_filter, self.trophies.clone());
+                }
+                Event::GrindKill { grind } => {
+                    for g in self.grinds.iter_mut().filter(|g| g.name == grind) {
+                        g.kills += 1;
+                    }
+                }
+                Event::ChallengeProgress { .. } | Event::ChallengeCompleted { .. } => self.refresh_challenges(),
+                Event::Alert { message, .. } => {
+                    self.notifications.push((message, ctx.input(|i| i.time)));
+                    ctx.send_viewport_cmd(ViewportCommand::RequestUserAttention(UserAttentionType::Informational));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Records finished runs first so a challenge that expired while nothing
+    // was being hunted still moves to the history.
+    fn refresh_challenges(&mut self) {
+        let store = self.store.as_ref();
+        let read = data::refresh_challenges(store).and_then(|challenges| Ok((challenges, store.read_challenge_history()?)));
+        match read {
+            Ok((challenges, runs)) => {
+                self.challenges = challenges;
+                self.challenge_runs = runs;
+            }
+            Err(e) => self.status_msg = format!("Unable to read challenges: {}", e),
+        }
+    }
+
+    fn show_notifications(&mut self, ctx: &Context) {
+        let now = ctx.input(|i| i.time);
+        self.notifications.retain(|(_, shown)| now - shown < NOTIFICATION_SECONDS);
+        if self.notifications.is_empty() {
+            return;
+        }
+        Area::new(Id::new("notifications"))
+            .anchor(Align2::RIGHT_BOTTOM, [-20.0, -40.0])
+            .show(ctx, |ui| {
+                Frame::popup(ui.style()).show(ui, |ui| {
+                    for (message, _) in &self.notifications {
+                        ui.label(RichText::new(message).strong().color(Color32::GOLD));
+                    }
+                });
+            });
+    }
 }
 impl eframe::App for MyApp {
     fn update(&mut self, ctx: &Context, _frame: &mut eframe::Frame) {        
+        self.handle_events(ctx);
         TopBottomPanel::top("top_panel")
             .resizable(false)
             .min_height(120.0)
@@ -397,11 +595,6 @@ impl eframe::App for MyApp {
                         .size(Size::remainder())
                         .vertical(|mut strip| {
                             strip.cell(|ui| {
-                                if let Ok(username) = self.user_rx.try_recv() {
-                                    if username != "" {
-                                        self.username = username;
-                                    }
-                                }
                                 ui.with_layout(Layout::top_down(Align::RIGHT), |ui| {
                                     ui.monospace(RichText::new(&self.username).color(Color32::DEBUG_COLOR));
                                 });
@@ -436,9 +629,13 @@ impl eframe::App for MyApp {
                 ui.add_space(5.0);
                 ui.selectable_value(&mut self.menu, Sidebar::Grinds, "Grinds");
                 ui.add_space(5.0);
-                ui.selectable_value(&mut self.menu, Sidebar::Challenges, "Challenges");
+                if ui.selectable_value(&mut self.menu, Sidebar::Challenges, "Challenges").clicked() {
+                    self.refresh_challenges();
+                }
+                ui.add_space(5.0);
+                ui.selectable_value(&mut self.menu, Sidebar::Alerts, "Alerts");
                 ui.add_space(5.0);
-                ui.selectable_value(&mut self.menu, Sidebar::Upgrade, "Upgrade");
+                ui.selectable_value(&mut self.menu, Sidebar::Data, "Data");
             });
         });
 
@@ -452,7 +649,7 @@ impl eframe::App for MyApp {
                     .vertical(|mut strip| {
                         strip.strip(|builder| {
                             builder
-                            .size(Size::exact(200.0))
+                            .size(Size::exact(300.0))
                             .size(Size::remainder())
                             .horizontal(|mut strip| {
                                 strip.cell(|ui| {
@@ -460,6 +657,8 @@ impl eframe::App for MyApp {
                                         ui.selectable_value(&mut self.trophy_tab, TrophyTab::Lodge, "Lodge");
                                         ui.add_space(5.0);
                                         ui.selectable_value(&mut self.trophy_tab, TrophyTab::Table, "Table");
+                                        ui.add_space(5.0);
+                                        ui.selectable_value(&mut self.trophy_tab, TrophyTab::GreatOnes, "Great Ones");
                                         ui.add_space(20.0);
                                     });
                                 });
@@ -561,11 +760,11 @@ impl eframe::App for MyApp {
                                             .show(ui, |ui| {
                                                 create_combo(ui, "Reserve", self.trophy_filter.reserve, Reserve::iter(), |x| {
                                                     self.trophy_filter.reserve = x;
-                                                    self.filtered_trophies = filter_data(&self.trophy_filter, self.trophies.clone());
+                                                    self.filtered_trophies = data::filter_data(&self.trophy_filter, self.trophies.clone());
                                                 });
                                                 create_combo(ui, "Rating", self.trophy_filter.rating, Rating::iter(), |x| {
                                                     self.trophy_filter.rating = x;
-                                                    self.filtered_trophies = filter_data(&self.trophy_filter, self.trophies.clone());
+                                                    self.filtered_trophies = data::filter_data(&self.trophy_filter, self.trophies.clone());
                                                 });
                                                 ui.label("Grind");
                                                 ComboBox::new("grind_filter", "")
@@ -576,7 +775,7 @@ impl eframe::App for MyApp {
                                                         let combo = ui.selectable_value(&mut self.trophy_filter.grind, g.name.clone(), g.name.clone());
                                                         if combo.clicked() {
                                                             self.trophy_filter.grind = g.name.clone();
-                                                            self.filtered_trophies = filter_data(&self.trophy_filter, self.trophies.clone());
+                                                            self.filtered_trophies = data::filter_data(&self.trophy_filter, self.trophies.clone());
                                                         }
                                                     }
                                                 });                                                              
@@ -592,17 +791,27 @@ impl eframe::App for MyApp {
                                                 let species = get_species(self.trophy_filter.reserve);
                                                 create_combo(ui, "Species", self.trophy_filter.species, species.into_iter(), |x| { 
                                                     self.trophy_filter.species = x;
-                                                    self.filtered_trophies = filter_data(&self.trophy_filter, self.trophies.clone());                                                                        
+                                                    self.filtered_trophies = data::filter_data(&self.trophy_filter, self.trophies.clone());                                                                        
                                                 });
                                                 create_combo(ui, "Gender", self.trophy_filter.gender, Gender::iter(), |x| {
                                                     self.trophy_filter.gender = x;
-                                                    self.filtered_trophies = filter_data(&self.trophy_filter, self.trophies.clone());
+                                                    self.filtered_trophies = data::filter_data(&self.trophy_filter, self.trophies.clone());
                                                 });                                
                                                 create_combo(ui, "Sort By", self.trophy_filter.sort_by, SortBy::iter(), |x| {
                                                     self.trophy_filter.sort_by = x;
-                                                    self.filtered_trophies = filter_data(&self.trophy_filter, self.trophies.clone());
+                                                    self.filtered_trophies = data::filter_data(&self.trophy_filter, self.trophies.clone());
                                                 });                                  
                                                 ui.end_row();  
+                                                create_combo(ui, "Weapon", self.trophy_filter.weapon, Weapon::iter(), |x| {
+                                                    self.trophy_filter.weapon = x;
+                                                    self.trophy_filter.ammo = Ammo::All;
+                                                    self.filtered_trophies = data::filter_data(&self.trophy_filter, self.trophies.clone());
+                                                });
+                                                create_combo(ui, "Ammo", self.trophy_filter.ammo, get_ammo(self.trophy_filter.weapon).into_iter(), |x| {
+                                                    self.trophy_filter.ammo = x;
+                                                    self.filtered_trophies = data::filter_data(&self.trophy_filter, self.trophies.clone());
+                                                });
+                                                ui.end_row();
                 
                                             });
                                         ui.add_space(10.0);
@@ -633,10 +842,6 @@ impl eframe::App for MyApp {
                 
                                     ui.add_space(20.0);
                 
-                                    if let Ok(trophy) = self.trophy_rx.try_recv() {
-                                        self.trophies.push(trophy);
-                                        self.filtered_trophies = filter_data(&self.trophy_filter, self.trophies.clone());                        
-                                    }
                                     ScrollArea::horizontal().show(ui, |ui| {
                                         let trophies = TableBuilder::new(ui)
                                         .striped(true)
@@ -647,8 +852,8 @@ impl eframe::App for MyApp {
         
                                         trophies.header(30.0, |mut header| {
                                             self.selected_cols.sort_by(|a, b| {
-                                                let trophy_col_a = TrophyCols::from_str(&a).unwrap();
-                                                let trophy_col_b = TrophyCols::from_str(&b).unwrap();
+                                                let trophy_col_a = TrophyCols::from_str(a).unwrap();
+                                                let trophy_col_b = TrophyCols::from_str(b).unwrap();
                                                 trophy_col_a.cmp(&trophy_col_b)
                                             });
                                             for h in self.selected_cols.iter() {
@@ -662,7 +867,8 @@ impl eframe::App for MyApp {
                                         .body(|body| {
                                             body.rows(30.0, self.filtered_trophies.len(), |mut row| {    
                                                 let trophy = self.filtered_trophies.get(row.index()).unwrap();
-                                                let row_index = row.index().clone();
+                                                let row_index = row.index();
+                                                row.set_selected(self.alert_rules.iter().any(|r| r.highlights(trophy)));
                                                 if self.selected_cols.contains(&"Species".to_string()) {
                                                     row.col(|ui| { 
                                                         col_label(ui, trophy.species.to_string());
@@ -748,6 +954,19 @@ impl eframe::App for MyApp {
                                                         let shot_damage = format!("{:.0}%", trophy.shot_damage);
                                                         col_label(ui, shot_damage);
                                                     });        
+                                                }
+                                                if self.selected_cols.contains(&"Weapon".to_string()) {
+                                                    row.col(|ui| {
+                                                        col_label(ui, trophy.weapon.to_string());
+                                                    });
+                                                }
+                                                if self.selected_cols.contains(&"Ammo".to_string()) {
+                                                    row.col(|ui| {
+                                                        let ammo = ui.vertical_centered(|ui| ui.add(Label::new(trophy.ammo.to_string()).wrap(false))).inner;
+                                                        if trophy.ammo.is_real() {
+                                                            ammo.on_hover_text(format!("Class {}", trophy.ammo.class()));
+                                                        }
+                                                    });
                                                 }                                         
                                                 if self.selected_cols.contains(&"Mods".to_string()) {
                                                     row.col(|ui| { 
@@ -783,6 +1002,52 @@ impl eframe::App for MyApp {
                                     });
         
                                 }
+                                // GREAT ONES
+                                TrophyTab::GreatOnes => {
+                                    let great_ones = data::great_ones(&self.trophies, &self.grinds);
+                                    if great_ones.is_empty() {
+                                        ui.label("No Great Ones yet.");
+                                        return;
+                                    }
+                                    ScrollArea::horizontal().show(ui, |ui| {
+                                        let table = TableBuilder::new(ui)
+                                            .striped(true)
+                                            .resizable(true)
+                                            .sense(Sense::hover())
+                                            .max_scroll_height(f32::INFINITY)
+                                            .columns(Column::auto(), 8);
+                                        table.header(30.0, |mut header| {
+                                            for name in ["Species", "Reserve", "Fur", "Score", "Date", "Grind", "Grind Kills", "Days Into Grind"] {
+                                                header.col(|ui| {
+                                                    ui.vertical_centered(|ui| {
+                                                        ui.add(Label::new(RichText::new(name).strong()).wrap(false));
+                                                    });
+                                                });
+                                            }
+                                        }).body(|body| {
+                                            body.rows(30.0, great_ones.len(), |mut row| {
+                                                let great_one = &great_ones[row.index()];
+                                                let trophy = &great_one.trophy;
+                                                let date = DateTime::parse_from_rfc3339(&trophy.date).map(|d| d.format("%Y-%m-%d").to_string()).unwrap_or(trophy.date.clone());
+                                                let cells = [
+                                                    trophy.species.to_string(),
+                                                    trophy.reserve.to_string(),
+                                                    trophy.fur.clone(),
+                                                    format!("{:.3}", trophy.score),
+                                                    date,
+                                                    great_one.grind.clone().unwrap_or_default(),
+                                                    great_one.grind.as_ref().map(|_| great_one.grind_kills.to_string()).unwrap_or_default(),
+                                                    great_one.grind_days.map(|d| d.to_string()).unwrap_or_default(),
+                                                ];
+                                                for cell in cells {
+                                                    row.col(|ui| {
+                                                        col_label(ui, cell);
+                                                    });
+                                                }
+                                            });
+                                        });
+                                    });
+                                }
                             }
                         });
                     });
@@ -807,38 +1072,44 @@ impl eframe::App for MyApp {
                                 self.grind_species = x;                                                                     
                             });           
                             ui.end_row();       
+                            create_combo(ui, "Weapon", self.grind_weapon, Weapon::iter(), |x| {
+                                self.grind_weapon = x;
+                                self.grind_ammo = Ammo::Unknown;
+                            });
+                            ui.end_row();
+                            create_combo(ui, "Ammo", self.grind_ammo, get_ammo(self.grind_weapon).into_iter(), |x| {
+                                self.grind_ammo = x;
+                            });
+                            ui.end_row();
                         });
                         ui.add_space(10.0);
                         ui.style_mut().visuals.widgets.hovered.weak_bg_fill = Color32::DARK_GREEN;
                         if ui.button("Start Grind").clicked() {
-                            let grind = Grind {
-                                name: self.grind_name.clone(),
-                                species: self.grind_species.clone(),
-                                reserve: self.grind_reserve.clone(),
-                                active: true,
-                                start: Local::now().to_rfc3339().to_owned(),
-                                kills: 0,
-                                is_deleted: false,
-                            };
-                            if grind.valid(&self.grinds) {
-                                data::add_grind(grind.clone());
-                                self.grinds.push(grind);
-                                self.grind_name = "".to_string();
-                                self.grind_species = Species::Unknown;
-                                self.grind_reserve = Reserve::Unknown;
+                            match data::add_grind(self.store.as_ref(), &self.grind_name, self.grind_species, self.grind_reserve, self.grind_weapon, self.grind_ammo) {
+                                Ok(grind) => {
+                                    self.grinds.push(grind);
+                                    self.grind_name = "".to_string();
+                                    self.grind_species = Species::Unknown;
+                                    self.grind_reserve = Reserve::Unknown;
+                                    self.grind_weapon = Weapon::Unknown;
+                                    self.grind_ammo = Ammo::Unknown;
+                                }
+                                Err(e) => self.status_msg = format!("Unable to save grind: {}", e),
                             }
                         }                 
                         ui.add_space(20.0);
                         ui.separator();
                     });
                     ui.add_space(20.0);
+                    let store = self.store.clone();
+                    let mut error = None;
                     ScrollArea::horizontal().show(ui, |ui| {
                         let grinds = TableBuilder::new(ui)
                             .striped(true)
                             .resizable(true)
                             .sense(Sense::hover())
                             .max_scroll_height(f32::INFINITY)
-                            .columns(Column::auto(), 7);
+                            .columns(Column::auto(), 8);
                         grinds.header(30.0, |mut header| {
                             header.col(|ui| {
                                 ui.vertical_centered(|ui| {
@@ -855,6 +1126,11 @@ impl eframe::App for MyApp {
                                     ui.add(Label::new(RichText::new("Reserve").strong()).wrap(false));
                                 });
                             }); 
+                            header.col(|ui| {
+                                ui.vertical_centered(|ui| {
+                                    ui.add(Label::new(RichText::new("Weapon").strong()).wrap(false));
+                                });
+                            });
                             header.col(|ui| {
                                 ui.vertical_centered(|ui| {
                                     ui.add(Label::new(RichText::new("Days").strong()).wrap(false));
@@ -878,13 +1154,6 @@ impl eframe::App for MyApp {
                         }).body(|body| {
                             self.grinds.retain(|x| !x.is_deleted);
 
-                            if let Ok(grind_kill) = self.grind_rx.try_recv() {
-                                for grind in self.grinds.iter_mut() {
-                                    if grind.name == grind_kill.name {
-                                        grind.kills += 1;
-                                    }
-                                }
-                            } 
                             body.rows(30.0, self.grinds.len(), |mut row| {
                                 let grind = self.grinds.get_mut(row.index()).unwrap();
                                 row.col(|ui| {
@@ -896,6 +1165,13 @@ impl eframe::App for MyApp {
                                 row.col(|ui| {
                                     col_label(ui, grind.reserve.to_string());
                                 });
+                                row.col(|ui| {
+                                    let weapon: Vec<String> = [grind.weapon.is_real().then(|| grind.weapon.to_string()), grind.ammo.is_real().then(|| grind.ammo.to_string())]
+                                        .into_iter()
+                                        .flatten()
+                                        .collect();
+                                    col_label(ui, if weapon.is_empty() { "Any".to_string() } else { weapon.join(", ") });
+                                });
                                 row.col(|ui| {
                                     let past = DateTime::parse_from_rfc3339(&grind.start).unwrap();
                                     let now = Local::now();
@@ -911,14 +1187,18 @@ impl eframe::App for MyApp {
                                             ui.style_mut().visuals.widgets.hovered.weak_bg_fill = Color32::BROWN;
                                             if ui.button("Stop").clicked() {
                                                 println!("Stop grind");
-                                                data::stop_grind(grind.name.clone());
+                                                if let Err(e) = data::stop_grind(store.as_ref(), &grind.name) {
+                                                    error = Some(format!("Unable to stop grind: {}", e));
+                                                }
                                                 grind.active = false;                                        
                                             }
                                         } else {
                                             ui.style_mut().visuals.widgets.hovered.weak_bg_fill = Color32::DARK_GREEN;
                                             if ui.button("Start").clicked() {
                                                 println!("Start grind");
-                                                data::start_grind(grind.name.clone());
+                                                if let Err(e) = data::start_grind(store.as_ref(), &grind.name) {
+                                                    error = Some(format!("Unable to start grind: {}", e));
+                                                }
                                                 grind.active = true;
                                             }
                                         }
@@ -928,7 +1208,9 @@ impl eframe::App for MyApp {
                                     ui.vertical_centered(|ui| {
                                         ui.style_mut().visuals.widgets.hovered.weak_bg_fill = Color32::BROWN;
                                         if ui.button("Delete").clicked() {
-                                            data::remove_grind(grind.name.clone());
+                                            if let Err(e) = data::remove_grind(store.as_ref(), &grind.name) {
+                                                error = Some(format!("Unable to delete grind: {}", e));
+                                            }
                                             grind.is_deleted = true;
                                         }
                                     });
@@ -936,6 +1218,9 @@ impl eframe::App for MyApp {
                             });
                         });
                     });
+                    if let Some(error) = error {
+                        self.status_msg = error;
+                    }
                 },
                 // CHALLENGES
                 Sidebar::Challenges => {
@@ -993,6 +1278,18 @@ impl eframe::App for MyApp {
                                         self.challenge.public = x;
                                     });
                                     ui.end_row();
+                                    create_combo(ui, "Count", self.challenge.collect, Collect::iter(), |x| {
+                                        self.challenge.collect = x;
+                                    });
+                                    create_combo(ui, "Weapon", self.challenge.weapon, Weapon::iter(), |x| {
+                                        self.challenge.weapon = x;
+                                        self.challenge.ammo = Ammo::Unknown;
+                                    });
+                                    ui.end_row();
+                                    create_combo(ui, "Ammo", self.challenge.ammo, get_ammo(self.challenge.weapon).into_iter(), |x| {
+                                        self.challenge.ammo = x;
+                                    });
+                                    ui.end_row();
                                 });
                                 ui.add_space(10.0);
                                 Grid::new("create_challeng_sliders")
@@ -1000,37 +1297,98 @@ impl eframe::App for MyApp {
                                 .striped(false)
                                 .spacing([10.0, 10.0])
                                 .show(ui, |ui| {
-                                    ui.label("Shot Damage (min)");
-                                    ui.add(Slider::new(&mut self.challenge.shot_damage, 0..=100));
-                                    ui.label("Score (min)");
-                                    ui.add(Slider::new(&mut self.challenge.score, 0.0..=1100.0));
+                                    let c = &mut self.challenge;
+                                    bound_slider(ui, "Shot Damage", &mut c.shot_damage_comparison, &mut c.shot_damage, &mut c.shot_damage_upper, 0..=100);
+                                    bound_slider(ui, "Score", &mut c.score_comparison, &mut c.score, &mut c.score_upper, 0.0..=1100.0);
                                     ui.end_row();
-                                    ui.label("Shot Distance (min)");
-                                    ui.add(Slider::new(&mut self.challenge.shot_distance, 0..=1000));
-                                    ui.label("Weight (min)");
-                                    ui.add(Slider::new(&mut self.challenge.weight, 0.0..=2000.0));
+                                    bound_slider(ui, "Shot Distance", &mut c.shot_distance_comparison, &mut c.shot_distance, &mut c.shot_distance_upper, 0..=1000);
+                                    bound_slider(ui, "Weight", &mut c.weight_comparison, &mut c.weight, &mut c.weight_upper, 0.0..=2000.0);
                                     ui.end_row();
-                                    ui.label("Tracking (max)");
-                                    ui.add(Slider::new(&mut self.challenge.tracking, 0..=1000));
-                                    ui.label("Kills").on_hover_text("Number of kills to complete the challenge");
+                                    bound_slider(ui, "Tracking", &mut c.tracking_comparison, &mut c.tracking, &mut c.tracking_upper, 0..=1000);
+                                    if self.challenge.collect == Collect::Kills {
+                                        ui.label("Kills").on_hover_text("Number of kills to complete the challenge");
+                                    } else {
+                                        ui.label("Different").on_hover_text(format!("Number of different {} among the kills to complete the challenge", self.challenge.collect.to_string().to_lowercase()));
+                                    }
                                     ui.add(Slider::new(&mut self.challenge.kills, 1..=50));
                                     ui.end_row();
+                                    ui.label("Furs").on_hover_text("Comma separated furs to collect, each needing its own kills");
+                                    ui.add(TextEdit::singleline(&mut self.challenge_furs).min_size([300.0, 20.0].into()));
+                                    if ui.button("Seen Furs").on_hover_text("Every fur stored for the species").clicked() {
+                                        self.challenge_furs = data::known_furs(&self.trophies, self.challenge.species).join(", ");
+                                    }
+                                    ui.end_row();
+                                    ui.label("Days").on_hover_text("Days to complete the challenge from its start, 0 for no time limit");
+                                    ui.add(Slider::new(&mut self.challenge_days, 0..=90));
+                                    ui.label("or Ends").on_hover_text("The last day of the challenge, used instead of the days");
+                                    ui.add(TextEdit::singleline(&mut self.challenge_ends).hint_text("YYYY-MM-DD").desired_width(120.0));
+                                    ui.end_row();
                                 });
                                 ui.add_space(10.0);
+                                ui.horizontal(|ui| {
+                                    if ui.checkbox(&mut self.challenge_history, "Count stored trophies").changed() {
+                                        self.challenge_preview = None;
+                                    }
+                                    if self.challenge_history {
+                                        ui.label("since");
+                                        ui.add(TextEdit::singleline(&mut self.challenge_since).hint_text("YYYY-MM-DD, empty for all").desired_width(180.0));
+                                    }
+                                });
+                                if let Some(preview) = &self.challenge_preview {
+                                    ui.add_space(10.0);
+                                    ui.label(format!("{} stored trophies would count", preview.counted.len()));
+                                    ScrollArea::vertical().id_source("challenge_preview").max_height(150.0).show(ui, |ui| {
+                                        for t in preview.counted.iter() {
+                                            ui.label(format!("{}  {} {} on {}, score {:.2}, {} fur", &t.date[..10.min(t.date.len())], t.rating, t.species, t.reserve, t.score, t.fur));
+                                        }
+                                    });
+                                }
+                                ui.add_space(10.0);
                                 ui.horizontal(|ui| {
                                     ui.style_mut().visuals.widgets.hovered.weak_bg_fill = Color32::BROWN;
                                     if ui.button("Reset").clicked() {
                                         self.challenge = Challenge::default();
+                                        self.challenge_furs.clear();
+                                        self.challenge_preview = None;
+                                        self.challenge_days = 0;
+                                        self.challenge_ends.clear();
                                     }
                                     ui.add_space(10.0);
                                     ui.style_mut().visuals.widgets.hovered.weak_bg_fill = Color32::DARK_GREEN;
-                                    if ui.button("Start Challenge").clicked() {
-                                        if self.challenge.valid() && !data::challenge_exists(&self.challenge) {
-                                            self.challenge.kills_remaining = self.challenge.kills;
-                                            self.challenge.start = Local::now().to_rfc3339();
-                                            data::save_challenge(&self.challenge);
-                                            self.challenge = Challenge::default();
-                                            self.challenges = data::get_challenges();
+                                    let ready = self.challenge.valid() && !self.store.challenge_exists(&self.challenge.name).unwrap_or(true);
+                                    let furs: Vec<String> = self.challenge_furs.split(',').map(|f| f.trim().to_string()).filter(|f| !f.is_empty()).collect();
+                                    let deadline = parse_deadline(self.challenge_days, &self.challenge_ends);
+                                    if self.challenge_history && ui.button("Preview").clicked() && ready {
+                                        let since = self.challenge_since.trim();
+                                        match (NaiveDate::parse_from_str(since, "%Y-%m-%d"), &deadline) {
+                                            (Err(_), _) if !since.is_empty() => self.status_msg = format!("{} is not a date like 2024-01-31", since),
+                                            (_, Err(e)) => self.status_msg = e.clone(),
+                                            (day, Ok(deadline)) => match data::preview_challenge(self.store.as_ref(), &self.challenge, &furs, day.ok(), *deadline) {
+                                                Ok(preview) => self.challenge_preview = Some(preview),
+                                                Err(e) => self.status_msg = format!("Unable to preview challenge: {}", e),
+                                            },
+                                        }
+                                    }
+                                    let start = (!self.challenge_history || self.challenge_preview.is_some()) && ui.button("Start Challenge").clicked() && ready;
+                                    if start {
+                                        let saved = match (self.challenge_preview.take(), deadline) {
+                                            (Some(preview), _) => data::commit_challenge(self.store.as_ref(), &preview),
+                                            (None, Ok(deadline)) => {
+                                                self.challenge.kills_remaining = self.challenge.kills;
+                                                self.challenge.start = Local::now().to_rfc3339();
+                                                data::save_challenge(self.store.as_ref(), &self.challenge, &furs, deadline)
+                                            }
+                                            (None, Err(e)) => Err(store::DataError::Invalid(e)),
+                                        };
+                                        match saved.and_then(|_| data::get_challenges(self.store.as_ref())) {
+                                            Ok(challenges) => {
+                                                self.challenge = Challenge::default();
+                                                self.challenge_furs.clear();
+                                                self.challenge_days = 0;
+                                                self.challenge_ends.clear();
+                                                self.challenges = challenges;
+                                            }
+                                            Err(e) => self.status_msg = format!("Unable to save challenge: {}", e),
                                         }
                                     }
                                 });
@@ -1039,19 +1397,123 @@ impl eframe::App for MyApp {
                             },
                             ChallengeTab::Discover => {
                                 ui.add_space(10.0);
-                                ui.label("coming soon...");
+                                ui.label(format!("Browse the built in challenges and any .toml or .json files in {}", catalog::catalog_dir(&self.data_dir).display()));
+                                ui.add_space(10.0);
+                                Grid::new("catalog_filter")
+                                .num_columns(4)
+                                .spacing([10.0, 10.0])
+                                .show(ui, |ui| {
+                                    create_combo(ui, "Reserve", self.catalog_filter.reserve, Reserve::iter(), |x| {
+                                        self.catalog_filter.reserve = x;
+                                    });
+                                    create_combo(ui, "Species", self.catalog_filter.species, get_species(self.catalog_filter.reserve).into_iter(), |x| {
+                                        self.catalog_filter.species = x;
+                                    });
+                                    ui.end_row();
+                                    create_combo(ui, "Rating", self.catalog_filter.rating, Rating::iter(), |x| {
+                                        self.catalog_filter.rating = x;
+                                    });
+                                    ui.label("Search");
+                                    ui.add(TextEdit::singleline(&mut self.catalog_filter.text).min_size([150.0, 20.0].into()));
+                                    ui.end_row();
+                                });
+                                ui.add_space(10.0);
+                                ui.horizontal(|ui| {
+                                    if ui.button("Clear").clicked() {
+                                        self.catalog_filter = catalog::CatalogFilter::default();
+                                    }
+                                    ui.add_space(10.0);
+                                    if ui.button("Reload").clicked() {
+                                        self.catalog = catalog::load_catalog(&catalog::catalog_dir(&self.data_dir));
+                                    }
+                                });
+                                ui.add_space(10.0);
+                                let mut start = None;
+                                Grid::new("catalog")
+                                .num_columns(4)
+                                .striped(true)
+                                .spacing([20.0, 10.0])
+                                .show(ui, |ui| {
+                                    ui.strong("Name");
+                                    ui.strong("Criteria");
+                                    ui.strong("Source");
+                                    ui.label("");
+                                    ui.end_row();
+                                    for entry in catalog::search(&self.catalog, &self.catalog_filter) {
+                                        ui.label(&entry.definition.name).on_hover_text(&entry.definition.description);
+                                        ui.vertical(|ui| {
+                                            for criterion in &entry.definition.criteria {
+                                                ui.label(criterion.to_string());
+                                            }
+                                            if entry.definition.days > 0 {
+                                                ui.small(format!("within {} days", entry.definition.days));
+                                            }
+                                        });
+                                        ui.label(entry.source.label());
+                                        if ui.button("Start").clicked() {
+                                            start = Some(entry.definition.clone());
+                                        }
+                                        ui.end_row();
+                                    }
+                                });
+                                if let Some(definition) = start {
+                                    let store = self.store.as_ref();
+                                    match data::import_challenge(store, &definition, self.import_conflict).and_then(|name| data::get_challenges(store).map(|c| (name, c))) {
+                                        Ok((name, challenges)) => {
+                                            self.status_msg = format!("Challenge {} started", name);
+                                            self.challenges = challenges;
+                                        }
+                                        Err(e) => self.status_msg = format!("Unable to start challenge: {}", e),
+                                    }
+                                }
+                                for problem in &self.catalog.problems {
+                                    ui.colored_label(Color32::LIGHT_RED, problem);
+                                }
+                                ui.add_space(20.0);
+                                ui.label("Start a challenge someone shared with you from its .toml or .json file. Use Export on a challenge below to share your own.");
+                                ui.add_space(10.0);
+                                Grid::new("import_challenge")
+                                .num_columns(2)
+                                .spacing([10.0, 10.0])
+                                .show(ui, |ui| {
+                                    ui.label("File");
+                                    ui.add(TextEdit::singleline(&mut self.import_path).min_size([400.0, 20.0].into()));
+                                    ui.end_row();
+                                    create_combo(ui, "If it exists", self.import_conflict, data::OnConflict::iter(), |x| {
+                                        self.import_conflict = x;
+                                    });
+                                    ui.end_row();
+                                });
+                                ui.add_space(10.0);
+                                ui.style_mut().visuals.widgets.hovered.weak_bg_fill = Color32::DARK_GREEN;
+                                if ui.button("Import Challenge").clicked() {
+                                    let store = self.store.as_ref();
+                                    let imported = data::read_definition(Path::new(self.import_path.trim()))
+                                        .and_then(|definition| data::import_challenge(store, &definition, self.import_conflict));
+                                    match imported.and_then(|name| data::get_challenges(store).map(|c| (name, c))) {
+                                        Ok((name, challenges)) => {
+                                            self.status_msg = format!("Challenge {} started", name);
+                                            self.challenges = challenges;
+                                            self.import_path = "".to_string();
+                                        }
+                                        Err(e) => self.status_msg = format!("Unable to import challenge: {}", e),
+                                    }
+                                }
                                 ui.add_space(20.0);
                                 ui.separator();
                             }
                         }
                     });
                     ui.add_space(20.0);
+                    let store = self.store.clone();
+                    let mut error = None;
+                    let mut shared = None;
                     ScrollArea::horizontal().show(ui, |ui| {
                         let challenges = TableBuilder::new(ui)
                             .striped(true)
                             .resizable(true)
                             .max_scroll_height(f32::INFINITY)
-                            .columns(Column::auto(), 5);  
+                            .columns(Column::auto(), 7);  
                         challenges.header(30.0, |mut header| {
                             header.col(|ui| {
                                 ui.vertical_centered(|ui| {
@@ -1072,15 +1534,22 @@ impl eframe::App for MyApp {
                                     ui.add(Label::new(RichText::new("Progress").strong()).wrap(false));
                                 });
                             });
+                            header.col(|ui| {
+                                ui.vertical_centered(|ui| {
+                                    ui.add(Label::new(RichText::new("Status").strong()).wrap(false));
+                                });
+                            });
+                            header.col(|ui| {
+                                ui.vertical_centered(|ui| {
+                                    ui.add(Label::new(RichText::new("Share").strong()).wrap(false));
+                                });
+                            });
                             header.col(|ui| {
                                 ui.vertical_centered(|ui| {
                                     ui.add(Label::new(RichText::new("Delete").strong()).wrap(false));
                                 });
                             });
                         }).body(|body| {
-                            if let Ok(_) = self.challenge_rx.try_recv() {
-                                self.challenges = data::get_challenges();
-                            }                             
                             self.challenges.retain(|x| !x.is_deleted);
 
 
@@ -1100,18 +1569,52 @@ impl eframe::App for MyApp {
                                 });
                                 row.col(|ui| {
                                     ui.vertical_centered(|ui| {
-                                        if challenge.percent == 1.0 {
-                                            ui.label(RichText::new("100% 👍").color(Color32::GOLD));
+                                        let progress = if challenge.percent == 1.0 {
+                                            ui.label(RichText::new("100% 👍").color(Color32::GOLD))
                                         } else { 
-                                            ui.add(ProgressBar::new(challenge.percent).show_percentage());
+                                            ui.add(ProgressBar::new(challenge.percent).show_percentage())
+                                        };
+                                        let mut details: Vec<String> = challenge.furs.iter().map(|f| format!("{}: {}/{}", f.fur, f.collected, f.total)).collect();
+                                        if !challenge.collected.is_empty() {
+                                            details.push(format!("Collected: {}", challenge.collected.join(", ")));
+                                        }
+                                        if !details.is_empty() {
+                                            progress.on_hover_text(details.join("\n"));
                                         }
                                     });
                                 })  ;
+                                row.col(|ui| {
+                                    let left = DateTime::parse_from_rfc3339(&challenge.end).ok().map(|end| end.signed_duration_since(Local::now()));
+                                    let (text, color) = match (challenge.status, left) {
+                                        (ChallengeStatus::Completed, _) => (challenge.status.to_string(), Color32::GOLD),
+                                        (ChallengeStatus::Expired, _) => (challenge.status.to_string(), Color32::LIGHT_RED),
+                                        (_, Some(left)) if left.num_days() > 0 => (format!("{} days left", left.num_days()), ui.visuals().text_color()),
+                                        (_, Some(left)) => (format!("{} hours left", left.num_hours().max(0)), Color32::YELLOW),
+                                        (_, None) => (challenge.status.to_string(), ui.visuals().text_color()),
+                                    };
+                                    let status = ui.vertical_centered(|ui| ui.add(Label::new(RichText::new(text).color(color)).wrap(false))).inner;
+                                    if !challenge.end.is_empty() {
+                                        status.on_hover_text(format!("Ends {}", &challenge.end[..16.min(challenge.end.len())].replace('T', " ")));
+                                    }
+                                });
+                                row.col(|ui| {
+                                    ui.vertical_centered(|ui| {
+                                        if ui.button("Export").clicked() {
+                                            let path = data::shared_path(&self.data_dir, &challenge.name);
+                                            shared = Some(match data::export_challenge(store.as_ref(), &challenge.name, &path) {
+                                                Ok(()) => format!("Challenge exported to {}", path.display()),
+                                                Err(e) => format!("Unable to export challenge: {}", e),
+                                            });
+                                        }
+                                    });
+                                });
                                 row.col(|ui| {
                                     ui.vertical_centered(|ui| {
                                         ui.style_mut().visuals.widgets.hovered.weak_bg_fill = Color32::BROWN;
                                         if ui.button("Delete").clicked() {
-                                            delete_challenge(&challenge.name);
+                                            if let Err(e) = store.delete_challenge(&challenge.name) {
+                                                error = Some(format!("Unable to delete challenge: {}", e));
+                                            }
                                             challenge.is_deleted = true;
                                         }
                                     });
@@ -1119,26 +1622,279 @@ impl eframe::App for MyApp {
                             });
                         });
                     });
+                    if let Some(message) = error.or(shared) {
+                        self.status_msg = message;
+                    }
+                    ui.add_space(20.0);
+                    ui.collapsing(format!("History ({})", self.challenge_runs.len()), |ui| {
+                        Grid::new("challenge_runs")
+                        .num_columns(5)
+                        .striped(true)
+                        .spacing([20.0, 8.0])
+                        .show(ui, |ui| {
+                            for label in ["Challenge", "Status", "Progress", "Started", "Finished"] {
+                                ui.label(RichText::new(label).strong());
+                            }
+                            ui.end_row();
+                            for run in self.challenge_runs.iter().rev() {
+                                ui.label(&run.name).on_hover_text(&run.description);
+                                ui.label(RichText::new(run.status.to_string()).color(if run.status == ChallengeStatus::Completed { Color32::GOLD } else { Color32::LIGHT_RED }));
+                                ui.label(format!("{:.0}%", run.percent * 100.0));
+                                ui.label(&run.start[..10.min(run.start.len())]);
+                                ui.label(&run.finished[..10.min(run.finished.len())]);
+                                ui.end_row();
+                            }
+                        });
+                    });
                 },
-                Sidebar::Upgrade => {
+                // ALERTS
+                Sidebar::Alerts => {
+                    ui.collapsing("Create", |ui| {
+                        ui.add_space(10.0);
+                        ui.small("(use Unknown or 0 to ignore the field, leave furs empty for any fur)");
+                        ui.add_space(10.0);
+                        Grid::new("create_alert")
+                        .num_columns(4)
+                        .spacing([10.0, 10.0])
+                        .show(ui, |ui| {
+                            ui.label("Name");
+                            ui.add(TextEdit::singleline(&mut self.alert_rule.name).min_size([150.0, 20.0].into()));
+                            ui.label("Furs");
+                            ui.add(TextEdit::singleline(&mut self.alert_furs).min_size([150.0, 20.0].into()))
+                                .on_hover_text("Comma separated, any of them matches");
+                            ui.end_row();
+                            create_combo(ui, "Reserve", self.alert_rule.criterion.reserve, Reserve::iter(), |x| {
+                                self.alert_rule.criterion.reserve = x;
+                            });
+                            let species = get_species(self.alert_rule.criterion.reserve);
+                            create_combo(ui, "Species", self.alert_rule.criterion.species, species.into_iter(), |x| {
+                                self.alert_rule.criterion.species = x;
+                            });
+                            ui.end_row();
+                            create_combo(ui, "Rating", self.alert_rule.criterion.rating, Rating::iter(), |x| {
+                                self.alert_rule.criterion.rating = x;
+                            });
+                            create_combo(ui, "Gender", self.alert_rule.criterion.gender, Gender::iter(), |x| {
+                                self.alert_rule.criterion.gender = x;
+                            });
+                            ui.end_row();
+                            let c = &mut self.alert_rule.criterion;
+                            bound_slider(ui, "Score", &mut c.score_comparison, &mut c.score, &mut c.score_upper, 0.0..=1100.0);
+                            bound_slider(ui, "Weight", &mut c.weight_comparison, &mut c.weight, &mut c.weight_upper, 0.0..=2000.0);
+                            ui.end_row();
+                        });
+                        ui.add_space(10.0);
+                        Grid::new("alert_actions")
+                        .num_columns(2)
+                        .spacing([10.0, 10.0])
+                        .show(ui, |ui| {
+                            ui.checkbox(&mut self.alert_actions.notify, "Notify");
+                            ui.add(TextEdit::singleline(&mut self.alert_actions.text).min_size([400.0, 20.0].into()))
+                                .on_hover_text("{species}, {reserve}, {rating}, {gender}, {fur}, {score} and {weight} are filled in from the trophy");
+                            ui.end_row();
+                            ui.checkbox(&mut self.alert_actions.sound, "Sound");
+                            ui.end_row();
+                            let mut webhook = self.alert_actions.webhook.is_some();
+                            if ui.checkbox(&mut webhook, "Webhook").changed() {
+                                self.alert_actions.webhook = if webhook { self.webhook_names.first().cloned().or(Some("".to_string())) } else { None };
+                            }
+                            if let Some(name) = self.alert_actions.webhook.clone() {
+                                ComboBox::from_id_source("alert_webhook")
+                                    .selected_text(if name.is_empty() { "add one to webhooks.toml".to_string() } else { name })
+                                    .show_ui(ui, |ui| {
+                                        for w in &self.webhook_names {
+                                            ui.selectable_value(&mut self.alert_actions.webhook, Some(w.clone()), w);
+                                        }
+                                    });
+                            }
+                            ui.end_row();
+                            ui.checkbox(&mut self.alert_actions.highlight, "Highlight in trophy table");
+                            ui.end_row();
+                        });
+                        ui.add_space(10.0);
+                        ui.horizontal(|ui| {
+                            ui.style_mut().visuals.widgets.hovered.weak_bg_fill = Color32::BROWN;
+                            if ui.button("Reset").clicked() {
+                                self.alert_rule = alerts::AlertRule::default();
+                                self.alert_actions = alerts::ActionChoices::from_actions(&self.alert_rule.actions);
+                                self.alert_furs.clear();
+                            }
+                            ui.add_space(10.0);
+                            ui.style_mut().visuals.widgets.hovered.weak_bg_fill = Color32::DARK_GREEN;
+                            if ui.button("Save Rule").clicked() {
+                                let mut rule = self.alert_rule.clone();
+                                rule.actions = self.alert_actions.actions();
+                                rule.criterion.furs = self.alert_furs.split(',').map(|f| f.trim().to_string()).filter(|f| !f.is_empty()).collect();
+                                let others: Vec<alerts::AlertRule> = self.alert_rules.iter().filter(|r| r.name != rule.name).cloned().collect();
+                                if let Some(problem) = rule.problem(&others) {
+                                    self.status_msg = format!("Unable to save rule: {}", problem);
+                                } else {
+                                    let mut rules = self.alert_rules.clone();
+                                    match rules.iter_mut().find(|r| r.name == rule.name) {
+                                        Some(existing) => *existing = rule,
+                                        None => rules.push(rule),
+                                    }
+                                    match self.alerts.save(rules) {
+                                        Ok(()) => {
+                                            self.alert_rules = self.alerts.rules();
+                                            self.alert_rule = alerts::AlertRule::default();
+                                            self.alert_actions = alerts::ActionChoices::from_actions(&self.alert_rule.actions);
+                                            self.alert_furs.clear();
+                                        }
+                                        Err(e) => self.status_msg = format!("Unable to save rule: {}", e),
+                                    }
+                                }
+                            }
+                        });
+                        ui.add_space(20.0);
+                        ui.separator();
+                    });
+                    ui.add_space(20.0);
+                    let mut rules = self.alert_rules.clone();
+                    let mut edit = None;
+                    Grid::new("alert_rules")
+                    .num_columns(5)
+                    .striped(true)
+                    .spacing([20.0, 10.0])
+                    .show(ui, |ui| {
+                        ui.strong("Name");
+                        ui.strong("When");
+                        ui.strong("Then");
+                        ui.strong("Enabled");
+                        ui.label("");
+                        ui.end_row();
+                        rules.retain_mut(|rule| {
+                            ui.label(&rule.name);
+                            ui.label(rule.describe());
+                            ui.label(rule.actions.iter().map(|a| a.to_string()).collect::<Vec<String>>().join(", "));
+                            ui.checkbox(&mut rule.enabled, "");
+                            let mut keep = true;
+                            ui.horizontal(|ui| {
+                                if ui.button("Edit").clicked() {
+                                    edit = Some(rule.clone());
+                                }
+                                ui.style_mut().visuals.widgets.hovered.weak_bg_fill = Color32::BROWN;
+                                keep = !ui.button("Delete").clicked();
+                            });
+                            ui.end_row();
+                            keep
+                        });
+                    });
+                    if let Some(rule) = edit {
+                        self.alert_actions = alerts::ActionChoices::from_actions(&rule.actions);
+                        self.alert_furs = rule.criterion.furs.join(", ");
+                        self.alert_rule = rule;
+                    }
+                    if rules != self.alert_rules {
+                        match self.alerts.save(rules) {
+                            Ok(()) => self.alert_rules = self.alerts.rules(),
+                            Err(e) => self.status_msg = format!("Unable to save rules: {}", e),
+                        }
+                    }
+                },
+                Sidebar::Data => {
                     ui.add_space(10.0);
-                    ui.label("If you have saved trophies from older versions of this tool, this will update the data to the latest version. Without this, you may not see all your data from previous versions.");
+                    ui.label(format!("Schema version {}. Migrations run automatically at startup after a backup of the database is taken.", self.schema_version));
                     ui.add_space(10.0);
-                    if ui.button("Upgrade Data").clicked() {
-                        data::upgrade_data();
-                        self.upgrade_msg = "Data has been upgraded. Restart the application to see the changes.".to_string();
+                    Grid::new("migrations")
+                        .striped(true)
+                        .num_columns(3)
+                        .spacing([40.0, 4.0])
+                        .show(ui, |ui| {
+                            ui.strong("Version");
+                            ui.strong("Migration");
+                            ui.strong("Applied");
+                            ui.end_row();
+                            for m in &self.migrations {
+                                ui.label(m.version.to_string());
+                                ui.label(&m.name);
+                                ui.label(&m.applied);
+                                ui.end_row();
+                            }
+                        });
+                    ui.add_space(20.0);
+                    ui.label("Trophies, grinds and challenges are stored in a database. You can export them as CSV files to use them in other tools.");
+                    ui.add_space(10.0);
+                    if ui.button("Export CSV").clicked() {
+                        self.data_msg = match data::export_csv(self.store.as_ref(), &self.data_dir) {
+                            Ok(export) => format!("Data has been exported to {}", export.display()),
+                            Err(e) => format!("Unable to export data: {}", e),
+                        };
                     }
                     ui.add_space(10.0);
-                    ui.strong(&self.upgrade_msg);
+                    ui.strong(&self.data_msg);
+                    ui.add_space(20.0);
+                    ui.heading("Data problems");
+                    ui.add_space(10.0);
+                    if self.problems.is_empty() {
+                        ui.label("All rows in your data files could be read.");
+                    } else {
+                        ui.label("These rows could not be read and are not shown anywhere else. Quarantine moves them into a separate file where they can be repaired by hand and imported again.");
+                        ui.add_space(10.0);
+                        let mut files: Vec<PathBuf> = self.problems.iter().map(|p| p.file.clone()).collect();
+                        files.dedup();
+                        let mut action = None;
+                        ScrollArea::vertical().show(ui, |ui| {
+                            for file in files {
+                                ui.horizontal(|ui| {
+                                    ui.strong(file.display().to_string());
+                                    if csv_store::is_quarantine(&file) {
+                                        if ui.button("Import Repaired").clicked() {
+                                            action = Some((file.clone(), true));
+                                        }
+                                    } else if ui.button("Quarantine").clicked() {
+                                        action = Some((file.clone(), false));
+                                    }
+                                });
+                                Grid::new(file.display().to_string())
+                                    .striped(true)
+                                    .num_columns(2)
+                                    .spacing([40.0, 4.0])
+                                    .show(ui, |ui| {
+                                        for p in self.problems.iter().filter(|p| p.file == file) {
+                                            ui.label(format!("Line {}", p.line));
+                                            ui.label(&p.error);
+                                            ui.end_row();
+                                        }
+                                    });
+                                ui.add_space(10.0);
+                            }
+                        });
+                        if let Some((file, repaired)) = action {
+                            let store = self.store.clone();
+                            self.data_msg = if repaired {
+                                match data::import_repaired(store.as_ref(), &file) {
+                                    Ok(count) => format!("Imported {} repaired rows from {}", count, file.display()),
+                                    Err(e) => format!("Unable to import repaired rows: {}", e),
+                                }
+                            } else {
+                                match data::quarantine(store.as_ref(), &file) {
+                                    Ok(quarantine) => format!("Rows have been moved to {} for repair", quarantine.display()),
+                                    Err(e) => format!("Unable to quarantine rows: {}", e),
+                                }
+                            };
+                            match store.read_problems() {
+                                Ok(problems) => self.problems = problems,
+                                Err(e) => self.data_msg = format!("Unable to read data problems: {}", e),
+                            }
+                            if let Ok(trophies) = store.read_trophies() {
+                                self.filtered_trophies = data::filter_data(&self.trophy_filter, trophies.clone());
+                                self.trophies = trophies;
+                            }
+                            if let Ok(grinds) = store.read_grinds() {
+                                self.grinds = grinds;
+                            }
+                            if let Ok(challenges) = data::get_challenges(store.as_ref()) {
+                                self.challenges = challenges;
+                            }
+                        }
+                    }
                 }
             }
         });
 
-        if let Ok(status) = self.status_rx.try_recv() {
-            self.status_msg = status;
-        } else {
-            ctx.request_repaint();
-        }
+        self.show_notifications(ctx);
+        ctx.request_repaint();
     }
 
     fn save(&mut self, storage: &mut dyn Storage) {
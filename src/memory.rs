@@ -0,0 +1,207 @@
+use std::collections::BTreeMap;
+use convert_case::{Case, Casing};
+#[cfg(windows)]
+use proc_mem::Process;
+
+pub trait MemorySource {
+    fn read_bytes(&self, address: usize, buf: &mut [u8]) -> bool;
+
+    // called by the monitor once per poll
+    fn end_frame(&self) {}
+}
+
+#[cfg(windows)]
+impl MemorySource for Process {
+    fn read_bytes(&self, address: usize, buf: &mut [u8]) -> bool {
+        Process::read_bytes(self, address, buf.as_mut_ptr(), buf.len())
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MemoryImage {
+    bytes: BTreeMap<usize, u8>,
+}
+impl MemoryImage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn regions(&self) -> Vec<(usize, Vec<u8>)> {
+        let mut regions: Vec<(usize, Vec<u8>)> = Vec::new();
+        for (address, byte) in self.bytes.iter() {
+            if let Some((start, bytes)) = regions.last_mut() {
+                if *start + bytes.len() == *address {
+                    bytes.push(*byte);
+                    continue;
+                }
+            }
+            regions.push((*address, vec![*byte]));
+        }
+        regions
+    }
+
+    pub fn write(&mut self, address: usize, bytes: &[u8]) {
+        for (i, b) in bytes.iter().enumerate() {
+            self.bytes.insert(address + i, *b);
+        }
+    }
+}
+#[cfg(test)]
+impl MemoryImage {
+    pub fn write_int(&mut self, address: usize, value: i32) {
+        self.write(address, &value.to_le_bytes());
+    }
+
+    pub fn write_float(&mut self, address: usize, value: f32) {
+        self.write(address, &value.to_le_bytes());
+    }
+
+    pub fn write_byte(&mut self, address: usize, value: u8) {
+        self.write(address, &[value]);
+    }
+
+    pub fn write_usize(&mut self, address: usize, value: usize) {
+        self.write(address, &value.to_le_bytes());
+    }
+
+    pub fn write_string(&mut self, address: usize, value: &str) {
+        self.write(address, value.as_bytes());
+        self.write_byte(address + value.len(), 0);
+    }
+}
+impl MemorySource for MemoryImage {
+    fn read_bytes(&self, address: usize, buf: &mut [u8]) -> bool {
+        for (i, b) in buf.iter_mut().enumerate() {
+            match self.bytes.get(&address.wrapping_add(i)) {
+                Some(x) => *b = *x,
+                None => return false,
+            }
+        }
+        true
+    }
+}
+
+pub struct Pointer {
+    pub base_address: usize,
+}
+impl Pointer {
+    pub fn new(base_address: usize) -> Self {
+        Self { base_address }
+    }
+    pub fn add(mut self, mem: &dyn MemorySource, offset: usize) -> Self {
+        self.base_address = read_usize(mem, self.base_address, offset);
+        self
+    }
+    pub fn address(self) -> usize {
+        self.base_address
+    }
+}
+
+fn read_mem<const N: usize>(mem: &dyn MemorySource, address: usize, offset: usize) -> Option<[u8; N]> {
+    let mut buf = [0u8; N];
+    if mem.read_bytes(address.wrapping_add(offset), &mut buf) {
+        Some(buf)
+    } else {
+        None
+    }
+}
+
+pub fn read_int(mem: &dyn MemorySource, address: usize, offset: usize) -> i32 {
+    if let Some(x) = read_mem(mem, address, offset) {
+        i32::from_le_bytes(x)
+    } else {
+        -1
+    }
+}
+
+pub fn read_float(mem: &dyn MemorySource, address: usize, offset: usize) -> f32 {
+    if let Some(x) = read_mem(mem, address, offset) {
+        f32::from_le_bytes(x)
+    } else {
+        f32::default()
+    }
+}
+
+pub fn read_byte(mem: &dyn MemorySource, address: usize, offset: usize) -> u8 {
+    if let Some(x) = read_mem::<1>(mem, address, offset) {
+        x[0]
+    } else {
+        u8::default()
+    }
+}
+
+pub fn read_usize(mem: &dyn MemorySource, address: usize, offset: usize) -> usize {
+    if let Some(x) = read_mem(mem, address, offset) {
+        usize::from_le_bytes(x)
+    } else {
+        usize::default()
+    }
+}
+
+pub fn read_string(mem: &dyn MemorySource, address: usize, offset: usize, format: bool) -> String {
+    let mut result = String::new();
+    let mut s_offset = 0x0;
+    loop {
+        let byte = read_byte(mem, address, offset + s_offset);
+        if byte == 0 {
+            break;
+        }
+        result.push(byte as char);
+        s_offset += 0x1;
+    }
+    if format {
+        result.replace("-", " ").to_case(Case::Title)
+    } else {
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_values() {
+        let mut image = MemoryImage::new();
+        image.write_int(0x100, 42);
+        image.write_float(0x104, 12.5);
+        image.write_byte(0x108, 7);
+        image.write_usize(0x110, 0xDEADBEEF);
+        assert_eq!(42, read_int(&image, 0x100, 0x0));
+        assert_eq!(12.5, read_float(&image, 0x100, 0x4));
+        assert_eq!(7, read_byte(&image, 0x108, 0x0));
+        assert_eq!(0xDEADBEEF, read_usize(&image, 0x100, 0x10));
+    }
+
+    #[test]
+    fn test_unmapped_reads() {
+        let image = MemoryImage::new();
+        assert_eq!(-1, read_int(&image, 0x100, 0x0));
+        assert_eq!(0.0, read_float(&image, 0x100, 0x0));
+        assert_eq!(0, read_usize(&image, 0x100, 0x0));
+        assert_eq!("", read_string(&image, 0x100, 0x0, false));
+    }
+
+    #[test]
+    fn test_read_string() {
+        let mut image = MemoryImage::new();
+        image.write_string(0x200, "red-deer");
+        assert_eq!("red-deer", read_string(&image, 0x200, 0x0, false));
+        assert_eq!("Red Deer", read_string(&image, 0x200, 0x0, true));
+    }
+
+    #[test]
+    fn test_pointer_chain() {
+        let mut image = MemoryImage::new();
+        image.write_usize(0x1000 + 0x20, 0x2000);
+        image.write_usize(0x2000 + 0x8, 0x3000);
+        let address = Pointer::new(0x1000).add(&image, 0x20).add(&image, 0x8).address();
+        assert_eq!(0x3000, address);
+        let broken = Pointer::new(0x1000).add(&image, 0x30).add(&image, 0x8).address();
+        assert_eq!(0, broken);
+    }
+}
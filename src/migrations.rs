@@ -0,0 +1,529 @@
+use std::collections::HashSet;
+use std::path::Path;
+use chrono::prelude::*;
+use csv::StringRecord;
+use rusqlite::{params, Connection, Transaction};
+use crate::models::*;
+use crate::store::*;
+
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    run: fn(&Transaction) -> DataResult<()>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppliedMigration {
+    pub version: u32,
+    pub name: String,
+    pub applied: String,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration { version: 1, name: "create tables", run: create_tables },
+    Migration { version: 2, name: "key trophies by identity", run: rekey_stored_trophies },
+    Migration { version: 3, name: "add data problems", run: create_problems },
+    Migration { version: 4, name: "add criterion comparisons", run: add_comparisons },
+    Migration { version: 5, name: "add challenge furs", run: add_challenge_furs },
+    Migration { version: 6, name: "add collection challenges", run: add_collections },
+    Migration { version: 7, name: "add challenge deadlines and history", run: add_deadlines },
+    Migration { version: 8, name: "add weapons and ammo", run: add_weapons },
+];
+
+pub fn schema_version(conn: &Connection) -> DataResult<u32> {
+    Ok(conn.query_row("PRAGMA user_version", [], |row| row.get(0))?)
+}
+
+// Runs every migration newer than the stored schema version, each in its own
+// transaction. When a backup path is given and the database already holds
+// tables, a copy is written there before anything changes.
+pub fn migrate(conn: &mut Connection, backup: Option<&Path>) -> DataResult<Vec<String>> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS migrations (
+            version INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            applied TEXT NOT NULL
+        );",
+    )?;
+    let version = schema_version(conn)?;
+    let pending: Vec<&Migration> = MIGRATIONS.iter().filter(|m| m.version > version).collect();
+    if pending.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut notes = Vec::new();
+    let tables: u32 = conn.query_row("SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name != 'migrations'", [], |row| row.get(0))?;
+    if let Some(backup) = backup.filter(|_| tables > 0) {
+        if backup.exists() {
+            std::fs::remove_file(backup)?;
+        }
+        conn.execute("VACUUM INTO ?1", [backup.to_string_lossy()])?;
+        notes.push(format!("Saved a backup of schema version {} to {}", version, backup.display()));
+    }
+    for migration in pending {
+        let tx = conn.transaction()?;
+        (migration.run)(&tx)?;
+        tx.execute(
+            "INSERT OR REPLACE INTO migrations (version, name, applied) VALUES (?1, ?2, ?3)",
+            params![migration.version, migration.name, Local::now().to_rfc3339()],
+        )?;
+        tx.pragma_update(None, "user_version", migration.version)?;
+        tx.commit()?;
+        notes.push(format!("Applied migration {}: {}", migration.version, migration.name));
+    }
+    Ok(notes)
+}
+
+pub fn migration_log(conn: &Connection) -> DataResult<Vec<AppliedMigration>> {
+    let mut stmt = conn.prepare("SELECT version, name, applied FROM migrations ORDER BY version")?;
+    let rows = stmt.query_map([], |row| {
+        Ok(AppliedMigration {
+            version: row.get(0)?,
+            name: row.get(1)?,
+            applied: row.get(2)?,
+        })
+    })?;
+    Ok(rows.collect::<Result<_, _>>()?)
+}
+
+fn create_tables(tx: &Transaction) -> DataResult<()> {
+    tx.execute_batch("
+        CREATE TABLE IF NOT EXISTS trophies (
+            id TEXT PRIMARY KEY,
+            species TEXT NOT NULL,
+            reserve TEXT NOT NULL,
+            rating TEXT NOT NULL,
+            date TEXT NOT NULL,
+            payload TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS trophies_species_reserve ON trophies (species, reserve);
+        CREATE INDEX IF NOT EXISTS trophies_date ON trophies (date);
+
+        CREATE TABLE IF NOT EXISTS grinds (
+            name TEXT PRIMARY KEY,
+            species TEXT NOT NULL,
+            reserve TEXT NOT NULL,
+            active INTEGER NOT NULL,
+            start TEXT NOT NULL,
+            kills INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS grinds_species_reserve ON grinds (species, reserve);
+
+        CREATE TABLE IF NOT EXISTS grind_kills (
+            grind TEXT NOT NULL REFERENCES grinds (name) ON DELETE CASCADE,
+            trophy_id TEXT NOT NULL,
+            date TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS grind_kills_grind ON grind_kills (grind);
+
+        CREATE TABLE IF NOT EXISTS challenges (
+            key TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            description TEXT NOT NULL,
+            start TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS challenge_criteria (
+            id INTEGER PRIMARY KEY,
+            challenge TEXT NOT NULL REFERENCES challenges (key) ON DELETE CASCADE,
+            species TEXT NOT NULL,
+            reserve TEXT NOT NULL,
+            kills_remaining INTEGER NOT NULL,
+            payload TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS challenge_criteria_challenge ON challenge_criteria (challenge);
+        CREATE INDEX IF NOT EXISTS challenge_criteria_species_reserve ON challenge_criteria (species, reserve);
+    ")?;
+    Ok(())
+}
+
+pub fn rekey(trophies: Vec<Trophy>) -> (Vec<Trophy>, Vec<Trophy>) {
+    let mut ids = HashSet::new();
+    let mut rekeyed = Vec::new();
+    let mut duplicates = Vec::new();
+    for mut t in trophies {
+        t.id = t.identity();
+        if ids.insert(t.id.clone()) {
+            rekeyed.push(t);
+        } else {
+            duplicates.push(t);
+        }
+    }
+    (rekeyed, duplicates)
+}
+
+fn rekey_stored_trophies(tx: &Transaction) -> DataResult<()> {
+    tx.execute_batch("
+        CREATE TABLE IF NOT EXISTS trophy_duplicates (
+            id TEXT NOT NULL,
+            payload TEXT NOT NULL
+        );
+    ")?;
+    let mut trophies = Vec::new();
+    {
+        let mut stmt = tx.prepare("SELECT payload FROM trophies ORDER BY rowid")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        for payload in rows {
+            trophies.push(serde_json::from_str::<Trophy>(&payload?)?);
+        }
+    }
+    let (trophies, duplicates) = rekey(trophies);
+    tx.execute("DELETE FROM trophies", [])?;
+    for t in trophies {
+        tx.execute(
+            "INSERT INTO trophies (id, species, reserve, rating, date, payload) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![t.id, format!("{:?}", t.species), format!("{:?}", t.reserve), format!("{:?}", t.rating), t.date, serde_json::to_string(&t)?],
+        )?;
+    }
+    for t in duplicates {
+        tx.execute("INSERT INTO trophy_duplicates (id, payload) VALUES (?1, ?2)", params![t.id, serde_json::to_string(&t)?])?;
+    }
+    Ok(())
+}
+
+fn create_problems(tx: &Transaction) -> DataResult<()> {
+    tx.execute_batch("
+        CREATE TABLE IF NOT EXISTS data_problems (
+            file TEXT NOT NULL,
+            line INTEGER NOT NULL,
+            error TEXT NOT NULL,
+            PRIMARY KEY (file, line)
+        );
+    ")?;
+    Ok(())
+}
+
+// Criteria saved before comparisons existed. The create form labelled
+// tracking as a maximum and the others as minimums, so that is what they keep.
+const LEGACY_COMPARISONS: &[(&str, Comparison)] = &[
+    ("shot_damage", Comparison::AtLeast),
+    ("shot_distance", Comparison::AtLeast),
+    ("tracking", Comparison::AtMost),
+    ("weight", Comparison::AtLeast),
+    ("score", Comparison::AtLeast),
+];
+
+// The challenge fields added since the first version, with the value a row
+// written before them gets.
+fn legacy_challenge_fields() -> Vec<(String, String)> {
+    let mut fields: Vec<(String, String)> = LEGACY_COMPARISONS
+        .iter()
+        .flat_map(|(field, comparison)| {
+            [(format!("{}_comparison", field), format!("{:?}", comparison)), (format!("{}_upper", field), "0".to_string())]
+        })
+        .collect();
+    fields.push(("fur".to_string(), String::new()));
+    fields.push(("collect".to_string(), format!("{:?}", Collect::Kills)));
+    fields.push(("end".to_string(), String::new()));
+    fields.push(("ammo".to_string(), format!("{:?}", Ammo::Unknown)));
+    fields
+}
+
+// The weapon fields trophies and grinds gained, stored before weapons were
+// read as Unknown.
+fn legacy_weapon_fields() -> Vec<(String, String)> {
+    vec![("weapon".to_string(), format!("{:?}", Weapon::Unknown)), ("ammo".to_string(), format!("{:?}", Ammo::Unknown))]
+}
+
+type Payload = serde_json::Map<String, serde_json::Value>;
+
+fn update_criteria(tx: &Transaction, update: impl Fn(&mut Payload) -> DataResult<()>) -> DataResult<()> {
+    update_payloads(tx, "challenge_criteria", update)
+}
+
+fn update_payloads(tx: &Transaction, table: &str, update: impl Fn(&mut Payload) -> DataResult<()>) -> DataResult<()> {
+    let mut rows = Vec::new();
+    {
+        let mut stmt = tx.prepare(&format!("SELECT rowid, payload FROM {}", table))?;
+        let query = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?;
+        for row in query {
+            rows.push(row?);
+        }
+    }
+    for (id, payload) in rows {
+        let mut criteria: serde_json::Value = serde_json::from_str(&payload)?;
+        if let Some(object) = criteria.as_object_mut() {
+            update(object)?;
+        }
+        tx.execute(&format!("UPDATE {} SET payload = ?1 WHERE rowid = ?2", table), params![serde_json::to_string(&criteria)?, id])?;
+    }
+    Ok(())
+}
+
+fn add_comparisons(tx: &Transaction) -> DataResult<()> {
+    update_criteria(tx, |object| {
+        for (field, comparison) in LEGACY_COMPARISONS {
+            object.entry(format!("{}_comparison", field)).or_insert(serde_json::to_value(comparison)?);
+            object.entry(format!("{}_upper", field)).or_insert(0.into());
+        }
+        Ok(())
+    })
+}
+
+fn add_challenge_furs(tx: &Transaction) -> DataResult<()> {
+    update_criteria(tx, |object| {
+        object.entry("fur").or_insert("".into());
+        Ok(())
+    })
+}
+
+fn add_collections(tx: &Transaction) -> DataResult<()> {
+    update_criteria(tx, |object| {
+        object.entry("collect").or_insert(serde_json::to_value(Collect::Kills)?);
+        Ok(())
+    })
+}
+
+fn add_deadlines(tx: &Transaction) -> DataResult<()> {
+    tx.execute_batch("
+        CREATE TABLE IF NOT EXISTS challenge_history (
+            name TEXT NOT NULL,
+            description TEXT NOT NULL,
+            start TEXT NOT NULL,
+            end TEXT NOT NULL,
+            finished TEXT NOT NULL,
+            status TEXT NOT NULL,
+            percent REAL NOT NULL,
+            PRIMARY KEY (name, start)
+        );
+    ")?;
+    update_criteria(tx, |object| {
+        object.entry("end").or_insert("".into());
+        Ok(())
+    })
+}
+
+fn add_weapons(tx: &Transaction) -> DataResult<()> {
+    tx.execute_batch("
+        ALTER TABLE grinds ADD COLUMN weapon TEXT NOT NULL DEFAULT 'Unknown';
+        ALTER TABLE grinds ADD COLUMN ammo TEXT NOT NULL DEFAULT 'Unknown';
+    ")?;
+    let add_fields = |object: &mut Payload| {
+        for (field, value) in legacy_weapon_fields() {
+            object.entry(field).or_insert(value.into());
+        }
+        Ok(())
+    };
+    update_payloads(tx, "trophies", add_fields)?;
+    update_payloads(tx, "trophy_duplicates", add_fields)?;
+    update_criteria(tx, |object| {
+        object.entry("ammo").or_insert(serde_json::to_value(Ammo::Unknown)?);
+        Ok(())
+    })
+}
+
+// The columns a csv from an older version lacks. Challenge, trophy and grind
+// files are told apart by a column only they have.
+fn missing_columns(headers: &StringRecord) -> Vec<(String, String)> {
+    let has = |column: &str| headers.iter().any(|h| h == column);
+    let fields = if has("kills_remaining") {
+        legacy_challenge_fields()
+    } else if has("session_score") || has("active") {
+        legacy_weapon_fields()
+    } else {
+        return Vec::new();
+    };
+    fields
+        .into_iter()
+        .filter(|(field, _)| !headers.iter().any(|h| h == field))
+        .collect()
+}
+
+pub fn upgrade_csv_headers(headers: &StringRecord) -> StringRecord {
+    let mut upgraded = headers.clone();
+    for (field, _) in missing_columns(headers) {
+        upgraded.push_field(&field);
+    }
+    upgraded
+}
+
+const BOOLEAN_COLUMNS: &[&str] = &["integrity", "mods", "public"];
+
+// Older versions wrote booleans in lower case. Only the boolean columns are
+// touched so names that happen to contain "true" are left alone. Takes the
+// headers as read from the file, before upgrade_csv_headers.
+pub fn upgrade_csv_record(headers: &StringRecord, record: &StringRecord) -> StringRecord {
+    let mut upgraded: StringRecord = headers
+        .iter()
+        .zip(record.iter())
+        .map(|(column, value)| match (BOOLEAN_COLUMNS.contains(&column), value) {
+            (true, "true") => "True",
+            (true, "false") => "False",
+            _ => value,
+        })
+        .collect();
+    for (_, value) in missing_columns(headers) {
+        upgraded.push_field(&value);
+    }
+    upgraded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::trophy;
+
+    fn insert_trophy(conn: &Connection, id: &str, t: &Trophy) {
+        conn.execute(
+            "INSERT INTO trophies (id, species, reserve, rating, date, payload) VALUES (?1, 'RedDeer', 'HirschfeldenHuntingReserve', 'Gold', ?2, ?3)",
+            params![id, t.date, serde_json::to_string(t).unwrap()],
+        ).unwrap();
+    }
+
+    #[test]
+    fn test_migrates_fresh_database() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let notes = migrate(&mut conn, None).unwrap();
+        assert_eq!(MIGRATIONS.len(), notes.len());
+        assert_eq!(MIGRATIONS.last().unwrap().version, schema_version(&conn).unwrap());
+        let log = migration_log(&conn).unwrap();
+        assert_eq!(vec![1, 2, 3, 4, 5, 6, 7, 8], log.iter().map(|m| m.version).collect::<Vec<u32>>());
+        assert_eq!("create tables", log[0].name);
+        assert!(migrate(&mut conn, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rekeys_with_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let backup = dir.path().join("lodge.db.v1");
+        let mut conn = Connection::open(dir.path().join("lodge.db")).unwrap();
+        conn.execute_batch("CREATE TABLE migrations (version INTEGER PRIMARY KEY, name TEXT NOT NULL, applied TEXT NOT NULL);").unwrap();
+        let tx = conn.transaction().unwrap();
+        create_tables(&tx).unwrap();
+        tx.pragma_update(None, "user_version", 1).unwrap();
+        tx.commit().unwrap();
+        let t = trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 200.5);
+        insert_trophy(&conn, "381", &t);
+        insert_trophy(&conn, "381.00003", &t);
+
+        let notes = migrate(&mut conn, Some(&backup)).unwrap();
+        assert_eq!(8, notes.len());
+        assert!(backup.exists());
+        let ids: Vec<String> = conn.prepare("SELECT id FROM trophies").unwrap()
+            .query_map([], |row| row.get(0)).unwrap().flatten().collect();
+        assert_eq!(vec![t.identity()], ids);
+        let duplicates: u32 = conn.query_row("SELECT count(*) FROM trophy_duplicates", [], |row| row.get(0)).unwrap();
+        assert_eq!(1, duplicates);
+
+        let old = Connection::open(&backup).unwrap();
+        assert_eq!(1, schema_version(&old).unwrap());
+    }
+
+    #[test]
+    fn test_migrates_trophies_without_weapons() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE migrations (version INTEGER PRIMARY KEY, name TEXT NOT NULL, applied TEXT NOT NULL);").unwrap();
+        let tx = conn.transaction().unwrap();
+        create_tables(&tx).unwrap();
+        tx.pragma_update(None, "user_version", 1).unwrap();
+        tx.commit().unwrap();
+        let t = trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 200.5);
+        let mut legacy = serde_json::to_value(&t).unwrap();
+        for (field, _) in legacy_weapon_fields() {
+            legacy.as_object_mut().unwrap().remove(&field);
+        }
+        conn.execute(
+            "INSERT INTO trophies (id, species, reserve, rating, date, payload) VALUES ('381', 'RedDeer', 'HirschfeldenHuntingReserve', 'Gold', ?1, ?2)",
+            params![t.date, legacy.to_string()],
+        ).unwrap();
+
+        assert_eq!(7, migrate(&mut conn, None).unwrap().len());
+        assert_eq!(8, schema_version(&conn).unwrap());
+        let payload: String = conn.query_row("SELECT payload FROM trophies", [], |row| row.get(0)).unwrap();
+        let stored: serde_json::Value = serde_json::from_str(&payload).unwrap();
+        assert_eq!("Unknown", stored["weapon"]);
+        assert_eq!("Unknown", stored["ammo"]);
+        assert_eq!(t, serde_json::from_value::<Trophy>(stored).unwrap());
+    }
+
+    #[test]
+    fn test_upgrade_csv_record() {
+        let headers = StringRecord::from(vec!["fur", "integrity", "mods", "grind"]);
+        let record = StringRecord::from(vec!["true-black", "true", "false", "true grit"]);
+        let upgraded = upgrade_csv_record(&headers, &record);
+        assert_eq!(StringRecord::from(vec!["true-black", "True", "False", "true grit"]), upgraded);
+    }
+
+    #[test]
+    fn test_upgrades_legacy_challenge_csv() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("deer.csv");
+        let mut legacy = serde_json::to_value(Challenge { name: "deer".to_string(), score: 200.0, tracking: 300, ..Challenge::default() }).unwrap();
+        for (field, _) in legacy_challenge_fields() {
+            legacy.as_object_mut().unwrap().remove(&field);
+        }
+        let object = legacy.as_object().unwrap();
+        let value = |v: &serde_json::Value| v.as_str().map(|s| s.to_string()).unwrap_or(v.to_string());
+        let header: Vec<&str> = object.keys().map(|k| k.as_str()).collect();
+        let row: Vec<String> = object.values().map(value).collect();
+        std::fs::write(&path, format!("{}\n{}\n", header.join(","), row.join(","))).unwrap();
+
+        let (criteria, skipped) = crate::csv_store::read_csv::<Challenge>(&path).unwrap();
+        assert!(skipped.is_empty());
+        assert_eq!(Comparison::AtLeast, criteria[0].score_comparison);
+        assert_eq!(Comparison::AtMost, criteria[0].tracking_comparison);
+        assert_eq!(Challenge { name: "deer".to_string(), score: 200.0, tracking: 300, ..Challenge::default() }, criteria[0]);
+        assert_eq!(headers_of(&["species", "tracking"]), upgrade_csv_headers(&headers_of(&["species", "tracking"])));
+    }
+
+    #[test]
+    fn test_upgrades_legacy_trophy_csv() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trophies.csv");
+        let t = trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 200.5);
+        let mut writer = csv::Writer::from_writer(vec![]);
+        writer.serialize(&t).unwrap();
+        let written = writer.into_inner().unwrap();
+        let mut reader = csv::Reader::from_reader(written.as_slice());
+        let headers = reader.headers().unwrap().clone();
+        let record = reader.records().next().unwrap().unwrap();
+        let keep: Vec<usize> = (0..headers.len()).filter(|&i| !["weapon", "ammo"].contains(&&headers[i])).collect();
+        let legacy = |r: &StringRecord| keep.iter().map(|&i| r[i].to_string()).collect::<Vec<String>>().join(",");
+        std::fs::write(&path, format!("{}\n{}\n", legacy(&headers), legacy(&record))).unwrap();
+
+        let (trophies, skipped) = crate::csv_store::read_csv::<Trophy>(&path).unwrap();
+        assert!(skipped.is_empty());
+        assert_eq!(vec![t], trophies);
+    }
+
+    fn headers_of(columns: &[&str]) -> StringRecord {
+        StringRecord::from(columns.to_vec())
+    }
+
+    #[test]
+    fn test_upgrades_stored_criteria() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE migrations (version INTEGER PRIMARY KEY, name TEXT NOT NULL, applied TEXT NOT NULL);").unwrap();
+        let tx = conn.transaction().unwrap();
+        create_tables(&tx).unwrap();
+        rekey_stored_trophies(&tx).unwrap();
+        tx.pragma_update(None, "user_version", 3).unwrap();
+        tx.commit().unwrap();
+        let mut legacy = serde_json::to_value(Challenge { name: "deer".to_string(), weight: 150.0, ..Challenge::default() }).unwrap();
+        for (field, _) in legacy_challenge_fields() {
+            legacy.as_object_mut().unwrap().remove(&field);
+        }
+        conn.execute("INSERT INTO challenges (key, name, description, start) VALUES ('deer', 'deer', '', '')", []).unwrap();
+        conn.execute(
+            "INSERT INTO challenge_criteria (challenge, species, reserve, kills_remaining, payload) VALUES ('deer', 'Unknown', 'Unknown', 1, ?1)",
+            [legacy.to_string()],
+        ).unwrap();
+
+        migrate(&mut conn, None).unwrap();
+        let payload: String = conn.query_row("SELECT payload FROM challenge_criteria", [], |row| row.get(0)).unwrap();
+        let criteria: Challenge = serde_json::from_str(&payload).unwrap();
+        assert_eq!(Challenge { name: "deer".to_string(), weight: 150.0, ..Challenge::default() }, criteria);
+    }
+
+    #[test]
+    fn test_rekey() {
+        let mut a = trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 200.5);
+        a.id = "410.5".to_string();
+        let b = trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 150.0);
+        let (trophies, duplicates) = rekey(vec![a.clone(), b, a]);
+        assert_eq!(2, trophies.len());
+        assert_eq!(1, duplicates.len());
+        assert_eq!(trophies[0].identity(), trophies[0].id);
+        assert_eq!(trophies[0].id, duplicates[0].id);
+    }
+}
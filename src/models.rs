@@ -1,3 +1,4 @@
+use strum::IntoEnumIterator;
 use strum_macros::{EnumIter, VariantArray, EnumString};
 use std::fmt;
 use std::cmp::{Ord, Ordering};
@@ -6,6 +7,7 @@ use serde::{Serialize, Deserialize};
 use std::collections::HashMap;
 use egui::{ImageSource, include_image};
 use chrono::prelude::*;
+use sha2::{Digest, Sha256};
 
 fn fmt_model(value: &impl fmt::Debug) -> String {
     format!("{:?}", value).to_case(Case::Title)
@@ -115,15 +117,11 @@ impl fmt::Display for Species {
 }
 impl Species {
     pub fn is_real(&self) -> bool {
-        match self {
-            Species::All => false,
-            Species::Unknown => false,
-            _ => true,
-        }
+        !matches!(self, Species::All | Species::Unknown)
     }
 }
 
-pub fn species_image(species: &Species) -> ImageSource {
+pub fn species_image(species: &Species) -> ImageSource<'_> {
     match species {
         Species::AmericanAlligator => include_image!("../static/species/Alligator_frame.png"),
         Species::AntelopeJackrabbit => include_image!("../static/species/Antilopejackrabbit_frame.png"),
@@ -212,6 +210,8 @@ pub fn species_image(species: &Species) -> ImageSource {
     }
 }
 
+// variant names are the game's reserve names
+#[allow(clippy::enum_variant_names)]
 #[derive(PartialEq, Debug, Clone, Copy, EnumIter, VariantArray, EnumString, Serialize, Deserialize, Hash, Eq)]
 #[strum(serialize_all = "title_case")]
 pub enum Reserve {
@@ -240,7 +240,7 @@ impl fmt::Display for Reserve {
     }
 }
 
-pub fn reserve_image(reserve: &Reserve) -> ImageSource {
+pub fn reserve_image(reserve: &Reserve) -> ImageSource<'_> {
     match reserve {
         Reserve::HirschfeldenHuntingReserve => include_image!("../static/reserves/hirschfelden_hunting_reserve.png"),
         Reserve::LaytonLakeDistrict => include_image!("../static/reserves/layton_lake_district.png"),
@@ -439,7 +439,7 @@ pub fn reserve_species() -> HashMap<Reserve, Vec<Species>> {
     map
 }
 
-#[derive(PartialOrd, PartialEq, Eq, Debug, Clone, Copy, EnumIter, VariantArray, EnumString, Deserialize, Serialize)]
+#[derive(PartialEq, Eq, Debug, Clone, Copy, EnumIter, VariantArray, EnumString, Deserialize, Serialize)]
 #[strum(serialize_all = "title_case")]
 pub enum Rating {
     All,
@@ -476,15 +476,167 @@ impl Ord for Rating {
         x.cmp(y)
     }
 }
+impl PartialOrd for Rating {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
 
-#[derive(PartialEq, Debug, Clone, Copy, EnumIter, VariantArray, EnumString, Serialize, Deserialize)]
+// Names are compared on their letters and digits only, so the game's
+// "ranger-243" and the catalog's "Ranger .243" are the same weapon.
+fn name_key(name: &str) -> String {
+    name.chars().filter(|c| c.is_ascii_alphanumeric()).collect::<String>().to_lowercase()
+}
+
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, Default, EnumIter, VariantArray, EnumString, Serialize, Deserialize)]
 pub enum Weapon {
+    All,
+    #[default]
     Unknown,
+    Hudzik22,
+    Ranger243,
+    Whitlock3030,
+    Solokhin,
+    Rangemaster338,
+    Mulholland470,
+    Kullman12G,
+    Panther44,
+    Bearclaw,
+    Crosspoint,
 }
 impl fmt::Display for Weapon {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let model =  fmt_model(&self);
-        write!(f, "{}", model)
+        write!(f, "{}", self.name())
+    }
+}
+impl Weapon {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Weapon::All => "All",
+            Weapon::Unknown => "Unknown",
+            Weapon::Hudzik22 => "Hudzik .22 LR",
+            Weapon::Ranger243 => "Ranger .243",
+            Weapon::Whitlock3030 => "Whitlock Model 86 .30-30",
+            Weapon::Solokhin => "Solokhin MN1890",
+            Weapon::Rangemaster338 => "Rangemaster .338",
+            Weapon::Mulholland470 => "Mulholland .470",
+            Weapon::Kullman12G => "Kullman 12G",
+            Weapon::Panther44 => "Panther .44 Magnum",
+            Weapon::Bearclaw => "Bearclaw Lite CB-60",
+            Weapon::Crosspoint => "Crosspoint CB-165",
+        }
+    }
+
+    // The weapon the game names, Unknown when it is not in the catalog.
+    pub fn from_name(name: &str) -> Weapon {
+        let key = name_key(name);
+        Weapon::iter()
+            .filter(|w| w.is_real())
+            .find(|w| name_key(w.name()) == key || name_key(&format!("{:?}", w)) == key)
+            .unwrap_or(Weapon::Unknown)
+    }
+
+    pub fn is_real(&self) -> bool {
+        !matches!(self, Weapon::All | Weapon::Unknown)
+    }
+
+    // The ammo the weapon fires, empty for All and Unknown.
+    pub fn ammo(&self) -> Vec<Ammo> {
+        match self {
+            Weapon::Hudzik22 => vec![Ammo::LongRifle22],
+            Weapon::Ranger243 => vec![Ammo::SoftPoint243, Ammo::PolymerTip243],
+            Weapon::Whitlock3030 => vec![Ammo::SoftPoint3030],
+            Weapon::Solokhin => vec![Ammo::SoftPoint762],
+            Weapon::Rangemaster338 => vec![Ammo::SoftPoint338, Ammo::PolymerTip338],
+            Weapon::Mulholland470 => vec![Ammo::SoftPoint470, Ammo::Solid470],
+            Weapon::Kullman12G => vec![Ammo::Birdshot12, Ammo::Buckshot12, Ammo::Slug12],
+            Weapon::Panther44 => vec![Ammo::SoftPoint44],
+            Weapon::Bearclaw => vec![Ammo::SmallGameArrow, Ammo::BroadheadArrow],
+            Weapon::Crosspoint => vec![Ammo::BroadheadBolt],
+            Weapon::All | Weapon::Unknown => Vec::new(),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, Default, EnumIter, VariantArray, EnumString, Serialize, Deserialize)]
+pub enum Ammo {
+    All,
+    #[default]
+    Unknown,
+    LongRifle22,
+    SoftPoint243,
+    PolymerTip243,
+    SoftPoint3030,
+    SoftPoint762,
+    SoftPoint338,
+    PolymerTip338,
+    SoftPoint470,
+    Solid470,
+    Birdshot12,
+    Buckshot12,
+    Slug12,
+    SoftPoint44,
+    SmallGameArrow,
+    BroadheadArrow,
+    BroadheadBolt,
+}
+impl fmt::Display for Ammo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+impl Ammo {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Ammo::All => "All",
+            Ammo::Unknown => "Unknown",
+            Ammo::LongRifle22 => ".22 Long Rifle Hollow Point",
+            Ammo::SoftPoint243 => ".243 Soft Point",
+            Ammo::PolymerTip243 => ".243 Polymer Tip",
+            Ammo::SoftPoint3030 => ".30-30 Soft Point",
+            Ammo::SoftPoint762 => "7.62x54R Soft Point",
+            Ammo::SoftPoint338 => ".338 Soft Point",
+            Ammo::PolymerTip338 => ".338 Polymer Tip",
+            Ammo::SoftPoint470 => ".470 Soft Point",
+            Ammo::Solid470 => ".470 Solid",
+            Ammo::Birdshot12 => "12 GA Birdshot",
+            Ammo::Buckshot12 => "12 GA Buckshot",
+            Ammo::Slug12 => "12 GA Slug",
+            Ammo::SoftPoint44 => ".44 Magnum Soft Point",
+            Ammo::SmallGameArrow => "Small Game Arrow",
+            Ammo::BroadheadArrow => "Broadhead Arrow",
+            Ammo::BroadheadBolt => "Broadhead Bolt",
+        }
+    }
+
+    // The ammo the game names, Unknown when it is not in the catalog.
+    pub fn from_name(name: &str) -> Ammo {
+        let key = name_key(name);
+        Ammo::iter()
+            .filter(|a| a.is_real())
+            .find(|a| name_key(a.name()) == key || name_key(&format!("{:?}", a)) == key)
+            .unwrap_or(Ammo::Unknown)
+    }
+
+    pub fn is_real(&self) -> bool {
+        !matches!(self, Ammo::All | Ammo::Unknown)
+    }
+
+    // The game's ammo class, 1 for the smallest game up to 9 for the largest.
+    // 0 for All and Unknown.
+    pub fn class(&self) -> u8 {
+        match self {
+            Ammo::LongRifle22 | Ammo::Birdshot12 | Ammo::SmallGameArrow => 1,
+            Ammo::Buckshot12 => 2,
+            Ammo::SoftPoint243 | Ammo::PolymerTip243 => 3,
+            Ammo::SoftPoint3030 | Ammo::Slug12 | Ammo::SoftPoint44 | Ammo::BroadheadArrow => 4,
+            Ammo::BroadheadBolt => 5,
+            Ammo::SoftPoint762 => 6,
+            Ammo::SoftPoint338 | Ammo::PolymerTip338 => 7,
+            Ammo::SoftPoint470 => 8,
+            Ammo::Solid470 => 9,
+            Ammo::All | Ammo::Unknown => 0,
+        }
     }
 }
 
@@ -537,18 +689,91 @@ impl std::convert::From<bool> for Boolean {
         }
     }
 }
-impl std::convert::Into<bool> for Boolean {
-    fn into(self) -> bool {
-        match self {
+impl std::convert::From<Boolean> for bool {
+    fn from(val: Boolean) -> Self {
+        match val {
             Boolean::True => true,
             Boolean::False => false,
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+// How a numeric challenge criterion is compared with a kill. Between takes the
+// criterion value as the lower bound and its upper field as the upper bound.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, EnumIter, EnumString, PartialEq)]
+pub enum Comparison {
+    AtLeast,
+    AtMost,
+    Between,
+}
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Comparison::AtLeast => write!(f, ">="),
+            Comparison::AtMost => write!(f, "<="),
+            Comparison::Between => write!(f, "between"),
+        }
+    }
+}
+impl Comparison {
+    // A zero value leaves the criterion unset, unless Between has an upper bound.
+    pub fn is_set(&self, value: f32, upper: f32) -> bool {
+        value > 0.0 || (*self == Comparison::Between && upper > 0.0)
+    }
+
+    pub fn check(&self, value: f32, upper: f32, actual: f32) -> Option<bool> {
+        match self {
+            _ if !self.is_set(value, upper) => None,
+            Comparison::AtLeast => Some(actual >= value),
+            Comparison::AtMost => Some(actual <= value),
+            Comparison::Between => Some(value <= actual && actual <= upper),
+        }
+    }
+
+    pub fn describe(&self, value: f32, upper: f32) -> String {
+        match self {
+            Comparison::Between => format!("between {:.0} and {:.0}", value, upper),
+            c => format!("{} {:.0}", c, value),
+        }
+    }
+}
+
+// What a challenge counts. Kills is the usual count of matching kills, the
+// others count the distinct values of that field among the matching kills.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, EnumIter, EnumString, PartialEq)]
+pub enum Collect {
+    Kills,
+    Species,
+    Reserve,
+    Fur,
+    Rating,
+    Gender,
+}
+impl fmt::Display for Collect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let model =  fmt_model(&self);
+        write!(f, "{}", model)
+    }
+}
+impl Collect {
+    // The value a kill adds to the collection, None for unreadable ones.
+    pub fn value(&self, trophy: &Trophy) -> Option<String> {
+        let value = match self {
+            Collect::Kills => return None,
+            Collect::Species if trophy.species.is_real() => trophy.species.to_string(),
+            Collect::Reserve if !matches!(trophy.reserve, Reserve::All | Reserve::Unknown) => trophy.reserve.to_string(),
+            Collect::Fur if !trophy.fur.is_empty() && trophy.fur != "Unknown" => trophy.fur.to_case(Case::Title),
+            Collect::Rating if !matches!(trophy.rating, Rating::All | Rating::Unknown) => trophy.rating.to_string(),
+            Collect::Gender if !matches!(trophy.gender, Gender::All | Gender::Unknown) => trophy.gender.to_string(),
+            _ => return None,
+        };
+        Some(value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Trophy {
-    pub id: f32,
+    pub id: String,
     pub species: Species,
     pub reserve: Reserve,
     pub rating: Rating,
@@ -565,6 +790,12 @@ pub struct Trophy {
     pub weapon_score: f32,
     pub shot_distance: f32,
     pub shot_damage: f32,
+    // defaulted so payloads stored before weapons were recorded still parse
+    // in the migrations that run ahead of add_weapons
+    #[serde(default)]
+    pub weapon: Weapon,
+    #[serde(default)]
+    pub ammo: Ammo,
     pub mods: Boolean,
     pub grind: Option<String>,
 }
@@ -572,6 +803,22 @@ impl Trophy {
     pub fn valid(&self) -> bool {
         self.species != Species::Unknown && self.reserve != Reserve::Unknown && self.weight != 0.0
     }
+
+    pub fn identity(&self) -> String {
+        let key = format!(
+            "{:?}|{:?}|{:?}|{:08x}|{:08x}|{}|{:?}|{}",
+            self.species,
+            self.reserve,
+            self.rating,
+            self.score.to_bits(),
+            self.weight.to_bits(),
+            self.fur,
+            self.gender,
+            self.session_score,
+        );
+        let hash = Sha256::digest(key.as_bytes());
+        hash[..8].iter().map(|b| format!("{:02x}", b)).collect()
+    }
 }
 
 pub struct TrophyFilter {
@@ -579,6 +826,8 @@ pub struct TrophyFilter {
     pub reserve: Reserve,
     pub rating: Rating,
     pub gender: Gender,
+    pub weapon: Weapon,
+    pub ammo: Ammo,
     pub grind: String,
     pub sort_by: SortBy,
 }
@@ -589,13 +838,15 @@ impl Default for TrophyFilter {
             reserve: Reserve::All,
             rating: Rating::All,
             gender: Gender::All,
+            weapon: Weapon::All,
+            ammo: Ammo::All,
             grind: "".to_string(),
             sort_by: SortBy::Date,
         }
     }
 }
 
-#[derive(PartialOrd, PartialEq, Eq, VariantArray, Debug, EnumIter, EnumString)]
+#[derive(PartialEq, Eq, VariantArray, Debug, EnumIter, EnumString)]
 #[strum(serialize_all = "title_case")]
 pub enum TrophyCols {
     Species,
@@ -614,6 +865,8 @@ pub enum TrophyCols {
     WeaponScore,
     ShotDistance,
     ShotDamage,
+    Weapon,
+    Ammo,
     Mods,
     Grind,
 }
@@ -641,8 +894,10 @@ fn trophy_col_order(col: &TrophyCols) -> i32 {
         TrophyCols::WeaponScore => 13,
         TrophyCols::ShotDistance => 14,
         TrophyCols::ShotDamage => 15,
-        TrophyCols::Mods => 16,
-        TrophyCols::Grind => 17,
+        TrophyCols::Weapon => 16,
+        TrophyCols::Ammo => 17,
+        TrophyCols::Mods => 18,
+        TrophyCols::Grind => 19,
     }
 }
 impl Ord for TrophyCols {
@@ -652,12 +907,19 @@ impl Ord for TrophyCols {
         x.cmp(&y)
     }
 }
+impl PartialOrd for TrophyCols {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Grind {
     pub name: String,
     pub species: Species,
     pub reserve: Reserve,
+    pub weapon: Weapon,
+    pub ammo: Ammo,
     pub active: bool,
     pub start: String,
     pub kills: i64,
@@ -669,6 +931,8 @@ impl Default for Grind {
             name: rand::random::<u32>().to_string(),
             species: Species::RedDeer,
             reserve: Reserve::HirschfeldenHuntingReserve,
+            weapon: Weapon::Unknown,
+            ammo: Ammo::Unknown,
             active: false,
             start: Local::now().to_rfc3339(),
             kills: 69,
@@ -686,18 +950,36 @@ impl Grind {
         false
     }
 
-    pub fn valid(&self, grinds: &Vec<Grind>) -> bool {
-        self.name != "" && 
-        self.species != Species::All && 
-        self.reserve != Reserve::All && 
-        self.species != Species::Unknown && 
-        self.reserve != Reserve::Unknown &&
-        !Grind::grind_exists(self.name.to_string(), grinds)
+    pub fn problem(&self, grinds: &Vec<Grind>) -> Option<String> {
+        if self.name.is_empty() {
+            Some("a grind needs a name".to_string())
+        } else if self.species == Species::All || self.species == Species::Unknown {
+            Some("a grind needs a species".to_string())
+        } else if self.reserve == Reserve::All || self.reserve == Reserve::Unknown {
+            Some("a grind needs a reserve".to_string())
+        } else if Grind::grind_exists(self.name.to_string(), grinds) {
+            Some(format!("a grind named {} already exists", self.name))
+        } else {
+            weapon_problem(self.weapon, self.ammo)
+        }
+    }
+
+    // A weapon or ammo of Unknown or All counts kills made with anything.
+    pub fn for_trophy(&self, trophy: &Trophy) -> bool {
+        self.active
+            && self.species == trophy.species
+            && self.reserve == trophy.reserve
+            && (!self.weapon.is_real() || self.weapon == trophy.weapon)
+            && (!self.ammo.is_real() || self.ammo == trophy.ammo)
     }
 }
 
-pub struct GrindKill {
-    pub name: String,
+pub fn weapon_problem(weapon: Weapon, ammo: Ammo) -> Option<String> {
+    if weapon.is_real() && ammo.is_real() && !weapon.ammo().contains(&ammo) {
+        Some(format!("{} cannot be fired from the {}", ammo, weapon))
+    } else {
+        None
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
@@ -720,6 +1002,20 @@ pub struct Challenge {
     pub total_shots: u32,
     pub weight: f32,
     pub score: f32,
+    pub shot_damage_comparison: Comparison,
+    pub shot_damage_upper: u32,
+    pub shot_distance_comparison: Comparison,
+    pub shot_distance_upper: u32,
+    pub tracking_comparison: Comparison,
+    pub tracking_upper: u32,
+    pub weight_comparison: Comparison,
+    pub weight_upper: f32,
+    pub score_comparison: Comparison,
+    pub score_upper: f32,
+    pub fur: String,
+    pub collect: Collect,
+    pub end: String,
+    pub ammo: Ammo,
 }
 impl Default for Challenge {
     fn default() -> Self {
@@ -742,12 +1038,26 @@ impl Default for Challenge {
             total_shots: 0,
             weight: 0.0,
             score: 0.0,
+            shot_damage_comparison: Comparison::AtLeast,
+            shot_damage_upper: 0,
+            shot_distance_comparison: Comparison::AtLeast,
+            shot_distance_upper: 0,
+            tracking_comparison: Comparison::AtMost,
+            tracking_upper: 0,
+            weight_comparison: Comparison::AtLeast,
+            weight_upper: 0.0,
+            score_comparison: Comparison::AtLeast,
+            score_upper: 0.0,
+            fur: "".to_string(),
+            collect: Collect::Kills,
+            end: "".to_string(),
+            ammo: Ammo::Unknown,
         }
     }
 }
 impl Challenge {
     pub fn valid(&self) -> bool {
-        self.name != "".to_string() && self.kills > 0
+        !self.name.is_empty() && self.kills > 0
     }
 
     pub fn for_trophy(&self, trophy: &Trophy) -> bool {
@@ -764,31 +1074,39 @@ impl Challenge {
         if self.gender != Gender::Unknown {
             matches.push(self.gender == trophy.gender);
         }
-        if self.shot_damage > 0 {
-            matches.push(self.shot_damage >= trophy.shot_damage as u32);
+        matches.extend(self.shot_damage_comparison.check(self.shot_damage as f32, self.shot_damage_upper as f32, trophy.shot_damage));
+        matches.extend(self.shot_distance_comparison.check(self.shot_distance as f32, self.shot_distance_upper as f32, trophy.shot_distance));
+        matches.extend(self.tracking_comparison.check(self.tracking as f32, self.tracking_upper as f32, trophy.tracking));
+        matches.extend(self.score_comparison.check(self.score, self.score_upper, trophy.score));
+        matches.extend(self.weight_comparison.check(self.weight, self.weight_upper, trophy.weight));
+        if !self.fur.is_empty() {
+            matches.push(self.fur.eq_ignore_ascii_case(&trophy.fur));
         }
-        if self.shot_distance > 0 {
-            matches.push(self.shot_distance >= trophy.shot_distance as u32);
+        if self.weapon.is_real() {
+            matches.push(self.weapon == trophy.weapon);
         }
-        if self.tracking > 0 {
-            matches.push(self.tracking <= trophy.tracking as u32);
-        }
-        if self.score > 0.0 {
-            matches.push(self.score >= trophy.score);
-        }
-        if self.weight > 0.0 {
-            matches.push(self.weight >= trophy.weight);
+        if self.ammo.is_real() {
+            matches.push(self.ammo == trophy.ammo);
         }
         if self.mods == Boolean::False {
             matches.push(trophy.mods == Boolean::False);
         }
+        matches.push(self.open_at(&trophy.date));
 
         matches.iter().all(|&x| x)
     }
+
+    // Whether a kill made at date still counts, false once the end has passed.
+    pub fn open_at(&self, date: &str) -> bool {
+        match (DateTime::parse_from_rfc3339(&self.end), DateTime::parse_from_rfc3339(date)) {
+            (Ok(end), Ok(date)) => date <= end,
+            _ => true,
+        }
+    }
 }
 impl PartialOrd for Challenge {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.reserve.to_string().cmp(&other.reserve.to_string()))
+        Some(self.cmp(other))
     }
 }
 impl Ord for Challenge {
@@ -798,13 +1116,38 @@ impl Ord for Challenge {
 }
 impl Eq for Challenge {}
 
-#[derive(Debug, Clone, PartialEq)]
+// How many of the rows asking for a fur have been collected.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FurProgress {
+    pub fur: String,
+    pub collected: u32,
+    pub total: u32,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, EnumIter, EnumString, PartialEq)]
+pub enum ChallengeStatus {
+    Active,
+    Completed,
+    Expired,
+}
+impl fmt::Display for ChallengeStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let model =  fmt_model(&self);
+        write!(f, "{}", model)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct ChallengeSummary {
     pub name: String,
     pub description: String,
     pub start: String,
+    pub end: String,
+    pub status: ChallengeStatus,
     pub percent: f32,
     pub is_deleted: bool,
+    pub furs: Vec<FurProgress>,
+    pub collected: Vec<String>,
 }
 impl Default for ChallengeSummary {
     fn default() -> Self {
@@ -812,12 +1155,24 @@ impl Default for ChallengeSummary {
             name: "".to_string(),
             description: "".to_string(),
             start: "".to_string(),
+            end: "".to_string(),
+            status: ChallengeStatus::Active,
             percent: 0.0,
             is_deleted: false,
+            furs: Vec::new(),
+            collected: Vec::new(),
         }
     }
 }
 
-pub struct ChallengeKill {
+// A challenge run that has been completed or has expired.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChallengeRun {
     pub name: String,
-}
\ No newline at end of file
+    pub description: String,
+    pub start: String,
+    pub end: String,
+    pub finished: String,
+    pub status: ChallengeStatus,
+    pub percent: f32,
+}
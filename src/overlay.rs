@@ -0,0 +1,114 @@
+use crate::models::*;
+use crate::store::*;
+use egui::ImageSource;
+use serde::Serialize;
+use strum::IntoEnumIterator;
+
+pub const PAGE: &str = include_str!("../static/overlay.html");
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct OverlayTrophy {
+    pub species: String,
+    pub reserve: String,
+    pub rating: String,
+    pub score: f32,
+    pub weight: f32,
+    pub fur: String,
+    pub shot_distance: f32,
+    pub date: String,
+    pub image: String,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct OverlayGrind {
+    pub name: String,
+    pub kills: i64,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct OverlayState {
+    pub trophy: Option<OverlayTrophy>,
+    pub grind: Option<OverlayGrind>,
+}
+
+pub fn image_path(species: Species) -> String {
+    format!("/overlay/species/{:?}.png", species)
+}
+
+// The png behind species_image, looked up by the name used in image_path.
+pub fn species_png(name: &str) -> Option<Vec<u8>> {
+    let species = Species::iter().find(|s| format!("{:?}", s) == name)?;
+    match species_image(&species) {
+        ImageSource::Bytes { bytes, .. } => Some(bytes.to_vec()),
+        _ => None,
+    }
+}
+
+// The last stored trophy, and the grind it counted towards. Without one the
+// most recently started active grind is shown.
+pub fn state(store: &dyn TrophyStore) -> DataResult<OverlayState> {
+    let trophy = store.read_trophies()?.into_iter().max_by(|a, b| a.date.cmp(&b.date));
+    let mut grinds = store.read_grinds()?;
+    grinds.sort_by(|a, b| b.start.cmp(&a.start));
+    let counted: Vec<String> = trophy
+        .as_ref()
+        .and_then(|t| t.grind.clone())
+        .map(|g| g.split('/').map(|s| s.to_string()).collect())
+        .unwrap_or_default();
+    let grind = grinds
+        .iter()
+        .find(|g| counted.contains(&g.name))
+        .or_else(|| grinds.iter().find(|g| g.active))
+        .map(|g| OverlayGrind { name: g.name.clone(), kills: g.kills });
+    Ok(OverlayState {
+        trophy: trophy.map(|t| OverlayTrophy {
+            species: t.species.to_string(),
+            reserve: t.reserve.to_string(),
+            rating: t.rating.to_string(),
+            score: t.score,
+            weight: t.weight,
+            fur: t.fur.clone(),
+            shot_distance: t.shot_distance,
+            date: t.date.clone(),
+            image: image_path(t.species),
+        }),
+        grind,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data;
+    use crate::sqlite_store::SqliteStore;
+    use crate::store::tests::trophy;
+
+    #[test]
+    fn test_state() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        assert_eq!(OverlayState { trophy: None, grind: None }, state(&store).unwrap());
+
+        data::add_grind(&store, "deer", Species::RedDeer, Reserve::HirschfeldenHuntingReserve, Weapon::Unknown, Ammo::Unknown).unwrap();
+        data::add_grind(&store, "roe", Species::RoeDeer, Reserve::HirschfeldenHuntingReserve, Weapon::Unknown, Ammo::Unknown).unwrap();
+        assert_eq!("roe", state(&store).unwrap().grind.unwrap().name);
+
+        let mut t = trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 200.0);
+        t.grind = Some("deer".to_string());
+        t.date = "2024-02-01T00:00:00+00:00".to_string();
+        store.add_trophy(&trophy(Species::RoeDeer, Reserve::HirschfeldenHuntingReserve, 20.0)).unwrap();
+        store.add_trophy(&t).unwrap();
+        store.add_grind_kill("deer", &t).unwrap();
+
+        let state = state(&store).unwrap();
+        let shown = state.trophy.unwrap();
+        assert_eq!("Red Deer", shown.species);
+        assert_eq!("/overlay/species/RedDeer.png", shown.image);
+        assert_eq!(Some(OverlayGrind { name: "deer".to_string(), kills: 1 }), state.grind);
+    }
+
+    #[test]
+    fn test_species_png() {
+        assert!(species_png("RedDeer").unwrap().starts_with(b"\x89PNG"));
+        assert!(species_png("Dragon").is_none());
+    }
+}
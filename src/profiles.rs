@@ -0,0 +1,228 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+use crate::memory::*;
+use crate::signatures::*;
+
+const BUILT_IN: &str = include_str!("../static/profiles/built_in.toml");
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PointerPath {
+    pub base: usize,
+    #[serde(default)]
+    pub chain: Vec<usize>,
+    #[serde(default)]
+    pub offset: usize,
+}
+impl PointerPath {
+    pub fn resolve(&self, mem: &dyn MemorySource, module_base: usize) -> usize {
+        let mut pointer = Pointer::new(module_base).add(mem, self.base);
+        for offset in self.chain.iter() {
+            pointer = pointer.add(mem, *offset);
+        }
+        pointer.address().wrapping_add(self.offset)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FurLookup {
+    pub table: PointerPath,
+    pub keys_offset: usize,
+    pub max_count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Offsets {
+    pub species: usize,
+    pub gender: usize,
+    pub weight: usize,
+    pub tracking: usize,
+    pub xp: usize,
+    pub cash: usize,
+    pub score: usize,
+    pub rating: usize,
+    pub integrity: usize,
+    pub fur_offset: usize,
+    pub session_score: usize,
+    pub reserve: usize,
+    pub weapon_score: usize,
+    pub shot_distance: usize,
+    pub shot_damage: usize,
+    // pointers in the shot info to the names of the weapon and ammo, left
+    // out when they are not known for a build
+    #[serde(default)]
+    pub weapon: Option<usize>,
+    #[serde(default)]
+    pub ammo: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OffsetProfile {
+    pub name: String,
+    #[serde(default)]
+    pub module_hashes: Vec<String>,
+    pub harvest: PointerPath,
+    pub shot: PointerPath,
+    pub username: PointerPath,
+    pub fur_lookups: Vec<FurLookup>,
+    pub fields: Offsets,
+    #[serde(default)]
+    pub signatures: Vec<Signature>,
+}
+impl OffsetProfile {
+    pub fn built_in() -> Self {
+        toml::from_str(BUILT_IN).expect("Built-in offset profile is invalid")
+    }
+
+    pub fn matches(&self, module_hash: &str) -> bool {
+        self.module_hashes.iter().any(|h| h.eq_ignore_ascii_case(module_hash))
+    }
+}
+
+pub enum ProfileMatch {
+    Matched(OffsetProfile),
+    Unmatched(OffsetProfile),
+    UnknownBuild(OffsetProfile),
+}
+impl ProfileMatch {
+    pub fn profile(&self) -> &OffsetProfile {
+        match self {
+            ProfileMatch::Matched(p) | ProfileMatch::Unmatched(p) | ProfileMatch::UnknownBuild(p) => p,
+        }
+    }
+
+    pub fn status(&self, module_hash: &Option<String>) -> String {
+        match self {
+            ProfileMatch::Matched(p) => format!("using {} offsets", p.name),
+            ProfileMatch::Unmatched(p) => {
+                let hash = module_hash.clone().unwrap_or_default();
+                let short_hash = hash.get(..12).unwrap_or(&hash);
+                format!("no offset profile for game build {}, using {} offsets", short_hash, p.name)
+            }
+            ProfileMatch::UnknownBuild(p) => format!("game build unknown, using {} offsets", p.name),
+        }
+    }
+}
+
+pub fn profiles_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join("profiles")
+}
+
+#[cfg_attr(not(windows), allow(dead_code))]
+pub fn module_hash(path: &Path) -> Option<String> {
+    let bytes = fs::read(path).ok()?;
+    Some(format!("{:x}", Sha256::digest(&bytes)))
+}
+
+pub fn load_profiles(dir: &Path) -> Vec<OffsetProfile> {
+    let mut profiles = Vec::new();
+    let Ok(entries) = fs::read_dir(dir) else {
+        return profiles;
+    };
+    let mut files: Vec<_> = entries.flatten().map(|e| e.path()).collect();
+    files.sort();
+    for file in files {
+        if file.extension().is_some_and(|x| x == "toml") {
+            let profile = fs::read_to_string(&file)
+                .map_err(|e| e.to_string())
+                .and_then(|s| toml::from_str::<OffsetProfile>(&s).map_err(|e| e.to_string()));
+            match profile {
+                Ok(p) => profiles.push(p),
+                Err(e) => println!("Unable to load offset profile {}: {}", file.display(), e),
+            }
+        }
+    }
+    profiles
+}
+
+pub fn select_profile(profiles: &[OffsetProfile], module_hash: &Option<String>) -> ProfileMatch {
+    let Some(hash) = module_hash else {
+        return ProfileMatch::UnknownBuild(OffsetProfile::built_in());
+    };
+    match profiles.iter().find(|p| p.matches(hash)) {
+        Some(p) => ProfileMatch::Matched(p.clone()),
+        None => ProfileMatch::Unmatched(OffsetProfile::built_in()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_built_in_profile() {
+        let profile = OffsetProfile::built_in();
+        assert_eq!(0x023F0FB8, profile.harvest.base);
+        assert_eq!(0x288, profile.harvest.offset);
+        assert_eq!(vec![0xD8, 0x2E0], profile.shot.chain);
+        assert_eq!(2, profile.fur_lookups.len());
+        assert_eq!(0x160, profile.fur_lookups[0].keys_offset);
+        assert_eq!(0xB0, profile.fields.session_score);
+        assert!(profile.signatures.is_empty());
+    }
+
+    #[test]
+    fn test_resolve_pointer_path() {
+        let mut image = MemoryImage::new();
+        image.write_usize(0x1000 + 0x10, 0x2000);
+        image.write_usize(0x2000 + 0x8, 0x3000);
+        let path = PointerPath { base: 0x10, chain: vec![0x8], offset: 0x4 };
+        assert_eq!(0x3004, path.resolve(&image, 0x1000));
+    }
+
+    #[test]
+    fn test_select_profile() {
+        let mut patched = OffsetProfile::built_in();
+        patched.name = "Patched".to_string();
+        patched.harvest.base = 0x1234;
+        patched.module_hashes = vec!["ABCDEF".to_string()];
+        let profiles = vec![patched.clone()];
+
+        let matched = select_profile(&profiles, &Some("abcdef".to_string()));
+        assert!(matches!(matched, ProfileMatch::Matched(_)));
+        assert_eq!(&patched, matched.profile());
+
+        let unmatched = select_profile(&profiles, &Some("0123456789abcdef".to_string()));
+        assert!(matches!(unmatched, ProfileMatch::Unmatched(_)));
+        assert_eq!("Built-in", unmatched.profile().name);
+        assert!(unmatched.status(&Some("0123456789abcdef".to_string())).contains("0123456789ab"));
+
+        let unknown = select_profile(&profiles, &None);
+        assert!(matches!(unknown, ProfileMatch::UnknownBuild(_)));
+    }
+
+    #[test]
+    fn test_module_hash() {
+        let path = std::env::temp_dir().join("trophy_lodge_module_hash.exe");
+        fs::write(&path, b"abc").unwrap();
+        let hash = module_hash(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(Some("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".to_string()), hash);
+        assert_eq!(None, module_hash(Path::new("does_not_exist.exe")));
+    }
+
+    #[test]
+    fn test_parse_profile() {
+        let profile = BUILT_IN.replace("name = \"Built-in\"", "name = \"Custom\"")
+            .replace("module_hashes = []", "module_hashes = [\"abc\"]");
+        let profile: OffsetProfile = toml::from_str(&profile).unwrap();
+        assert_eq!("Custom", profile.name);
+        assert!(profile.matches("ABC"));
+    }
+
+    #[test]
+    fn test_parse_signatures() {
+        let profile = format!("{}\n{}", BUILT_IN, r#"
+[[signatures]]
+target = "harvest"
+pattern = "48 8B 05 ?? ?? ?? ?? 48 85 C0"
+offset = 3
+instruction_length = 7
+"#);
+        let profile: OffsetProfile = toml::from_str(&profile).unwrap();
+        assert_eq!(1, profile.signatures.len());
+        assert_eq!(SignatureTarget::Harvest, profile.signatures[0].target);
+        assert_eq!(7, profile.signatures[0].instruction_length);
+    }
+}
@@ -0,0 +1,276 @@
+use crate::cli::parse_variant;
+use crate::data;
+use crate::events::*;
+use crate::overlay;
+use crate::models::*;
+use crate::store::*;
+use std::io::{self, Write};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+use serde::Serialize;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+const JSON: &str = "application/json";
+const HTML: &str = "text/html; charset=utf-8";
+const PNG: &str = "image/png";
+
+// Read only JSON API and event stream for overlays and dashboards. It only
+// listens on localhost and stops when dropped.
+pub struct ApiServer {
+    server: Arc<Server>,
+    addr: SocketAddr,
+}
+impl ApiServer {
+    pub fn start(store: Arc<dyn TrophyStore>, events: EventBus, port: u16) -> DataResult<ApiServer> {
+        let server = Server::http(("127.0.0.1", port)).map_err(|e| DataError::Io(io::Error::other(e)))?;
+        let addr = server.server_addr().to_ip().ok_or_else(|| DataError::Invalid("the API is not listening on an ip address".to_string()))?;
+        let server = Arc::new(server);
+        let incoming = server.clone();
+        thread::spawn(move || {
+            for request in incoming.incoming_requests() {
+                if *request.method() == Method::Get && request.url().split('?').next() == Some("/api/events") {
+                    let rx = events.subscribe();
+                    thread::spawn(move || stream_events(request, rx));
+                } else {
+                    handle(store.as_ref(), request);
+                }
+            }
+        });
+        Ok(ApiServer { server, addr })
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+}
+impl Drop for ApiServer {
+    fn drop(&mut self) {
+        self.server.unblock();
+    }
+}
+
+#[derive(Serialize)]
+struct ApiError {
+    error: String,
+}
+
+fn handle(store: &dyn TrophyStore, request: Request) {
+    let (status, content_type, body) = if *request.method() == Method::Get {
+        route(store, request.url())
+    } else {
+        (405, JSON, json(&ApiError { error: "only GET is supported".to_string() }).into_bytes())
+    };
+    let response = Response::from_data(body)
+        .with_status_code(status)
+        .with_header(Header::from_bytes("Content-Type", content_type).unwrap())
+        .with_header(Header::from_bytes("Access-Control-Allow-Origin", "*").unwrap());
+    let _ = request.respond(response);
+}
+
+// Server-Sent Events, one JSON event per message until the client goes away.
+fn stream_events(request: Request, rx: Receiver<Event>) {
+    let mut writer = request.into_writer();
+    let head = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nAccess-Control-Allow-Origin: *\r\n\r\n";
+    let mut message = head.to_string();
+    loop {
+        if writer.write_all(message.as_bytes()).and_then(|_| writer.flush()).is_err() {
+            return;
+        }
+        message = match rx.recv_timeout(KEEP_ALIVE) {
+            Ok(event) => format!("data: {}\n\n", json(&event)),
+            Err(RecvTimeoutError::Timeout) => ": keep alive\n\n".to_string(),
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+    }
+}
+
+fn json<T: Serialize + ?Sized>(value: &T) -> String {
+    serde_json::to_string(value).unwrap()
+}
+
+fn not_found(path: &str) -> (u16, &'static str, Vec<u8>) {
+    (404, JSON, json(&ApiError { error: format!("no such endpoint {}", path) }).into_bytes())
+}
+
+fn route(store: &dyn TrophyStore, url: &str) -> (u16, &'static str, Vec<u8>) {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let path = path.trim_end_matches('/');
+    if path == "/overlay" {
+        return (200, HTML, overlay::PAGE.as_bytes().to_vec());
+    }
+    if let Some(name) = path.strip_prefix("/overlay/species/").and_then(|p| p.strip_suffix(".png")) {
+        return match overlay::species_png(name) {
+            Some(png) => (200, PNG, png),
+            None => not_found(path),
+        };
+    }
+    let result = match path {
+        "/api/trophies" => trophies(store, query),
+        "/api/grinds" => store.read_grinds().map(|g| json(&g)),
+        "/api/challenges" => data::get_challenges(store).map(|c| json(&c)),
+        "/overlay/state" => overlay::state(store).map(|s| json(&s)),
+        _ => return not_found(path),
+    };
+    let (status, body) = match result {
+        Ok(body) => (200, body),
+        Err(DataError::Invalid(e)) => (400, json(&ApiError { error: e })),
+        Err(e) => (500, json(&ApiError { error: e.to_string() })),
+    };
+    (status, JSON, body.into_bytes())
+}
+
+fn decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = value.get(i + 1..i + 3).and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'+', _) => decoded.push(b' '),
+            (b'%', Some(b)) => {
+                decoded.push(b);
+                i += 2;
+            }
+            (b, _) => decoded.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+// Takes the same filters as the trophy table and the trophies command:
+// species, reserve, rating, gender, weapon, ammo, grind, sort_by and limit,
+// e.g. /api/trophies?species=red-deer&rating=diamond&sort_by=score&limit=10
+fn trophies(store: &dyn TrophyStore, query: &str) -> DataResult<String> {
+    let mut filter = TrophyFilter::default();
+    let mut limit = None;
+    for pair in query.split('&').filter(|p| !p.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let value = decode(value);
+        let invalid = |e: String| DataError::Invalid(format!("{}: {}", key, e));
+        match key {
+            "species" => filter.species = parse_variant(&value).map_err(invalid)?,
+            "reserve" => filter.reserve = parse_variant(&value).map_err(invalid)?,
+            "rating" => filter.rating = parse_variant(&value).map_err(invalid)?,
+            "gender" => filter.gender = parse_variant(&value).map_err(invalid)?,
+            "weapon" => filter.weapon = parse_variant(&value).map_err(invalid)?,
+            "ammo" => filter.ammo = parse_variant(&value).map_err(invalid)?,
+            "grind" => filter.grind = value,
+            "sort_by" => filter.sort_by = parse_variant(&value).map_err(invalid)?,
+            "limit" => limit = Some(value.parse::<usize>().map_err(|e| invalid(e.to_string()))?),
+            _ => return Err(DataError::Invalid(format!("unknown filter {}", key))),
+        }
+    }
+    let mut trophies = data::filter_data(&filter, store.read_trophies()?);
+    if let Some(limit) = limit {
+        trophies.truncate(limit);
+    }
+    Ok(json(&trophies))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sqlite_store::SqliteStore;
+    use crate::store::tests::trophy;
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::TcpStream;
+
+    fn get(addr: &str, path: &str) -> (u16, String) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n", path, addr).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        (head[9..12].parse().unwrap(), body.to_string())
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!("red deer", decode("red+deer"));
+        assert_eq!("Red Deer/Roe", decode("Red%20Deer%2FRoe"));
+        assert_eq!("100%", decode("100%"));
+    }
+
+    #[test]
+    fn test_api() {
+        let store = Arc::new(SqliteStore::open_in_memory().unwrap());
+        let mut diamond = trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 250.0);
+        diamond.rating = Rating::Diamond;
+        diamond.weapon = Weapon::Ranger243;
+        diamond.ammo = Ammo::SoftPoint243;
+        store.add_trophy(&diamond).unwrap();
+        store.add_trophy(&trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 200.0)).unwrap();
+        store.add_trophy(&trophy(Species::RoeDeer, Reserve::HirschfeldenHuntingReserve, 20.0)).unwrap();
+        data::add_grind(store.as_ref(), "deer", Species::RedDeer, Reserve::HirschfeldenHuntingReserve, Weapon::Unknown, Ammo::Unknown).unwrap();
+
+        let api = ApiServer::start(store, EventBus::new(), 0).unwrap();
+        let addr = api.url().trim_start_matches("http://").to_string();
+
+        let (status, body) = get(&addr, "/api/trophies?species=red+deer&sort_by=score");
+        assert_eq!(200, status);
+        let trophies: Vec<Trophy> = serde_json::from_str(&body).unwrap();
+        assert_eq!(2, trophies.len());
+        assert_eq!(Rating::Diamond, trophies[0].rating);
+
+        let (_, body) = get(&addr, "/api/trophies?weapon=ranger-243&ammo=.243+soft+point");
+        let trophies: Vec<Trophy> = serde_json::from_str(&body).unwrap();
+        assert_eq!(vec![diamond.id.clone()], trophies.iter().map(|t| t.id.clone()).collect::<Vec<String>>());
+
+        let (_, body) = get(&addr, "/api/trophies?limit=1");
+        assert_eq!(1, serde_json::from_str::<Vec<Trophy>>(&body).unwrap().len());
+
+        let (status, body) = get(&addr, "/api/trophies?species=dragon");
+        assert_eq!(400, status);
+        assert!(body.contains("species: expected one of"));
+
+        let (status, body) = get(&addr, "/api/grinds");
+        assert_eq!(200, status);
+        assert_eq!("deer", serde_json::from_str::<Vec<Grind>>(&body).unwrap()[0].name);
+
+        let (status, body) = get(&addr, "/api/challenges");
+        assert_eq!((200, "[]".to_string()), (status, body));
+
+        assert_eq!(404, get(&addr, "/api/nothing").0);
+
+        let (status, body) = get(&addr, "/overlay");
+        assert_eq!(200, status);
+        assert!(body.contains("EventSource(\"/api/events\")"));
+        let (status, body) = get(&addr, "/overlay/state");
+        assert_eq!(200, status);
+        assert!(body.contains("\"image\":\"/overlay/species/"));
+        assert_eq!(404, get(&addr, "/overlay/species/Dragon.png").0);
+    }
+
+    #[test]
+    fn test_event_stream() {
+        let store = Arc::new(SqliteStore::open_in_memory().unwrap());
+        let events = EventBus::new();
+        let api = ApiServer::start(store, events.clone(), 0).unwrap();
+        let addr = api.url().trim_start_matches("http://").to_string();
+
+        let mut stream = TcpStream::connect(&addr).unwrap();
+        write!(stream, "GET /api/events HTTP/1.1\r\nHost: {}\r\n\r\n", addr).unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut head = Vec::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line == "\r\n" {
+                break;
+            }
+            head.push(line);
+        }
+        assert_eq!("HTTP/1.1 200 OK\r\n", head[0]);
+        assert!(head.contains(&"Content-Type: text/event-stream\r\n".to_string()));
+
+        events.publish(Event::GrindKill { grind: "deer".to_string() });
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!("data: {\"type\":\"grind_kill\",\"grind\":\"deer\"}\n", line);
+    }
+}
@@ -0,0 +1,177 @@
+use std::fmt;
+use serde::{Serialize, Deserialize};
+use crate::memory::*;
+use crate::profiles::*;
+
+const SCAN_CHUNK: usize = 0x10000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureTarget {
+    Harvest,
+    Shot,
+    Username,
+    FurLookup,
+}
+impl fmt::Display for SignatureTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SignatureTarget::Harvest => "harvest",
+            SignatureTarget::Shot => "shot",
+            SignatureTarget::Username => "username",
+            SignatureTarget::FurLookup => "fur_lookup",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// A RIP-relative instruction that loads one of the base pointers, e.g.
+// "48 8B 05 ?? ?? ?? ??" with the displacement at offset 3 and an instruction
+// length of 7. The resolved address replaces the base of the target.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Signature {
+    pub target: SignatureTarget,
+    pub pattern: String,
+    pub offset: usize,
+    pub instruction_length: usize,
+}
+
+fn parse_pattern(pattern: &str) -> Option<Vec<Option<u8>>> {
+    let bytes: Option<Vec<Option<u8>>> = pattern
+        .split_whitespace()
+        .map(|b| match b {
+            "?" | "??" => Some(None),
+            _ => u8::from_str_radix(b, 16).ok().map(Some),
+        })
+        .collect();
+    bytes.filter(|b| !b.is_empty())
+}
+
+fn find_pattern(haystack: &[u8], pattern: &[Option<u8>]) -> Option<usize> {
+    if haystack.len() < pattern.len() {
+        return None;
+    }
+    (0..=haystack.len() - pattern.len()).find(|&i| {
+        pattern.iter().enumerate().all(|(j, p)| p.is_none_or(|b| haystack[i + j] == b))
+    })
+}
+
+pub fn scan(mem: &dyn MemorySource, start: usize, size: usize, pattern: &[Option<u8>]) -> Option<usize> {
+    let overlap = pattern.len().saturating_sub(1);
+    let mut chunk_start = 0;
+    while chunk_start < size {
+        let chunk_size = (SCAN_CHUNK + overlap).min(size - chunk_start);
+        let mut chunk = vec![0u8; chunk_size];
+        if mem.read_bytes(start + chunk_start, &mut chunk) {
+            if let Some(i) = find_pattern(&chunk, pattern) {
+                return Some(start + chunk_start + i);
+            }
+        }
+        chunk_start += SCAN_CHUNK;
+    }
+    None
+}
+
+fn resolve_signature(signature: &Signature, mem: &dyn MemorySource, module_base: usize, module_size: usize) -> Option<usize> {
+    let pattern = parse_pattern(&signature.pattern)?;
+    let found = scan(mem, module_base, module_size, &pattern)?;
+    let mut displacement = [0u8; 4];
+    if !mem.read_bytes(found + signature.offset, &mut displacement) {
+        return None;
+    }
+    let target = (found + signature.instruction_length).checked_add_signed(i32::from_le_bytes(displacement) as isize)?;
+    target.checked_sub(module_base)
+}
+
+pub fn resolve_signatures(profile: &OffsetProfile, mem: &dyn MemorySource, module_base: usize, module_size: usize) -> (OffsetProfile, Vec<SignatureTarget>) {
+    let mut resolved = profile.clone();
+    let mut failed = Vec::new();
+    for signature in profile.signatures.iter() {
+        match resolve_signature(signature, mem, module_base, module_size) {
+            Some(base) => match signature.target {
+                SignatureTarget::Harvest => resolved.harvest.base = base,
+                SignatureTarget::Shot => resolved.shot.base = base,
+                SignatureTarget::Username => resolved.username.base = base,
+                SignatureTarget::FurLookup => {
+                    for lookup in resolved.fur_lookups.iter_mut() {
+                        lookup.table.base = base;
+                    }
+                }
+            },
+            None => failed.push(signature.target),
+        }
+    }
+    (resolved, failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODULE: usize = 0x1_4000_0000;
+
+    fn module_image(size: usize, code_at: usize, code: &[u8]) -> MemoryImage {
+        let mut image = MemoryImage::new();
+        image.write(MODULE, &vec![0xCC; size]);
+        image.write(MODULE + code_at, code);
+        image
+    }
+
+    fn signature(target: SignatureTarget, pattern: &str) -> Signature {
+        Signature {
+            target,
+            pattern: pattern.to_string(),
+            offset: 3,
+            instruction_length: 7,
+        }
+    }
+
+    #[test]
+    fn test_parse_pattern() {
+        assert_eq!(Some(vec![Some(0x48), None, Some(0x05)]), parse_pattern("48 ?? 05"));
+        assert_eq!(Some(vec![None]), parse_pattern("?"));
+        assert_eq!(None, parse_pattern("48 XY"));
+        assert_eq!(None, parse_pattern(""));
+    }
+
+    #[test]
+    fn test_scan_across_chunks() {
+        let code = [0x48, 0x8B, 0x05, 0x10, 0x00, 0x00, 0x00];
+        let at = SCAN_CHUNK - 2;
+        let image = module_image(SCAN_CHUNK * 2, at, &code);
+        let pattern = parse_pattern("48 8B 05 ?? ?? ?? ??").unwrap();
+        assert_eq!(Some(MODULE + at), scan(&image, MODULE, SCAN_CHUNK * 2, &pattern));
+        let missing = parse_pattern("48 8B 0D").unwrap();
+        assert_eq!(None, scan(&image, MODULE, SCAN_CHUNK * 2, &missing));
+    }
+
+    #[test]
+    fn test_resolves_profile_bases() {
+        // mov rax, [rip + 0x100] at 0x500 and mov rcx, [rip - 0x200] at 0x900
+        let mut image = module_image(0x1000, 0x500, &[0x48, 0x8B, 0x05, 0x00, 0x01, 0x00, 0x00]);
+        image.write(MODULE + 0x900, &[0x48, 0x8B, 0x0D, 0x00, 0xFE, 0xFF, 0xFF]);
+        let mut profile = OffsetProfile::built_in();
+        profile.signatures = vec![
+            signature(SignatureTarget::Harvest, "48 8B 05 ?? ?? ?? ??"),
+            signature(SignatureTarget::FurLookup, "48 8B 0D ?? ?? ?? ??"),
+            signature(SignatureTarget::Shot, "48 8B 15 ?? ?? ?? ??"),
+        ];
+
+        let (resolved, failed) = resolve_signatures(&profile, &image, MODULE, 0x1000);
+        assert_eq!(0x500 + 7 + 0x100, resolved.harvest.base);
+        assert_eq!(0x900 + 7 - 0x200, resolved.fur_lookups[0].table.base);
+        assert_eq!(0x900 + 7 - 0x200, resolved.fur_lookups[1].table.base);
+        assert_eq!(profile.shot, resolved.shot);
+        assert_eq!(vec![SignatureTarget::Shot], failed);
+    }
+
+    #[test]
+    fn test_unreadable_module() {
+        let image = MemoryImage::new();
+        let mut profile = OffsetProfile::built_in();
+        profile.signatures = vec![signature(SignatureTarget::Username, "48 8B 05 ?? ?? ?? ??")];
+        let (resolved, failed) = resolve_signatures(&profile, &image, MODULE, 0x1000);
+        assert_eq!(profile.username, resolved.username);
+        assert_eq!(vec![SignatureTarget::Username], failed);
+    }
+}
@@ -0,0 +1,277 @@
+use std::cell::{Cell, RefCell};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use serde::{Serialize, Deserialize};
+use chrono::prelude::*;
+use crate::memory::*;
+use crate::profiles::*;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Region {
+    pub address: usize,
+    pub bytes: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Snapshot {
+    pub captured: String,
+    pub base_address: usize,
+    pub directory: Option<String>,
+    #[serde(default)]
+    pub module_hash: Option<String>,
+    #[serde(default)]
+    pub profile: Option<OffsetProfile>,
+    pub regions: Vec<Region>,
+}
+impl Snapshot {
+    pub fn new(base_address: usize, directory: Option<String>, module_hash: Option<String>, profile: Option<OffsetProfile>, image: &MemoryImage) -> Self {
+        let regions = image.regions()
+            .into_iter()
+            .map(|(address, bytes)| Region { address, bytes: to_hex(&bytes) })
+            .collect();
+        Snapshot {
+            captured: Local::now().to_rfc3339(),
+            base_address,
+            directory,
+            module_hash,
+            profile,
+            regions,
+        }
+    }
+
+    pub fn image(&self) -> MemoryImage {
+        let mut image = MemoryImage::new();
+        for region in self.regions.iter() {
+            if let Some(bytes) = from_hex(&region.bytes) {
+                image.write(region.address, &bytes);
+            }
+        }
+        image
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn read_snapshot(path: &Path) -> io::Result<Snapshot> {
+    let contents = fs::read_to_string(path)?;
+    serde_json::from_str(&contents).map_err(io::Error::from)
+}
+
+fn write_snapshot(path: &Path, snapshot: &Snapshot) -> io::Result<()> {
+    let contents = serde_json::to_string_pretty(snapshot)?;
+    fs::write(path, contents)
+}
+
+pub fn read_snapshots(path: &Path) -> io::Result<Vec<Snapshot>> {
+    if path.is_file() {
+        return Ok(vec![read_snapshot(path)?]);
+    }
+    let mut files = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let file = entry?.path();
+        if file.extension().is_some_and(|x| x == "json") {
+            files.push(file);
+        }
+    }
+    files.sort();
+    files.iter().map(|f| read_snapshot(f)).collect()
+}
+
+// Highest snapshot number already in the directory, so a new recording
+// carries on after an earlier one instead of overwriting it.
+fn last_snapshot_number(dir: &Path) -> usize {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            name.strip_prefix("snapshot_")?.strip_suffix(".json")?.parse::<usize>().ok()
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+// Wraps the live game and keeps every byte the monitor reads. At the end of
+// each poll the bytes are written out as a snapshot unless nothing changed.
+pub struct RecordingSource {
+    inner: Box<dyn MemorySource>,
+    dir: PathBuf,
+    base_address: usize,
+    directory: Option<String>,
+    module_hash: Option<String>,
+    profile: Option<OffsetProfile>,
+    frame: RefCell<MemoryImage>,
+    last_frame: RefCell<MemoryImage>,
+    saved: Cell<usize>,
+}
+impl RecordingSource {
+    pub fn new(inner: Box<dyn MemorySource>, dir: PathBuf, base_address: usize, directory: Option<String>, module_hash: Option<String>, profile: Option<OffsetProfile>) -> Self {
+        let saved = last_snapshot_number(&dir);
+        RecordingSource {
+            inner,
+            dir,
+            base_address,
+            directory,
+            module_hash,
+            profile,
+            frame: RefCell::new(MemoryImage::new()),
+            last_frame: RefCell::new(MemoryImage::new()),
+            saved: Cell::new(saved),
+        }
+    }
+
+    pub fn take_frame(&self) -> Option<MemoryImage> {
+        let frame = self.frame.replace(MemoryImage::new());
+        if frame.is_empty() || frame == *self.last_frame.borrow() {
+            return None;
+        }
+        self.last_frame.replace(frame.clone());
+        Some(frame)
+    }
+}
+impl MemorySource for RecordingSource {
+    fn read_bytes(&self, address: usize, buf: &mut [u8]) -> bool {
+        if self.inner.read_bytes(address, buf) {
+            self.frame.borrow_mut().write(address, buf);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn end_frame(&self) {
+        if let Some(frame) = self.take_frame() {
+            let snapshot = Snapshot::new(self.base_address, self.directory.clone(), self.module_hash.clone(), self.profile.clone(), &frame);
+            let count = self.saved.get() + 1;
+            let path = self.dir.join(format!("snapshot_{:04}.json", count));
+            let result = fs::create_dir_all(&self.dir).and_then(|_| write_snapshot(&path, &snapshot));
+            match result {
+                Ok(_) => self.saved.set(count),
+                Err(e) => println!("Unable to write snapshot {}: {}", path.display(), e),
+            }
+        }
+    }
+}
+
+// Plays back recorded snapshots, one per poll. Once they run out every read
+// fails, which the monitor treats as the game being closed.
+pub struct ReplaySource {
+    frames: Vec<MemoryImage>,
+    current: Cell<usize>,
+}
+impl ReplaySource {
+    pub fn new(snapshots: &[Snapshot]) -> Self {
+        ReplaySource {
+            frames: snapshots.iter().map(|s| s.image()).collect(),
+            current: Cell::new(0),
+        }
+    }
+}
+impl MemorySource for ReplaySource {
+    fn read_bytes(&self, address: usize, buf: &mut [u8]) -> bool {
+        match self.frames.get(self.current.get()) {
+            Some(frame) => frame.read_bytes(address, buf),
+            None => false,
+        }
+    }
+
+    fn end_frame(&self) {
+        self.current.set(self.current.get() + 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex() {
+        let bytes = vec![0x00, 0x0F, 0xA0, 0xFF];
+        assert_eq!("000FA0FF", to_hex(&bytes));
+        assert_eq!(Some(bytes), from_hex("000FA0FF"));
+        assert_eq!(None, from_hex("0"));
+        assert_eq!(None, from_hex("ZZ"));
+    }
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let mut image = MemoryImage::new();
+        image.write_int(0x100, 42);
+        image.write_string(0x200, "red-deer");
+        let snapshot = Snapshot::new(0x1000, Some("C:\\game".to_string()), Some("abc".to_string()), Some(OffsetProfile::built_in()), &image);
+        assert_eq!(2, snapshot.regions.len());
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let loaded: Snapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(snapshot, loaded);
+        assert_eq!(image, loaded.image());
+    }
+
+    #[test]
+    fn test_recording_keeps_reads() {
+        let mut image = MemoryImage::new();
+        image.write_int(0x100, 42);
+        image.write_int(0x200, 7);
+        let recorder = RecordingSource::new(Box::new(image), PathBuf::from("unused"), 0x0, None, None, None);
+        assert_eq!(42, read_int(&recorder, 0x100, 0x0));
+        assert_eq!(-1, read_int(&recorder, 0x300, 0x0));
+
+        let frame = recorder.take_frame().unwrap();
+        assert_eq!(42, read_int(&frame, 0x100, 0x0));
+        assert_eq!(-1, read_int(&frame, 0x200, 0x0));
+
+        read_int(&recorder, 0x100, 0x0);
+        assert_eq!(None, recorder.take_frame());
+    }
+
+    #[test]
+    fn test_recording_keeps_earlier_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        for value in [1, 2] {
+            let mut image = MemoryImage::new();
+            image.write_int(0x100, value);
+            let recorder = RecordingSource::new(Box::new(image), dir.path().to_path_buf(), 0x0, None, None, None);
+            read_int(&recorder, 0x100, 0x0);
+            recorder.end_frame();
+        }
+        assert!(dir.path().join("snapshot_0001.json").is_file());
+        assert!(dir.path().join("snapshot_0002.json").is_file());
+
+        let snapshots = read_snapshots(dir.path()).unwrap();
+        let values: Vec<i32> = snapshots.iter().map(|s| read_int(&s.image(), 0x100, 0x0)).collect();
+        assert_eq!(vec![1, 2], values);
+    }
+
+    #[test]
+    fn test_replay_advances() {
+        let mut first = MemoryImage::new();
+        first.write_int(0x100, 1);
+        let mut second = MemoryImage::new();
+        second.write_int(0x100, 2);
+        let snapshots = vec![
+            Snapshot::new(0x0, None, None, None, &first),
+            Snapshot::new(0x0, None, None, None, &second),
+        ];
+        let replay = ReplaySource::new(&snapshots);
+        assert_eq!(1, read_int(&replay, 0x100, 0x0));
+        replay.end_frame();
+        assert_eq!(2, read_int(&replay, 0x100, 0x0));
+        replay.end_frame();
+        assert_eq!(-1, read_int(&replay, 0x100, 0x0));
+    }
+}
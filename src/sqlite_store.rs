@@ -0,0 +1,359 @@
+use crate::models::*;
+use crate::challenges::*;
+use crate::store::*;
+use crate::migrations::{self, AppliedMigration};
+use crate::backup::version_backup_path;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
+
+fn key(value: &impl std::fmt::Debug) -> String {
+    format!("{:?}", value)
+}
+
+fn from_key<T: serde::de::DeserializeOwned>(value: String) -> Option<T> {
+    serde_json::from_value(serde_json::Value::String(value)).ok()
+}
+
+fn challenge_key(name: &str) -> String {
+    convert_challenge_name(name).trim_end_matches(".csv").to_string()
+}
+
+pub struct SqliteStore {
+    conn: Mutex<Connection>,
+    path: Option<PathBuf>,
+}
+impl SqliteStore {
+    pub fn open(path: &Path) -> DataResult<Self> {
+        Self::init(Connection::open(path)?, Some(path.to_path_buf()))
+    }
+
+    pub fn check(path: &Path) -> bool {
+        let Ok(conn) = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY) else {
+            return false;
+        };
+        conn.query_row("PRAGMA integrity_check", [], |row| row.get::<_, String>(0))
+            .is_ok_and(|r| r == "ok")
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> DataResult<Self> {
+        let store = Self::init(Connection::open_in_memory()?, None)?;
+        store.migrate()?;
+        Ok(store)
+    }
+
+    fn init(conn: Connection, path: Option<PathBuf>) -> DataResult<Self> {
+        conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+        Ok(SqliteStore { conn: Mutex::new(conn), path })
+    }
+
+    // Brings the schema up to date. A database file is copied to
+    // lodge.db.v<old version> before the first pending migration runs.
+    pub fn migrate(&self) -> DataResult<Vec<String>> {
+        let mut conn = self.conn.lock().unwrap();
+        let version = migrations::schema_version(&conn)?;
+        let backup = self.path.as_ref().map(|p| version_backup_path(p, version));
+        migrations::migrate(&mut conn, backup.as_deref())
+    }
+
+    pub fn schema_version(&self) -> DataResult<u32> {
+        migrations::schema_version(&self.conn.lock().unwrap())
+    }
+
+    pub fn migration_log(&self) -> DataResult<Vec<AppliedMigration>> {
+        migrations::migration_log(&self.conn.lock().unwrap())
+    }
+
+    fn insert_trophy(conn: &Connection, trophy: &Trophy) -> DataResult<()> {
+        conn.execute(
+            "INSERT OR IGNORE INTO trophies (id, species, reserve, rating, date, payload) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![trophy.id, key(&trophy.species), key(&trophy.reserve), key(&trophy.rating), trophy.date, serde_json::to_string(trophy)?],
+        )?;
+        Ok(())
+    }
+}
+impl TrophyStore for SqliteStore {
+    fn read_trophies(&self) -> DataResult<Vec<Trophy>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT payload FROM trophies ORDER BY rowid")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        let mut trophies = Vec::new();
+        for payload in rows {
+            trophies.push(serde_json::from_str(&payload?)?);
+        }
+        Ok(trophies)
+    }
+
+    fn trophy_exists(&self, trophy: &Trophy) -> DataResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let found = conn.query_row("SELECT 1 FROM trophies WHERE id = ?1", [&trophy.id], |_| Ok(())).optional()?;
+        Ok(found.is_some())
+    }
+
+    fn add_trophy(&self, trophy: &Trophy) -> DataResult<()> {
+        Self::insert_trophy(&self.conn.lock().unwrap(), trophy)
+    }
+
+    fn replace_trophies(&self, trophies: &[Trophy]) -> DataResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM trophies", [])?;
+        for trophy in trophies {
+            Self::insert_trophy(&tx, trophy)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn read_grinds(&self) -> DataResult<Vec<Grind>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT name, species, reserve, active, start, kills, weapon, ammo FROM grinds ORDER BY rowid")?;
+        let rows = stmt.query_map([], |row| {
+            Ok(Grind {
+                name: row.get(0)?,
+                species: from_key(row.get(1)?).unwrap_or(Species::Unknown),
+                reserve: from_key(row.get(2)?).unwrap_or(Reserve::Unknown),
+                weapon: from_key(row.get(6)?).unwrap_or(Weapon::Unknown),
+                ammo: from_key(row.get(7)?).unwrap_or(Ammo::Unknown),
+                active: row.get(3)?,
+                start: row.get(4)?,
+                kills: row.get(5)?,
+                is_deleted: false,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn add_grind(&self, grind: &Grind) -> DataResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO grinds (name, species, reserve, active, start, kills, weapon, ammo) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![grind.name, key(&grind.species), key(&grind.reserve), grind.active, grind.start, grind.kills, key(&grind.weapon), key(&grind.ammo)],
+        )?;
+        Ok(())
+    }
+
+    fn remove_grind(&self, name: &str) -> DataResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM grinds WHERE name = ?1", [name])?;
+        Ok(())
+    }
+
+    fn set_grind_active(&self, name: &str, active: bool) -> DataResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("UPDATE grinds SET active = ?1 WHERE name = ?2", params![active, name])?;
+        Ok(())
+    }
+
+    fn add_grind_kill(&self, name: &str, trophy: &Trophy) -> DataResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("INSERT INTO grind_kills (grind, trophy_id, date) VALUES (?1, ?2, ?3)", params![name, trophy.id, trophy.date])?;
+        tx.execute("UPDATE grinds SET kills = kills + 1 WHERE name = ?1", [name])?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn read_challenges(&self) -> DataResult<Vec<Vec<Challenge>>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT challenge, kills_remaining, payload FROM challenge_criteria ORDER BY challenge, id")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, u32>(1)?, row.get::<_, String>(2)?))
+        })?;
+        let mut challenges: Vec<(String, Vec<Challenge>)> = Vec::new();
+        for row in rows {
+            let (challenge, kills_remaining, payload) = row?;
+            let mut c: Challenge = serde_json::from_str(&payload)?;
+            c.kills_remaining = kills_remaining;
+            match challenges.last_mut() {
+                Some((key, criteria)) if *key == challenge => criteria.push(c),
+                _ => challenges.push((challenge, vec![c])),
+            }
+        }
+        Ok(challenges.into_iter().map(|(_, c)| c).collect())
+    }
+
+    fn read_challenge(&self, name: &str) -> DataResult<Vec<Challenge>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT kills_remaining, payload FROM challenge_criteria WHERE challenge = ?1 ORDER BY id")?;
+        let rows = stmt.query_map([challenge_key(name)], |row| Ok((row.get::<_, u32>(0)?, row.get::<_, String>(1)?)))?;
+        let mut criteria = Vec::new();
+        for row in rows {
+            let (kills_remaining, payload) = row?;
+            let mut c: Challenge = serde_json::from_str(&payload)?;
+            c.kills_remaining = kills_remaining;
+            criteria.push(c);
+        }
+        Ok(criteria)
+    }
+
+    fn challenge_exists(&self, name: &str) -> DataResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let found = conn.query_row("SELECT 1 FROM challenges WHERE key = ?1", [challenge_key(name)], |_| Ok(())).optional()?;
+        Ok(found.is_some())
+    }
+
+    fn add_challenge(&self, criteria: &[Challenge]) -> DataResult<()> {
+        let Some(first) = criteria.first() else {
+            return Ok(());
+        };
+        let challenge = challenge_key(&first.name);
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT OR REPLACE INTO challenges (key, name, description, start) VALUES (?1, ?2, ?3, ?4)",
+            params![challenge, first.name, first.description, first.start],
+        )?;
+        for c in criteria {
+            tx.execute(
+                "INSERT INTO challenge_criteria (challenge, species, reserve, kills_remaining, payload) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![challenge, key(&c.species), key(&c.reserve), c.kills_remaining, serde_json::to_string(c)?],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn delete_challenge(&self, name: &str) -> DataResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM challenges WHERE key = ?1", [challenge_key(name)])?;
+        Ok(())
+    }
+
+    fn record_challenge_kill(&self, trophy: &Trophy) -> DataResult<Vec<String>> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut updated: Vec<String> = Vec::new();
+        {
+            let mut stmt = tx.prepare(
+                "SELECT c.id, c.payload, h.name FROM challenge_criteria c JOIN challenges h ON h.key = c.challenge
+                 WHERE c.kills_remaining > 0 AND c.species IN (?1, 'Unknown') AND c.reserve IN (?2, 'Unknown')
+                 ORDER BY c.challenge, c.id",
+            )?;
+            let rows = stmt.query_map(params![key(&trophy.species), key(&trophy.reserve)], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+            })?;
+            let rows: Vec<_> = rows.collect::<Result<_, _>>()?;
+            for (id, payload, name) in rows {
+                let criteria: Challenge = serde_json::from_str(&payload)?;
+                if criteria.for_trophy(trophy) {
+                    if criteria.collect == Collect::Kills {
+                        tx.execute("UPDATE challenge_criteria SET kills_remaining = kills_remaining - 1 WHERE id = ?1", [id])?;
+                    }
+                    if !updated.contains(&name) {
+                        updated.push(name);
+                    }
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(updated)
+    }
+
+    fn read_challenge_history(&self) -> DataResult<Vec<ChallengeRun>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT name, description, start, end, finished, status, percent FROM challenge_history ORDER BY start, name")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                ChallengeRun {
+                    name: row.get(0)?,
+                    description: row.get(1)?,
+                    start: row.get(2)?,
+                    end: row.get(3)?,
+                    finished: row.get(4)?,
+                    status: ChallengeStatus::Active,
+                    percent: row.get(6)?,
+                },
+                row.get::<_, String>(5)?,
+            ))
+        })?;
+        let mut runs = Vec::new();
+        for row in rows {
+            let (mut run, status) = row?;
+            run.status = from_key(status).unwrap_or(ChallengeStatus::Expired);
+            runs.push(run);
+        }
+        Ok(runs)
+    }
+
+    fn add_challenge_history(&self, run: &ChallengeRun) -> DataResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR IGNORE INTO challenge_history (name, description, start, end, finished, status, percent) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![run.name, run.description, run.start, run.end, run.finished, key(&run.status), run.percent],
+        )?;
+        Ok(())
+    }
+
+    fn read_problems(&self) -> DataResult<Vec<SkippedRow>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT file, line, error FROM data_problems ORDER BY file, line")?;
+        let rows = stmt.query_map([], |row| {
+            Ok(SkippedRow {
+                file: PathBuf::from(row.get::<_, String>(0)?),
+                line: row.get(1)?,
+                error: row.get(2)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn set_problems(&self, file: &Path, problems: &[SkippedRow]) -> DataResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM data_problems WHERE file = ?1", [file.to_string_lossy()])?;
+        for p in problems {
+            tx.execute(
+                "INSERT OR REPLACE INTO data_problems (file, line, error) VALUES (?1, ?2, ?3)",
+                params![p.file.to_string_lossy(), p.line, p.error],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv_store::CsvStore;
+    use crate::store::tests::{check_store, trophy};
+
+    #[test]
+    fn test_sqlite_store() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        check_store(&store);
+    }
+
+    #[test]
+    fn test_copy_between_stores() {
+        let root = tempfile::tempdir().unwrap();
+        let csv = CsvStore::new(root.path()).unwrap();
+        let trophy = trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 200.5);
+        csv.add_trophy(&trophy).unwrap();
+        let mut challenge = Challenge {
+            name: "Any Red Deer".to_string(),
+            species: Species::RedDeer,
+            kills: 3,
+            kills_remaining: 2,
+            ..Challenge::default()
+        };
+        csv.add_challenge(&[challenge.clone()]).unwrap();
+
+        let sqlite = SqliteStore::open_in_memory().unwrap();
+        copy_store(&csv, &sqlite).unwrap();
+        copy_store(&csv, &sqlite).unwrap();
+        assert!(sqlite.trophy_exists(&trophy).unwrap());
+        assert_eq!(1, sqlite.read_trophies().unwrap().len());
+        assert_eq!(vec![vec![challenge.clone()]], sqlite.read_challenges().unwrap());
+        assert_eq!(vec!["Any Red Deer".to_string()], sqlite.record_challenge_kill(&trophy).unwrap());
+
+        let export = root.path().join("export");
+        let exported = CsvStore::new(&export).unwrap();
+        copy_store(&sqlite, &exported).unwrap();
+        challenge.kills_remaining = 1;
+        assert_eq!(vec![vec![challenge]], exported.read_challenges().unwrap());
+        assert_eq!(trophy.id, exported.read_trophies().unwrap()[0].id);
+    }
+}
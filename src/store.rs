@@ -0,0 +1,239 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::models::*;
+
+#[derive(Debug)]
+pub enum DataError {
+    Io(io::Error),
+    Csv(csv::Error),
+    Sqlite(rusqlite::Error),
+    Json(serde_json::Error),
+    Invalid(String),
+}
+impl fmt::Display for DataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataError::Io(e) => write!(f, "{}", e),
+            DataError::Csv(e) => write!(f, "{}", e),
+            DataError::Sqlite(e) => write!(f, "database error: {}", e),
+            DataError::Json(e) => write!(f, "{}", e),
+            DataError::Invalid(e) => write!(f, "{}", e),
+        }
+    }
+}
+impl std::error::Error for DataError {}
+impl From<io::Error> for DataError {
+    fn from(e: io::Error) -> Self {
+        DataError::Io(e)
+    }
+}
+impl From<csv::Error> for DataError {
+    fn from(e: csv::Error) -> Self {
+        DataError::Csv(e)
+    }
+}
+impl From<rusqlite::Error> for DataError {
+    fn from(e: rusqlite::Error) -> Self {
+        DataError::Sqlite(e)
+    }
+}
+impl From<serde_json::Error> for DataError {
+    fn from(e: serde_json::Error) -> Self {
+        DataError::Json(e)
+    }
+}
+
+pub type DataResult<T> = Result<T, DataError>;
+
+// A csv row that could not be read, kept so it can be shown and repaired
+// instead of disappearing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkippedRow {
+    pub file: PathBuf,
+    pub line: u64,
+    pub error: String,
+}
+
+pub trait TrophyStore: Send + Sync {
+    fn read_trophies(&self) -> DataResult<Vec<Trophy>>;
+    fn trophy_exists(&self, trophy: &Trophy) -> DataResult<bool>;
+    fn add_trophy(&self, trophy: &Trophy) -> DataResult<()>;
+    fn replace_trophies(&self, trophies: &[Trophy]) -> DataResult<()>;
+
+    fn read_grinds(&self) -> DataResult<Vec<Grind>>;
+    fn add_grind(&self, grind: &Grind) -> DataResult<()>;
+    fn remove_grind(&self, name: &str) -> DataResult<()>;
+    fn set_grind_active(&self, name: &str, active: bool) -> DataResult<()>;
+    fn add_grind_kill(&self, name: &str, trophy: &Trophy) -> DataResult<()>;
+
+    // each challenge is returned as its expanded criteria rows
+    fn read_challenges(&self) -> DataResult<Vec<Vec<Challenge>>>;
+    // the rows of one challenge, empty when there is none by that name
+    fn read_challenge(&self, name: &str) -> DataResult<Vec<Challenge>>;
+    fn challenge_exists(&self, name: &str) -> DataResult<bool>;
+    fn add_challenge(&self, criteria: &[Challenge]) -> DataResult<()>;
+    fn delete_challenge(&self, name: &str) -> DataResult<()>;
+    fn record_challenge_kill(&self, trophy: &Trophy) -> DataResult<Vec<String>>;
+
+    // finished challenge runs, a run already recorded for the same name and
+    // start is left as it is
+    fn read_challenge_history(&self) -> DataResult<Vec<ChallengeRun>>;
+    fn add_challenge_history(&self, run: &ChallengeRun) -> DataResult<()>;
+
+    fn read_problems(&self) -> DataResult<Vec<SkippedRow>>;
+    // replaces the problems recorded for a file
+    fn set_problems(&self, file: &Path, problems: &[SkippedRow]) -> DataResult<()>;
+}
+
+pub fn copy_store(from: &dyn TrophyStore, to: &dyn TrophyStore) -> DataResult<()> {
+    to.replace_trophies(&from.read_trophies()?)?;
+    for grind in from.read_grinds()? {
+        to.remove_grind(&grind.name)?;
+        to.add_grind(&grind)?;
+    }
+    for criteria in from.read_challenges()? {
+        if let Some(c) = criteria.first() {
+            to.delete_challenge(&c.name)?;
+            to.add_challenge(&criteria)?;
+        }
+    }
+    for run in from.read_challenge_history()? {
+        to.add_challenge_history(&run)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    pub fn trophy(species: Species, reserve: Reserve, score: f32) -> Trophy {
+        let mut trophy = Trophy {
+            id: String::new(),
+            species,
+            reserve,
+            rating: Rating::Gold,
+            score,
+            weight: 180.5,
+            fur: "Piebald".to_string(),
+            date: "2024-01-01T00:00:00+00:00".to_string(),
+            gender: Gender::Male,
+            cash: 100,
+            xp: 200,
+            session_score: 10,
+            integrity: Boolean::True,
+            tracking: 0.0,
+            weapon_score: 0.0,
+            shot_distance: 0.0,
+            shot_damage: 0.0,
+            weapon: Weapon::Unknown,
+            ammo: Ammo::Unknown,
+            mods: Boolean::False,
+            grind: None,
+        };
+        trophy.id = trophy.identity();
+        trophy
+    }
+
+    pub fn grind(name: &str) -> Grind {
+        Grind {
+            name: name.to_string(),
+            species: Species::RedDeer,
+            reserve: Reserve::HirschfeldenHuntingReserve,
+            weapon: Weapon::Unknown,
+            ammo: Ammo::Unknown,
+            active: true,
+            start: "2024-01-01T00:00:00+00:00".to_string(),
+            kills: 0,
+            is_deleted: false,
+        }
+    }
+
+    fn challenge(name: &str, species: Species, kills: u32) -> Challenge {
+        Challenge {
+            name: name.to_string(),
+            species,
+            reserve: Reserve::HirschfeldenHuntingReserve,
+            kills,
+            kills_remaining: kills,
+            ..Challenge::default()
+        }
+    }
+
+    // every store implementation is expected to pass this
+    pub fn check_store(store: &dyn TrophyStore) {
+        let red_deer = trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 200.5);
+        let roe_deer = trophy(Species::RoeDeer, Reserve::HirschfeldenHuntingReserve, 20.0);
+        assert!(!store.trophy_exists(&red_deer).unwrap());
+        store.add_trophy(&red_deer).unwrap();
+        store.add_trophy(&roe_deer).unwrap();
+        assert!(store.trophy_exists(&red_deer).unwrap());
+        let trophies = store.read_trophies().unwrap();
+        assert_eq!(2, trophies.len());
+        assert_eq!(red_deer.id, trophies[0].id);
+        assert_eq!(red_deer.score, trophies[0].score);
+        store.replace_trophies(std::slice::from_ref(&roe_deer)).unwrap();
+        assert!(!store.trophy_exists(&red_deer).unwrap());
+        assert_eq!(1, store.read_trophies().unwrap().len());
+
+        store.add_grind(&grind("deer")).unwrap();
+        store.add_grind(&grind("other")).unwrap();
+        store.add_grind_kill("deer", &red_deer).unwrap();
+        store.set_grind_active("other", false).unwrap();
+        let grinds = store.read_grinds().unwrap();
+        assert_eq!(2, grinds.len());
+        assert_eq!(1, grinds.iter().find(|g| g.name == "deer").unwrap().kills);
+        assert!(!grinds.iter().find(|g| g.name == "other").unwrap().active);
+        store.remove_grind("other").unwrap();
+        assert_eq!(1, store.read_grinds().unwrap().len());
+
+        store.add_challenge(&[challenge("Deer Hunt", Species::RedDeer, 2), challenge("Deer Hunt", Species::RoeDeer, 1)]).unwrap();
+        store.add_challenge(&[challenge("Roe Only", Species::RoeDeer, 1)]).unwrap();
+        assert!(store.challenge_exists("Deer Hunt").unwrap());
+        assert!(store.challenge_exists("deer hunt").unwrap());
+        assert_eq!(vec!["Deer Hunt".to_string()], store.record_challenge_kill(&red_deer).unwrap());
+        let mut updated = store.record_challenge_kill(&roe_deer).unwrap();
+        updated.sort();
+        assert_eq!(vec!["Deer Hunt".to_string(), "Roe Only".to_string()], updated);
+        assert!(store.record_challenge_kill(&roe_deer).unwrap().is_empty());
+
+        let challenges = store.read_challenges().unwrap();
+        assert_eq!(2, challenges.len());
+        let deer_hunt = challenges.iter().find(|c| c[0].name == "Deer Hunt").unwrap();
+        assert_eq!(2, deer_hunt.len());
+        let remaining: u32 = deer_hunt.iter().map(|c| c.kills_remaining).sum();
+        assert_eq!(1, remaining);
+        assert_eq!(*deer_hunt, store.read_challenge("deer hunt").unwrap());
+        assert!(store.read_challenge("Elk Hunt").unwrap().is_empty());
+
+        store.delete_challenge("Deer Hunt").unwrap();
+        assert!(!store.challenge_exists("Deer Hunt").unwrap());
+        assert_eq!(1, store.read_challenges().unwrap().len());
+
+        let run = |start: &str, status| ChallengeRun {
+            name: "Deer Hunt".to_string(),
+            description: "".to_string(),
+            start: start.to_string(),
+            end: "2024-01-08T00:00:00+00:00".to_string(),
+            finished: "2024-01-08T00:00:00+00:00".to_string(),
+            status,
+            percent: 0.5,
+        };
+        store.add_challenge_history(&run("2024-01-01T00:00:00+00:00", ChallengeStatus::Expired)).unwrap();
+        store.add_challenge_history(&run("2024-01-01T00:00:00+00:00", ChallengeStatus::Completed)).unwrap();
+        store.add_challenge_history(&run("2024-02-01T00:00:00+00:00", ChallengeStatus::Completed)).unwrap();
+        let history = store.read_challenge_history().unwrap();
+        assert_eq!(vec![run("2024-01-01T00:00:00+00:00", ChallengeStatus::Expired), run("2024-02-01T00:00:00+00:00", ChallengeStatus::Completed)], history);
+
+        let file = Path::new("imported").join("trophies.csv");
+        let problem = |line| SkippedRow { file: file.clone(), line, error: "missing field".to_string() };
+        store.set_problems(&file, &[problem(3), problem(7)]).unwrap();
+        store.set_problems(Path::new("grinds.csv"), &[]).unwrap();
+        assert_eq!(vec![problem(3), problem(7)], store.read_problems().unwrap());
+        store.set_problems(&file, &[problem(2)]).unwrap();
+        assert_eq!(vec![problem(2)], store.read_problems().unwrap());
+        store.set_problems(&file, &[]).unwrap();
+        assert!(store.read_problems().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,193 @@
+use crate::events::*;
+use crate::models::*;
+use crate::store::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+pub const WEBHOOKS: &str = "webhooks.toml";
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PayloadFormat {
+    #[default]
+    Json,
+    Discord,
+}
+
+// One outgoing webhook and the kills it fires on, e.g.
+//
+// [[webhook]]
+// name = "Discord"
+// url = "https://discord.com/api/webhooks/..."
+// format = "discord"
+// ratings = ["Diamond", "GreatOne"]
+// furs = ["Albino", "Melanistic"]
+// challenge_completed = true
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Webhook {
+    pub name: String,
+    pub url: String,
+    #[serde(default)]
+    pub format: PayloadFormat,
+    #[serde(default)]
+    pub ratings: Vec<Rating>,
+    #[serde(default)]
+    pub furs: Vec<String>,
+    #[serde(default)]
+    pub challenge_completed: bool,
+}
+impl Webhook {
+    pub fn fires_on(&self, event: &Event) -> bool {
+        match event {
+            Event::TrophyStored { trophy } => {
+                self.ratings.contains(&trophy.rating) || self.furs.iter().any(|f| f.eq_ignore_ascii_case(&trophy.fur))
+            }
+            Event::ChallengeCompleted { .. } => self.challenge_completed,
+            _ => false,
+        }
+    }
+
+    pub fn payload(&self, event: &Event) -> serde_json::Value {
+        let message = message(event);
+        match self.format {
+            PayloadFormat::Json => json!({ "message": message, "event": event }),
+            PayloadFormat::Discord => json!({ "content": message }),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct WebhookFile {
+    #[serde(default)]
+    webhook: Vec<Webhook>,
+}
+
+pub fn webhooks_path(data_dir: &Path) -> PathBuf {
+    data_dir.join(WEBHOOKS)
+}
+
+pub fn load_webhooks(path: &Path) -> DataResult<Vec<Webhook>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let file: WebhookFile = toml::from_str(&fs::read_to_string(path)?)
+        .map_err(|e| DataError::Invalid(format!("{}: {}", path.display(), e)))?;
+    Ok(file.webhook)
+}
+
+pub fn message(event: &Event) -> String {
+    match event {
+        Event::TrophyStored { trophy } => format!(
+            "{} {} on {}: score {:.2}, {:.1} kg, {} fur",
+            trophy.rating, trophy.species, trophy.reserve, trophy.score, trophy.weight, trophy.fur
+        ),
+        Event::ChallengeCompleted { challenge } => format!("Challenge {} completed", challenge),
+        event => event.status_line().unwrap_or_default(),
+    }
+}
+
+pub fn send(agent: &ureq::Agent, webhook: &Webhook, event: &Event) -> Result<(), String> {
+    agent
+        .post(&webhook.url)
+        .send_json(webhook.payload(event))
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Posts on a worker thread of its own so slow or unreachable hosts never hold
+// up the monitor. Failures are reported back on the bus as a status.
+pub fn spawn_sender(webhooks: Vec<Webhook>, events: &EventBus) {
+    if webhooks.is_empty() {
+        return;
+    }
+    let rx = events.subscribe();
+    let events = events.clone();
+    thread::spawn(move || {
+        let agent = ureq::AgentBuilder::new().timeout(Duration::from_secs(10)).build();
+        for event in rx {
+            for webhook in webhooks.iter().filter(|w| w.fires_on(&event)) {
+                if let Err(e) = send(&agent, webhook, &event) {
+                    events.publish(Event::status(format!("Webhook {} failed: {}", webhook.name, e)));
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::trophy;
+
+    fn hook(url: &str, format: PayloadFormat) -> Webhook {
+        Webhook {
+            name: "test".to_string(),
+            url: url.to_string(),
+            format,
+            ratings: vec![Rating::Diamond, Rating::GreatOne],
+            furs: vec!["Albino".to_string()],
+            challenge_completed: true,
+        }
+    }
+
+    #[test]
+    fn test_load_webhooks() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = webhooks_path(dir.path());
+        assert!(load_webhooks(&path).unwrap().is_empty());
+        fs::write(&path, "[[webhook]]\nname = \"Discord\"\nurl = \"http://localhost\"\nformat = \"discord\"\nratings = [\"GreatOne\"]\n").unwrap();
+        let webhooks = load_webhooks(&path).unwrap();
+        assert_eq!(PayloadFormat::Discord, webhooks[0].format);
+        assert_eq!(vec![Rating::GreatOne], webhooks[0].ratings);
+        assert!(!webhooks[0].challenge_completed);
+        fs::write(&path, "[[webhook]]\nname = \"Broken\"\n").unwrap();
+        assert!(matches!(load_webhooks(&path), Err(DataError::Invalid(_))));
+    }
+
+    #[test]
+    fn test_fires_on() {
+        let webhook = hook("http://localhost", PayloadFormat::Json);
+        let mut t = trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 200.0);
+        assert!(!webhook.fires_on(&Event::TrophyStored { trophy: t.clone() }));
+        t.fur = "albino".to_string();
+        assert!(webhook.fires_on(&Event::TrophyStored { trophy: t.clone() }));
+        t.fur = "Piebald".to_string();
+        t.rating = Rating::Diamond;
+        assert!(webhook.fires_on(&Event::TrophyStored { trophy: t.clone() }));
+        assert!(!webhook.fires_on(&Event::Duplicate { trophy: t }));
+        assert!(webhook.fires_on(&Event::ChallengeCompleted { challenge: "Deer".to_string() }));
+        assert!(!webhook.fires_on(&Event::ChallengeProgress { challenge: "Deer".to_string(), percent: 0.5 }));
+    }
+
+    #[test]
+    fn test_posts_to_local_server() {
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", server.server_addr().to_ip().unwrap());
+        let events = EventBus::new();
+        spawn_sender(vec![hook(&url, PayloadFormat::Json), Webhook { name: "discord".to_string(), format: PayloadFormat::Discord, ..hook(&url, PayloadFormat::Discord) }], &events);
+
+        let mut t = trophy(Species::RedDeer, Reserve::HirschfeldenHuntingReserve, 250.0);
+        events.publish(Event::TrophyStored { trophy: t.clone() });
+        t.rating = Rating::Diamond;
+        events.publish(Event::TrophyStored { trophy: t });
+
+        let mut bodies = Vec::new();
+        for _ in 0..2 {
+            let mut request = server.recv_timeout(Duration::from_secs(10)).unwrap().unwrap();
+            assert_eq!("/hook", request.url());
+            let mut body = String::new();
+            request.as_reader().read_to_string(&mut body).unwrap();
+            bodies.push(serde_json::from_str::<serde_json::Value>(&body).unwrap());
+            request.respond(tiny_http::Response::empty(204)).unwrap();
+        }
+        let message = "Diamond Red Deer on Hirschfelden Hunting Reserve: score 250.00, 180.5 kg, Piebald fur";
+        assert_eq!(message, bodies[0]["message"]);
+        assert_eq!("trophy_stored", bodies[0]["event"]["type"]);
+        assert_eq!(json!({ "content": message }), bodies[1]);
+        assert!(server.recv_timeout(Duration::from_millis(200)).unwrap().is_none());
+    }
+}